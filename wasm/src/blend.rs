@@ -0,0 +1,327 @@
+// blend modes and source-over compositing as defined by the w3c
+// compositing and blending level 1 spec, matching canvas globalCompositeOperation
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+    Hue,
+    Saturation,
+    Color,
+    Luminosity,
+}
+
+impl BlendMode {
+    pub fn from_string(s: &str) -> Option<BlendMode> {
+        match s {
+            "normal" => Some(BlendMode::Normal),
+            "multiply" => Some(BlendMode::Multiply),
+            "screen" => Some(BlendMode::Screen),
+            "overlay" => Some(BlendMode::Overlay),
+            "darken" => Some(BlendMode::Darken),
+            "lighten" => Some(BlendMode::Lighten),
+            "color-dodge" => Some(BlendMode::ColorDodge),
+            "color-burn" => Some(BlendMode::ColorBurn),
+            "hard-light" => Some(BlendMode::HardLight),
+            "soft-light" => Some(BlendMode::SoftLight),
+            "difference" => Some(BlendMode::Difference),
+            "exclusion" => Some(BlendMode::Exclusion),
+            "hue" => Some(BlendMode::Hue),
+            "saturation" => Some(BlendMode::Saturation),
+            "color" => Some(BlendMode::Color),
+            "luminosity" => Some(BlendMode::Luminosity),
+            _ => None,
+        }
+    }
+}
+
+fn multiply(cb: f32, cs: f32) -> f32 {
+    cb * cs
+}
+
+fn screen(cb: f32, cs: f32) -> f32 {
+    cb + cs - cb * cs
+}
+
+fn hard_light(cb: f32, cs: f32) -> f32 {
+    if cs <= 0.5 {
+        multiply(cb, 2.0 * cs)
+    } else {
+        screen(cb, 2.0 * cs - 1.0)
+    }
+}
+
+fn color_dodge(cb: f32, cs: f32) -> f32 {
+    if cb <= 0.0 {
+        0.0
+    } else if cs >= 1.0 {
+        1.0
+    } else {
+        (cb / (1.0 - cs)).min(1.0)
+    }
+}
+
+fn color_burn(cb: f32, cs: f32) -> f32 {
+    if cb >= 1.0 {
+        1.0
+    } else if cs <= 0.0 {
+        0.0
+    } else {
+        1.0 - ((1.0 - cb) / cs).min(1.0)
+    }
+}
+
+fn soft_light(cb: f32, cs: f32) -> f32 {
+    if cs <= 0.5 {
+        cb - (1.0 - 2.0 * cs) * cb * (1.0 - cb)
+    } else {
+        let d = if cb <= 0.25 {
+            ((16.0 * cb - 12.0) * cb + 4.0) * cb
+        } else {
+            cb.sqrt()
+        };
+        cb + (2.0 * cs - 1.0) * (d - cb)
+    }
+}
+
+fn separable(mode: BlendMode, cb: f32, cs: f32) -> f32 {
+    match mode {
+        BlendMode::Multiply => multiply(cb, cs),
+        BlendMode::Screen => screen(cb, cs),
+        BlendMode::Overlay => hard_light(cs, cb),
+        BlendMode::Darken => cb.min(cs),
+        BlendMode::Lighten => cb.max(cs),
+        BlendMode::ColorDodge => color_dodge(cb, cs),
+        BlendMode::ColorBurn => color_burn(cb, cs),
+        BlendMode::HardLight => hard_light(cb, cs),
+        BlendMode::SoftLight => soft_light(cb, cs),
+        BlendMode::Difference => (cb - cs).abs(),
+        BlendMode::Exclusion => cb + cs - 2.0 * cb * cs,
+        _ => cs,
+    }
+}
+
+fn lum(c: [f32; 3]) -> f32 {
+    0.3 * c[0] + 0.59 * c[1] + 0.11 * c[2]
+}
+
+fn clip_color(c: [f32; 3]) -> [f32; 3] {
+    let l = lum(c);
+    let n = c[0].min(c[1]).min(c[2]);
+    let x = c[0].max(c[1]).max(c[2]);
+    let mut out = c;
+    if n < 0.0 {
+        for v in out.iter_mut() {
+            *v = l + (*v - l) * l / (l - n);
+        }
+    }
+    if x > 1.0 {
+        for v in out.iter_mut() {
+            *v = l + (*v - l) * (1.0 - l) / (x - l);
+        }
+    }
+    out
+}
+
+fn set_lum(c: [f32; 3], l: f32) -> [f32; 3] {
+    let d = l - lum(c);
+    clip_color([c[0] + d, c[1] + d, c[2] + d])
+}
+
+fn sat(c: [f32; 3]) -> f32 {
+    c[0].max(c[1]).max(c[2]) - c[0].min(c[1]).min(c[2])
+}
+
+fn set_sat(c: [f32; 3], s: f32) -> [f32; 3] {
+    let mut idx = [0usize, 1, 2];
+    idx.sort_by(|&a, &b| c[a].total_cmp(&c[b]));
+    let (min, mid, max) = (idx[0], idx[1], idx[2]);
+
+    let mut out = [0.0f32; 3];
+    if c[max] > c[min] {
+        out[mid] = (c[mid] - c[min]) * s / (c[max] - c[min]);
+        out[max] = s;
+    }
+    out
+}
+
+// B(Cb, Cs) on straight colours in 0..1
+pub fn blend_color(mode: BlendMode, cb: [f32; 3], cs: [f32; 3]) -> [f32; 3] {
+    match mode {
+        BlendMode::Normal => cs,
+        BlendMode::Hue => set_lum(set_sat(cs, sat(cb)), lum(cb)),
+        BlendMode::Saturation => set_lum(set_sat(cb, sat(cs)), lum(cb)),
+        BlendMode::Color => set_lum(cs, lum(cb)),
+        BlendMode::Luminosity => set_lum(cb, lum(cs)),
+        _ => [
+            separable(mode, cb[0], cs[0]),
+            separable(mode, cb[1], cs[1]),
+            separable(mode, cb[2], cs[2]),
+        ],
+    }
+}
+
+// blends the source into the backdrop and composites it source-over
+pub fn composite_pixel(
+    backdrop: [u8; 4],
+    source: [u8; 4],
+    opacity: f32,
+    mode: BlendMode,
+) -> [u8; 4] {
+    let ab = backdrop[3] as f32 / 255.0;
+    let a_s = source[3] as f32 / 255.0 * opacity;
+    if a_s <= 0.0 {
+        return backdrop;
+    }
+
+    let cb = [
+        backdrop[0] as f32 / 255.0,
+        backdrop[1] as f32 / 255.0,
+        backdrop[2] as f32 / 255.0,
+    ];
+    let cs = [
+        source[0] as f32 / 255.0,
+        source[1] as f32 / 255.0,
+        source[2] as f32 / 255.0,
+    ];
+    let blended = blend_color(mode, cb, cs);
+
+    let ao = a_s + ab * (1.0 - a_s);
+    let mut out = [0u8; 4];
+    for c in 0..3 {
+        // the blend only applies where the backdrop is present
+        let mixed = (1.0 - ab) * cs[c] + ab * blended[c].clamp(0.0, 1.0);
+        let co = a_s * mixed + ab * cb[c] * (1.0 - a_s);
+        out[c] = (co / ao * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+    out[3] = (ao * 255.0).round().clamp(0.0, 255.0) as u8;
+    out
+}
+
+// draws the overlay onto a copy of the base with its top-left corner at (x, y)
+#[allow(clippy::too_many_arguments)]
+pub fn composite(
+    base: &[u8],
+    base_width: u32,
+    base_height: u32,
+    overlay: &[u8],
+    overlay_width: u32,
+    overlay_height: u32,
+    x: i32,
+    y: i32,
+    opacity: f32,
+    mode: BlendMode,
+) -> Vec<u8> {
+    let mut result = base.to_vec();
+    composite_into(
+        &mut result,
+        base_width,
+        base_height,
+        overlay,
+        overlay_width,
+        overlay_height,
+        x,
+        y,
+        opacity,
+        mode,
+    );
+    result
+}
+
+// in-place variant of `composite`, only the overlapping area is touched
+#[allow(clippy::too_many_arguments)]
+pub fn composite_into(
+    base: &mut [u8],
+    base_width: u32,
+    base_height: u32,
+    overlay: &[u8],
+    overlay_width: u32,
+    overlay_height: u32,
+    x: i32,
+    y: i32,
+    opacity: f32,
+    mode: BlendMode,
+) {
+    let opacity = opacity.clamp(0.0, 1.0);
+    let x0 = x.max(0);
+    let y0 = y.max(0);
+    let x1 = (x as i64 + overlay_width as i64).min(base_width as i64) as i32;
+    let y1 = (y as i64 + overlay_height as i64).min(base_height as i64) as i32;
+
+    for by in y0..y1 {
+        for bx in x0..x1 {
+            let ox = (bx - x) as u32;
+            let oy = (by - y) as u32;
+            let src = ((oy * overlay_width + ox) * 4) as usize;
+            let dst = ((by as u32 * base_width + bx as u32) * 4) as usize;
+
+            let backdrop = [base[dst], base[dst + 1], base[dst + 2], base[dst + 3]];
+            let source = [
+                overlay[src],
+                overlay[src + 1],
+                overlay[src + 2],
+                overlay[src + 3],
+            ];
+            base[dst..dst + 4].copy_from_slice(&composite_pixel(backdrop, source, opacity, mode));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_separable_modes() {
+        let cb = [0.2, 0.5, 1.0];
+        assert_eq!(blend_color(BlendMode::Multiply, cb, [1.0, 1.0, 1.0]), cb);
+        assert_eq!(blend_color(BlendMode::Screen, cb, [0.0, 0.0, 0.0]), cb);
+        assert_eq!(blend_color(BlendMode::Difference, cb, cb), [0.0, 0.0, 0.0]);
+        assert_eq!(
+            blend_color(BlendMode::Overlay, [0.25, 0.75, 0.5], [0.5, 0.5, 0.5]),
+            [0.25, 0.75, 0.5]
+        );
+    }
+
+    #[test]
+    fn test_luminosity_keeps_backdrop_hue() {
+        let out = blend_color(BlendMode::Luminosity, [1.0, 0.0, 0.0], [0.3, 0.3, 0.3]);
+        assert!((lum(out) - 0.3).abs() < 1e-5);
+        assert!(out[0] > out[1] && out[1] == out[2]);
+    }
+
+    #[test]
+    fn test_source_over() {
+        let red = [255, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+        assert_eq!(composite_pixel(red, blue, 1.0, BlendMode::Normal), blue);
+        assert_eq!(
+            composite_pixel(red, blue, 0.5, BlendMode::Normal),
+            [128, 0, 128, 255]
+        );
+        // nothing underneath, so the blend mode has no effect
+        assert_eq!(
+            composite_pixel([0, 0, 0, 0], blue, 1.0, BlendMode::Multiply),
+            blue
+        );
+    }
+
+    #[test]
+    fn test_composite_clips_to_base() {
+        let base = vec![0u8; 2 * 2 * 4];
+        let overlay = vec![255u8; 2 * 2 * 4];
+        let out = composite(&base, 2, 2, &overlay, 2, 2, 1, -1, 1.0, BlendMode::Normal);
+        let alphas: Vec<u8> = out.chunks_exact(4).map(|p| p[3]).collect();
+        assert_eq!(alphas, vec![0, 255, 0, 0]);
+    }
+}
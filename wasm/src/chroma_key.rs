@@ -0,0 +1,111 @@
+use crate::utils::smoothstep;
+
+// keying happens on the cb/cr plane so shading on the backdrop does not matter
+
+// chroma distance between the two furthest apart rgb colours, pure green and
+// magenta, so a tolerance of 1 reaches from any key to every colour
+const CHROMA_RANGE: f32 = 1.0673;
+
+// bt.601 luma and centred chroma, all inputs in 0..1
+fn to_ycbcr(r: f32, g: f32, b: f32) -> [f32; 3] {
+    let y = 0.299 * r + 0.587 * g + 0.114 * b;
+    [y, (b - y) * 0.564, (r - y) * 0.713]
+}
+
+fn from_ycbcr(c: [f32; 3]) -> [f32; 3] {
+    let r = c[0] + 1.403 * c[2];
+    let g = c[0] - 0.344 * c[1] - 0.714 * c[2];
+    let b = c[0] + 1.773 * c[1];
+    [r, g, b]
+}
+
+// pixels within `tolerance` of the key become transparent, alpha ramps back up
+// over `softness`, and `spill` strips the key hue from what is left
+pub fn chroma_key(data: &[u8], key: [u8; 4], tolerance: f32, softness: f32, spill: f32) -> Vec<u8> {
+    let key = to_ycbcr(
+        key[0] as f32 / 255.0,
+        key[1] as f32 / 255.0,
+        key[2] as f32 / 255.0,
+    );
+    let key_len = (key[1] * key[1] + key[2] * key[2]).sqrt();
+    let key_dir = if key_len > 0.0 {
+        [key[1] / key_len, key[2] / key_len]
+    } else {
+        [0.0, 0.0]
+    };
+
+    let mut result = data.to_vec();
+    for pixel in result.chunks_exact_mut(4) {
+        let mut c = to_ycbcr(
+            pixel[0] as f32 / 255.0,
+            pixel[1] as f32 / 255.0,
+            pixel[2] as f32 / 255.0,
+        );
+        let d = ((c[1] - key[1]).powi(2) + (c[2] - key[2]).powi(2)).sqrt() / CHROMA_RANGE;
+        // without softness the matte is a hard cut at the tolerance
+        let matte = if softness > 0.0 {
+            smoothstep(tolerance, tolerance + softness, d)
+        } else if d < tolerance {
+            0.0
+        } else {
+            1.0
+        };
+
+        let along = c[1] * key_dir[0] + c[2] * key_dir[1];
+        if along > 0.0 && spill > 0.0 {
+            c[1] -= key_dir[0] * along * spill;
+            c[2] -= key_dir[1] * along * spill;
+            let rgb = from_ycbcr(c);
+            for i in 0..3 {
+                pixel[i] = (rgb[i] * 255.0).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+        pixel[3] = (pixel[3] as f32 * matte).round() as u8;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GREEN: [u8; 4] = [0, 200, 0, 255];
+
+    #[test]
+    fn test_key_removes_backdrop_and_keeps_subject() {
+        let data = [0, 200, 0, 255, 0, 180, 10, 255, 200, 60, 40, 255];
+        let out = chroma_key(&data, GREEN, 0.1, 0.1, 0.0);
+        // the slightly different green is within tolerance of the key
+        assert_eq!(out[3], 0);
+        assert_eq!(out[7], 0);
+        assert_eq!(&out[8..12], &data[8..12]);
+    }
+
+    #[test]
+    fn test_spill_suppression() {
+        // a grey edge pixel tinted green by the backdrop
+        let data = [120, 160, 120, 255];
+        let out = chroma_key(&data, GREEN, 0.05, 0.05, 1.0);
+        assert_eq!(out[3], 255);
+        assert!(out[1] < 160);
+        assert!(out[1].abs_diff(out[0]) <= 2 && out[0].abs_diff(out[2]) <= 2);
+    }
+
+    #[test]
+    fn test_softness_ramps_alpha() {
+        let data = [60, 200, 60, 255];
+        let hard = chroma_key(&data, GREEN, 0.1, 0.0, 0.0);
+        let soft = chroma_key(&data, GREEN, 0.0, 0.5, 0.0);
+        assert_eq!(hard[3], 255);
+        assert!(soft[3] > 0 && soft[3] < 255);
+    }
+
+    #[test]
+    fn test_full_tolerance_reaches_the_opposite_corner() {
+        // magenta is as far from a pure green key as any colour gets
+        let data = [255, 0, 255, 255];
+        let key = [0, 255, 0, 255];
+        assert_eq!(chroma_key(&data, key, 0.999, 0.0, 0.0)[3], 255);
+        assert_eq!(chroma_key(&data, key, 1.0, 0.0, 0.0)[3], 0);
+    }
+}
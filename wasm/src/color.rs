@@ -0,0 +1,112 @@
+// colour space conversions, all on straight (non-premultiplied) srgb
+
+// d65 reference white
+const WHITE: [f32; 3] = [0.950_47, 1.0, 1.088_83];
+
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn lab_f(t: f32) -> f32 {
+    const DELTA: f32 = 6.0 / 29.0;
+    if t > DELTA * DELTA * DELTA {
+        t.cbrt()
+    } else {
+        t / (3.0 * DELTA * DELTA) + 4.0 / 29.0
+    }
+}
+
+// cie l*a*b*, l in 0..100
+pub fn rgb_to_lab(r: u8, g: u8, b: u8) -> [f32; 3] {
+    let r = srgb_to_linear(r as f32 / 255.0);
+    let g = srgb_to_linear(g as f32 / 255.0);
+    let b = srgb_to_linear(b as f32 / 255.0);
+
+    let x = 0.412_456_4 * r + 0.357_576_1 * g + 0.180_437_5 * b;
+    let y = 0.212_672_9 * r + 0.715_152_2 * g + 0.072_175 * b;
+    let z = 0.019_333_9 * r + 0.119_192 * g + 0.950_304_1 * b;
+
+    let fx = lab_f(x / WHITE[0]);
+    let fy = lab_f(y / WHITE[1]);
+    let fz = lab_f(z / WHITE[2]);
+
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+// oklab (ottosson 2020), l in 0..1
+pub fn rgb_to_oklab(r: u8, g: u8, b: u8) -> [f32; 3] {
+    let r = srgb_to_linear(r as f32 / 255.0);
+    let g = srgb_to_linear(g as f32 / 255.0);
+    let b = srgb_to_linear(b as f32 / 255.0);
+
+    let l = (0.412_221_47 * r + 0.536_332_55 * g + 0.051_445_995 * b).cbrt();
+    let m = (0.211_903_5 * r + 0.680_699_5 * g + 0.107_396_96 * b).cbrt();
+    let s = (0.088_302_46 * r + 0.281_718_85 * g + 0.629_978_7 * b).cbrt();
+
+    [
+        0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+        1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+        0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+    ]
+}
+
+// back to srgb, out-of-gamut colours are clipped per channel
+pub fn oklab_to_rgb(lab: [f32; 3]) -> [u8; 3] {
+    let l = lab[0] + 0.396_337_78 * lab[1] + 0.215_803_76 * lab[2];
+    let m = lab[0] - 0.105_561_346 * lab[1] - 0.063_854_17 * lab[2];
+    let s = lab[0] - 0.089_484_18 * lab[1] - 1.291_485_5 * lab[2];
+    let (l, m, s) = (l * l * l, m * m * m, s * s * s);
+
+    let rgb = [
+        4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s,
+        -1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s,
+        -0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s,
+    ];
+    rgb.map(|c| (linear_to_srgb(c.clamp(0.0, 1.0)) * 255.0).round() as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lab_reference_points() {
+        let white = rgb_to_lab(255, 255, 255);
+        assert!((white[0] - 100.0).abs() < 0.01);
+        assert!(white[1].abs() < 0.01 && white[2].abs() < 0.01);
+
+        let black = rgb_to_lab(0, 0, 0);
+        assert!(black[0].abs() < 0.01);
+
+        let red = rgb_to_lab(255, 0, 0);
+        assert!((red[0] - 53.24).abs() < 0.05);
+        assert!((red[1] - 80.09).abs() < 0.05);
+        assert!((red[2] - 67.20).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_oklab_round_trip() {
+        let white = rgb_to_oklab(255, 255, 255);
+        assert!((white[0] - 1.0).abs() < 1e-3);
+        assert!(white[1].abs() < 1e-3 && white[2].abs() < 1e-3);
+
+        for rgb in [[255, 0, 0], [12, 200, 99], [128, 128, 128], [3, 7, 250]] {
+            let back = oklab_to_rgb(rgb_to_oklab(rgb[0], rgb[1], rgb[2]));
+            for c in 0..3 {
+                assert!(back[c].abs_diff(rgb[c]) <= 1, "{:?} -> {:?}", rgb, back);
+            }
+        }
+    }
+}
@@ -0,0 +1,401 @@
+// effects built from the alpha channel, drawn on a canvas grown to fit them
+
+use crate::blend::{composite_into, BlendMode};
+use crate::crop_image;
+use crate::layout::place;
+use crate::mask::{distance_transform, gaussian_blur_plane, radius_to_sigma};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StrokePosition {
+    Inside,
+    Center,
+    Outside,
+}
+
+impl StrokePosition {
+    pub fn from_string(s: &str) -> Option<StrokePosition> {
+        match s {
+            "inside" => Some(StrokePosition::Inside),
+            "center" => Some(StrokePosition::Center),
+            "outside" => Some(StrokePosition::Outside),
+            _ => None,
+        }
+    }
+}
+
+// the output canvas and where the original image's top-left corner ended up
+pub struct Expanded {
+    pub data: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+// the image alpha in 0..1, placed at (x, y) on an empty canvas
+#[allow(clippy::too_many_arguments)]
+fn alpha_plane(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    canvas_width: u32,
+    canvas_height: u32,
+    x: u32,
+    y: u32,
+) -> Vec<f32> {
+    let mut plane = vec![0.0f32; (canvas_width * canvas_height) as usize];
+    for row in 0..height {
+        for col in 0..width {
+            let src = ((row * width + col) * 4 + 3) as usize;
+            plane[((y + row) * canvas_width + x + col) as usize] = data[src] as f32 / 255.0;
+        }
+    }
+    plane
+}
+
+// signed distance to the alpha edge, negative inside; pixel centres next to
+// the edge sit half a pixel from it
+fn signed_distance(alpha: &[f32], width: u32, height: u32) -> Vec<f32> {
+    let inside: Vec<bool> = alpha.iter().map(|&a| a >= 0.5).collect();
+    let outside: Vec<bool> = inside.iter().map(|&b| !b).collect();
+    let to_inside = distance_transform(&inside, width, height);
+    let to_outside = distance_transform(&outside, width, height);
+    (0..alpha.len())
+        .map(|i| {
+            if inside[i] {
+                0.5 - to_outside[i]
+            } else {
+                to_inside[i] - 0.5
+            }
+        })
+        .collect()
+}
+
+// dilates a soft alpha by `spread` pixels with an anti-aliased edge
+fn spread_alpha(alpha: &[f32], width: u32, height: u32, spread: f32) -> Vec<f32> {
+    if spread <= 0.0 {
+        return alpha.to_vec();
+    }
+    let sd = signed_distance(alpha, width, height);
+    alpha
+        .iter()
+        .zip(&sd)
+        .map(|(&a, &d)| a.max((spread + 0.5 - d).clamp(0.0, 1.0)))
+        .collect()
+}
+
+fn color_layer(coverage: &[f32], color: [u8; 4], opacity: f32) -> Vec<u8> {
+    let alpha = color[3] as f32 / 255.0 * opacity;
+    coverage
+        .iter()
+        .flat_map(|&c| {
+            let a = (c.clamp(0.0, 1.0) * alpha * 255.0).round() as u8;
+            [color[0], color[1], color[2], a]
+        })
+        .collect()
+}
+
+// shadow of the alpha shifted by the offset, grown by `spread` and blurred
+// over `blur` pixels, drawn underneath the image
+#[allow(clippy::too_many_arguments)]
+pub fn drop_shadow(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    offset_x: i32,
+    offset_y: i32,
+    blur: f32,
+    spread: f32,
+    color: [u8; 4],
+    opacity: f32,
+) -> Expanded {
+    let reach = (blur + spread).ceil() as i64;
+    let (dx, dy) = (offset_x as i64, offset_y as i64);
+    let left = (reach - dx).max(0) as u32;
+    let right = (reach + dx).max(0) as u32;
+    let top = (reach - dy).max(0) as u32;
+    let bottom = (reach + dy).max(0) as u32;
+    let canvas_width = width + left + right;
+    let canvas_height = height + top + bottom;
+
+    let shadow = alpha_plane(
+        data,
+        width,
+        height,
+        canvas_width,
+        canvas_height,
+        (left as i64 + dx) as u32,
+        (top as i64 + dy) as u32,
+    );
+    let shadow = spread_alpha(&shadow, canvas_width, canvas_height, spread);
+    let shadow = gaussian_blur_plane(&shadow, canvas_width, canvas_height, radius_to_sigma(blur));
+
+    let mut canvas = color_layer(&shadow, color, opacity);
+    place(&mut canvas, canvas_width, data, width, height, left, top);
+    Expanded {
+        data: canvas,
+        width: canvas_width,
+        height: canvas_height,
+        x: left,
+        y: top,
+    }
+}
+
+// a shadow without an offset, usually in a light colour
+pub fn outer_glow(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    radius: f32,
+    spread: f32,
+    color: [u8; 4],
+    opacity: f32,
+) -> Expanded {
+    drop_shadow(data, width, height, 0, 0, radius, spread, color, opacity)
+}
+
+// colour fading inwards from the alpha edge, the canvas keeps its size
+pub fn inner_glow(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    radius: f32,
+    color: [u8; 4],
+    opacity: f32,
+) -> Vec<u8> {
+    // work with a transparent margin so the image bounds count as an edge
+    let margin = radius.ceil() as u32 + 1;
+    let padded_w = width + 2 * margin;
+    let padded_h = height + 2 * margin;
+    let alpha = alpha_plane(data, width, height, padded_w, padded_h, margin, margin);
+    let outside: Vec<f32> = alpha.iter().map(|&a| 1.0 - a).collect();
+    let glow = gaussian_blur_plane(&outside, padded_w, padded_h, radius_to_sigma(radius));
+
+    let mut coverage = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let i = ((y + margin) * padded_w + x + margin) as usize;
+            // scaled so the edge itself gets the full colour
+            coverage.push((glow[i] * 2.0).min(1.0) * alpha[i]);
+        }
+    }
+
+    let mut result = data.to_vec();
+    let layer = color_layer(&coverage, color, opacity);
+    composite_into(
+        &mut result,
+        width,
+        height,
+        &layer,
+        width,
+        height,
+        0,
+        0,
+        1.0,
+        BlendMode::Normal,
+    );
+    // keep the original coverage, the glow only recolours
+    for (out, src) in result.chunks_exact_mut(4).zip(data.chunks_exact(4)) {
+        out[3] = src[3];
+    }
+    result
+}
+
+// outline of the given width along the alpha edge, anti-aliased from the distance field
+pub fn stroke(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    stroke_width: f32,
+    color: [u8; 4],
+    position: StrokePosition,
+) -> Expanded {
+    // an inside stroke keeps the size, but still needs a transparent border
+    // so the image bounds count as an edge
+    let margin = match position {
+        StrokePosition::Inside => 1,
+        StrokePosition::Center => (stroke_width / 2.0).ceil() as u32 + 1,
+        StrokePosition::Outside => stroke_width.ceil() as u32 + 1,
+    };
+    let canvas_width = width + 2 * margin;
+    let canvas_height = height + 2 * margin;
+    let alpha = alpha_plane(
+        data,
+        width,
+        height,
+        canvas_width,
+        canvas_height,
+        margin,
+        margin,
+    );
+    let sd = signed_distance(&alpha, canvas_width, canvas_height);
+
+    // the band of signed distances the stroke covers
+    let (inner, outer) = match position {
+        StrokePosition::Inside => (-stroke_width, f32::INFINITY),
+        StrokePosition::Center => (-stroke_width / 2.0, stroke_width / 2.0),
+        // runs under the image as well so semi-transparent edges show no gap
+        StrokePosition::Outside => (f32::NEG_INFINITY, stroke_width),
+    };
+    let coverage: Vec<f32> = sd
+        .iter()
+        .zip(&alpha)
+        .map(|(&d, &a)| {
+            let c = (outer + 0.5 - d).clamp(0.0, 1.0) * (d - inner + 0.5).clamp(0.0, 1.0);
+            if position == StrokePosition::Inside {
+                c * a
+            } else {
+                c
+            }
+        })
+        .collect();
+    let layer = color_layer(&coverage, color, 1.0);
+
+    let mut canvas = vec![0u8; (canvas_width * canvas_height * 4) as usize];
+    if position == StrokePosition::Outside {
+        canvas.copy_from_slice(&layer);
+        place(
+            &mut canvas,
+            canvas_width,
+            data,
+            width,
+            height,
+            margin,
+            margin,
+        );
+    } else {
+        place(
+            &mut canvas,
+            canvas_width,
+            data,
+            width,
+            height,
+            margin,
+            margin,
+        );
+        composite_into(
+            &mut canvas,
+            canvas_width,
+            canvas_height,
+            &layer,
+            canvas_width,
+            canvas_height,
+            0,
+            0,
+            1.0,
+            BlendMode::Normal,
+        );
+    }
+
+    if position == StrokePosition::Inside {
+        return Expanded {
+            data: crop_image(&canvas, canvas_width, margin, margin, width, height),
+            width,
+            height,
+            x: 0,
+            y: 0,
+        };
+    }
+    Expanded {
+        data: canvas,
+        width: canvas_width,
+        height: canvas_height,
+        x: margin,
+        y: margin,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // opaque black square of `size` in the middle of a transparent image
+    fn square(width: u32, size: u32) -> Vec<u8> {
+        let start = (width - size) / 2;
+        let mut data = vec![0u8; (width * width * 4) as usize];
+        for y in start..start + size {
+            for x in start..start + size {
+                let i = ((y * width + x) * 4) as usize;
+                data[i..i + 4].copy_from_slice(&[0, 0, 0, 255]);
+            }
+        }
+        data
+    }
+
+    fn alpha_at(e: &Expanded, x: u32, y: u32) -> u8 {
+        e.data[((y * e.width + x) * 4 + 3) as usize]
+    }
+
+    #[test]
+    fn test_drop_shadow_grows_canvas() {
+        let data = square(4, 4);
+        let out = drop_shadow(&data, 4, 4, 3, 2, 0.0, 0.0, [255, 0, 0, 255], 0.5);
+        assert_eq!((out.width, out.height, out.x, out.y), (7, 6, 0, 0));
+        // the image is untouched and the hard shadow peeks out at the offset
+        assert_eq!(&out.data[0..4], &[0, 0, 0, 255]);
+        let i = ((5 * 7 + 6) * 4) as usize;
+        assert_eq!(&out.data[i..i + 4], &[255, 0, 0, 128]);
+        assert_eq!(alpha_at(&out, 6, 0), 0);
+    }
+
+    #[test]
+    fn test_stroke_positions() {
+        let data = square(6, 2);
+        let outside = stroke(
+            &data,
+            6,
+            6,
+            2.0,
+            [255, 255, 255, 255],
+            StrokePosition::Outside,
+        );
+        assert_eq!((outside.width, outside.x), (12, 3));
+        // two pixels out from the square's left edge at x = 5
+        assert_eq!(alpha_at(&outside, 3, 6), 255);
+        assert_eq!(alpha_at(&outside, 2, 6), 0);
+        assert_eq!(
+            &outside.data[((6 * 12 + 5) * 4) as usize..][..3],
+            &[0, 0, 0]
+        );
+
+        let inside = stroke(
+            &data,
+            6,
+            6,
+            1.0,
+            [255, 255, 255, 255],
+            StrokePosition::Inside,
+        );
+        assert_eq!(inside.width, 6);
+        // the whole 2x2 square is within a pixel of its edge
+        assert_eq!(
+            &inside.data[((2 * 6 + 2) * 4) as usize..][..4],
+            &[255, 255, 255, 255]
+        );
+        assert_eq!(alpha_at(&inside, 1, 2), 0);
+
+        // on an opaque photo the image bounds are the edge
+        let photo = [0u8, 0, 0, 255].repeat(16);
+        let framed = stroke(
+            &photo,
+            4,
+            4,
+            1.0,
+            [255, 255, 255, 255],
+            StrokePosition::Inside,
+        );
+        assert_eq!(&framed.data[0..4], &[255, 255, 255, 255]);
+        assert_eq!(&framed.data[((4 + 1) * 4) as usize..][..4], &[0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_inner_glow_keeps_alpha() {
+        let data = square(8, 6);
+        let out = inner_glow(&data, 8, 8, 2.0, [255, 255, 255, 255], 1.0);
+        let alphas: Vec<u8> = out.chunks_exact(4).map(|p| p[3]).collect();
+        let original: Vec<u8> = data.chunks_exact(4).map(|p| p[3]).collect();
+        assert_eq!(alphas, original);
+        // brighter at the edge than in the centre
+        assert!(out[((8 + 1) * 4) as usize] > out[((4 * 8 + 4) * 4) as usize]);
+    }
+}
@@ -0,0 +1,293 @@
+use std::fmt;
+
+use wasm_bindgen::prelude::*;
+
+// 8192 x 8192, comfortably above any export preset
+pub const DEFAULT_MAX_PIXELS: u64 = 8192 * 8192;
+
+// pixel offsets are computed in u32 byte indices, so rgba buffers must stay below 4gb
+pub const PIXEL_LIMIT_CEILING: u64 = u32::MAX as u64 / 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImageError {
+    NotInitialized,
+    BufferSizeMismatch {
+        expected: u64,
+        actual: u64,
+    },
+    ZeroDimension {
+        width: u32,
+        height: u32,
+    },
+    OutOfBounds {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        image_width: u32,
+        image_height: u32,
+    },
+    UnknownFilter(String),
+    TooLarge {
+        pixels: u64,
+        max_pixels: u64,
+    },
+    InvalidArgument(String),
+}
+
+impl ImageError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ImageError::NotInitialized => "NotInitialized",
+            ImageError::BufferSizeMismatch { .. } => "BufferSizeMismatch",
+            ImageError::ZeroDimension { .. } => "ZeroDimension",
+            ImageError::OutOfBounds { .. } => "OutOfBounds",
+            ImageError::UnknownFilter(_) => "UnknownFilter",
+            ImageError::TooLarge { .. } => "TooLarge",
+            ImageError::InvalidArgument(_) => "InvalidArgument",
+        }
+    }
+
+    pub fn invalid(message: impl Into<String>) -> ImageError {
+        ImageError::InvalidArgument(message.into())
+    }
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::NotInitialized => write!(f, "processor not initialized"),
+            ImageError::BufferSizeMismatch { expected, actual } => {
+                write!(f, "buffer has {} bytes, expected {}", actual, expected)
+            }
+            ImageError::ZeroDimension { width, height } => {
+                write!(f, "dimensions must be non-zero, got {}x{}", width, height)
+            }
+            ImageError::OutOfBounds {
+                x,
+                y,
+                width,
+                height,
+                image_width,
+                image_height,
+            } => write!(
+                f,
+                "rectangle {}x{} at ({}, {}) is outside the {}x{} image",
+                width, height, x, y, image_width, image_height
+            ),
+            ImageError::UnknownFilter(name) => write!(f, "unknown filter: {}", name),
+            ImageError::TooLarge { pixels, max_pixels } => write!(
+                f,
+                "image has {} pixels, the limit is {}",
+                pixels, max_pixels
+            ),
+            ImageError::InvalidArgument(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+fn set(target: &JsValue, key: &str, value: JsValue) {
+    let _ = js_sys::Reflect::set(target, &JsValue::from_str(key), &value);
+}
+
+// thrown on the js side as an Error with `code` and the offending values attached
+impl From<ImageError> for JsValue {
+    fn from(err: ImageError) -> JsValue {
+        let js_err: JsValue = js_sys::Error::new(&err.to_string()).into();
+        set(&js_err, "name", JsValue::from_str("ImageError"));
+        set(&js_err, "code", JsValue::from_str(err.code()));
+
+        match &err {
+            ImageError::BufferSizeMismatch { expected, actual } => {
+                set(&js_err, "expected", JsValue::from_f64(*expected as f64));
+                set(&js_err, "actual", JsValue::from_f64(*actual as f64));
+            }
+            ImageError::ZeroDimension { width, height } => {
+                set(&js_err, "width", JsValue::from(*width));
+                set(&js_err, "height", JsValue::from(*height));
+            }
+            ImageError::OutOfBounds {
+                x,
+                y,
+                width,
+                height,
+                image_width,
+                image_height,
+            } => {
+                set(&js_err, "x", JsValue::from(*x));
+                set(&js_err, "y", JsValue::from(*y));
+                set(&js_err, "width", JsValue::from(*width));
+                set(&js_err, "height", JsValue::from(*height));
+                set(&js_err, "imageWidth", JsValue::from(*image_width));
+                set(&js_err, "imageHeight", JsValue::from(*image_height));
+            }
+            ImageError::UnknownFilter(name) => {
+                set(&js_err, "filter", JsValue::from_str(name));
+            }
+            ImageError::TooLarge { pixels, max_pixels } => {
+                set(&js_err, "pixels", JsValue::from_f64(*pixels as f64));
+                set(&js_err, "maxPixels", JsValue::from_f64(*max_pixels as f64));
+            }
+            ImageError::NotInitialized | ImageError::InvalidArgument(_) => {}
+        }
+
+        js_err
+    }
+}
+
+// checks that the dimensions are usable and under the pixel limit
+pub fn validate_dimensions(width: u32, height: u32, max_pixels: u64) -> Result<(), ImageError> {
+    if width == 0 || height == 0 {
+        return Err(ImageError::ZeroDimension { width, height });
+    }
+    let pixels = width as u64 * height as u64;
+    if pixels > max_pixels {
+        return Err(ImageError::TooLarge { pixels, max_pixels });
+    }
+    Ok(())
+}
+
+// checks an rgba buffer against its claimed dimensions
+pub fn validate_image(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    max_pixels: u64,
+) -> Result<(), ImageError> {
+    validate_dimensions(width, height, max_pixels)?;
+    validate_len(data.len(), (width as u64 * height as u64).saturating_mul(4))
+}
+
+// checks a single-channel mask against the image dimensions
+pub fn validate_mask(mask: &[u8], width: u32, height: u32) -> Result<(), ImageError> {
+    validate_len(mask.len(), width as u64 * height as u64)
+}
+
+pub fn validate_len(actual: usize, expected: u64) -> Result<(), ImageError> {
+    if actual as u64 != expected {
+        return Err(ImageError::BufferSizeMismatch {
+            expected,
+            actual: actual as u64,
+        });
+    }
+    Ok(())
+}
+
+// checks that a rectangle is non-empty and lies inside the image
+pub fn validate_rect(
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    image_width: u32,
+    image_height: u32,
+) -> Result<(), ImageError> {
+    if width == 0 || height == 0 {
+        return Err(ImageError::ZeroDimension { width, height });
+    }
+    if x as u64 + width as u64 > image_width as u64
+        || y as u64 + height as u64 > image_height as u64
+    {
+        return Err(ImageError::OutOfBounds {
+            x,
+            y,
+            width,
+            height,
+            image_width,
+            image_height,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_image() {
+        let data = vec![0u8; 2 * 3 * 4];
+        assert!(validate_image(&data, 2, 3, DEFAULT_MAX_PIXELS).is_ok());
+        assert_eq!(
+            validate_image(&data, 3, 3, DEFAULT_MAX_PIXELS),
+            Err(ImageError::BufferSizeMismatch {
+                expected: 36,
+                actual: 24
+            })
+        );
+        assert_eq!(
+            validate_image(&data, 0, 3, DEFAULT_MAX_PIXELS)
+                .unwrap_err()
+                .code(),
+            "ZeroDimension"
+        );
+        assert_eq!(
+            validate_image(&data, 2, 3, 5),
+            Err(ImageError::TooLarge {
+                pixels: 6,
+                max_pixels: 5
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate_image_does_not_overflow() {
+        let err = validate_image(&[], u32::MAX, u32::MAX, u64::MAX).unwrap_err();
+        assert_eq!(err.code(), "BufferSizeMismatch");
+    }
+
+    #[test]
+    fn test_validate_rect() {
+        assert!(validate_rect(2, 2, 3, 3, 5, 5).is_ok());
+        assert_eq!(
+            validate_rect(3, 0, 3, 1, 5, 5).unwrap_err().code(),
+            "OutOfBounds"
+        );
+        assert_eq!(
+            validate_rect(u32::MAX, 0, 2, 1, 5, 5).unwrap_err().code(),
+            "OutOfBounds"
+        );
+        assert_eq!(
+            validate_rect(0, 0, 0, 1, 5, 5).unwrap_err().code(),
+            "ZeroDimension"
+        );
+    }
+
+    #[test]
+    fn test_processor_rejects_bad_input() {
+        let mut processor = crate::ImageProcessor::default();
+        let data = vec![0u8; 4 * 4 * 4];
+
+        let short = &data[..10];
+        assert_eq!(
+            processor
+                .apply_filter(short, 4, 4, "blur", 0.5)
+                .unwrap_err()
+                .code(),
+            "BufferSizeMismatch"
+        );
+        assert_eq!(
+            processor
+                .apply_filter(&data, 4, 4, "sparkle", 0.5)
+                .unwrap_err()
+                .code(),
+            "UnknownFilter"
+        );
+        assert_eq!(
+            processor.crop(&data, 4, 4, 2, 2, 3, 1).unwrap_err().code(),
+            "OutOfBounds"
+        );
+        assert_eq!(
+            processor.resize(&data, 4, 4, 0, 2).unwrap_err().code(),
+            "ZeroDimension"
+        );
+
+        processor.set_max_pixels(8);
+        assert_eq!(
+            processor.flip(&data, 4, 4, true).unwrap_err().code(),
+            "TooLarge"
+        );
+    }
+}
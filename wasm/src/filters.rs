@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
+use crate::gradient_map::GradientStop;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum FilterType {
     Grayscale,
@@ -21,6 +23,19 @@ pub enum FilterType {
     Noise,
     Pixelate,
     ChromaticAberration,
+    GradientMap,
+    Halftone,
+    OilPaint,
+    Watercolor,
+    PencilSketch,
+    Cartoon,
+    PixelSort,
+    RgbSplit,
+    BlockDisplace,
+    ScanlineJitter,
+    Crt,
+    Vhs,
+    Scanlines,
 }
 
 impl FilterType {
@@ -44,6 +59,19 @@ impl FilterType {
             FilterType::Noise => "noise",
             FilterType::Pixelate => "pixelate",
             FilterType::ChromaticAberration => "chromatic_aberration",
+            FilterType::GradientMap => "gradient_map",
+            FilterType::Halftone => "halftone",
+            FilterType::OilPaint => "oil_paint",
+            FilterType::Watercolor => "watercolor",
+            FilterType::PencilSketch => "pencil_sketch",
+            FilterType::Cartoon => "cartoon",
+            FilterType::PixelSort => "pixel_sort",
+            FilterType::RgbSplit => "rgb_split",
+            FilterType::BlockDisplace => "block_displace",
+            FilterType::ScanlineJitter => "scanline_jitter",
+            FilterType::Crt => "crt",
+            FilterType::Vhs => "vhs",
+            FilterType::Scanlines => "scanlines",
         }
     }
 
@@ -67,11 +95,155 @@ impl FilterType {
             "noise" => Some(FilterType::Noise),
             "pixelate" => Some(FilterType::Pixelate),
             "chromatic_aberration" => Some(FilterType::ChromaticAberration),
+            "gradient_map" => Some(FilterType::GradientMap),
+            "halftone" => Some(FilterType::Halftone),
+            "oil_paint" => Some(FilterType::OilPaint),
+            "watercolor" => Some(FilterType::Watercolor),
+            "pencil_sketch" => Some(FilterType::PencilSketch),
+            "cartoon" => Some(FilterType::Cartoon),
+            "pixel_sort" => Some(FilterType::PixelSort),
+            "rgb_split" => Some(FilterType::RgbSplit),
+            "block_displace" => Some(FilterType::BlockDisplace),
+            "scanline_jitter" => Some(FilterType::ScanlineJitter),
+            "crt" => Some(FilterType::Crt),
+            "vhs" => Some(FilterType::Vhs),
+            "scanlines" => Some(FilterType::Scanlines),
             _ => None,
         }
     }
 }
 
+// settings beyond the intensity for the filters that need them, passed from js
+// as a plain object; each filter has its own keys and rejects any others
+
+// gradient_map: explicit stops, or a duotone / tritone preset from shadows to
+// highlights; with none of them the map runs black to white
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct GradientMapParams {
+    pub stops: Vec<GradientStop>,
+    pub duotone: Option<[[u8; 3]; 2]>,
+    pub tritone: Option<[[u8; 3]; 3]>,
+}
+
+// halftone: "dot", "line" or "cross" cells of `cell_size` pixels at `angle`
+// degrees; `cmyk` prints four inks at `ink_angles` (c, m, y, k), otherwise an
+// `output` of "mask" gives the ink coverage instead of an image
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct HalftoneParams {
+    pub pattern: Option<String>,
+    pub cell_size: Option<f32>,
+    pub angle: Option<f32>,
+    pub cmyk: bool,
+    pub ink_angles: Option<[f32; 4]>,
+    pub output: Option<String>,
+}
+
+// oil_paint: stroke `radius` in pixels and how strongly the calmest direction
+// wins (`sharpness`, 1..16)
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct OilPaintParams {
+    pub radius: Option<f32>,
+    pub sharpness: Option<f32>,
+}
+
+// watercolor: stroke `radius` in pixels, 0..1 strengths of its pigment
+// effects, and a `seed` that fixes the paper
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct WatercolorParams {
+    pub radius: Option<f32>,
+    pub edge_darkening: Option<f32>,
+    pub granulation: Option<f32>,
+    pub bleed: Option<f32>,
+    pub seed: Option<u32>,
+}
+
+// pencil_sketch: `radius` sets the line softness, `hatching` (0..1) strokes
+// in the darker tones along paths fixed by `seed`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct SketchParams {
+    pub radius: Option<f32>,
+    pub hatching: Option<f32>,
+    pub seed: Option<u32>,
+}
+
+// cartoon: lightness `levels` (2..32) and outline `edge_thickness` in pixels
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct CartoonParams {
+    pub levels: Option<u32>,
+    pub edge_thickness: Option<f32>,
+}
+
+// pixel_sort: runs whose luminance lies in `threshold` (low, high in 0..1)
+// are sorted by `sort_by` ("luminance" or "hue") along `angle` degrees
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct PixelSortParams {
+    pub sort_by: Option<String>,
+    pub threshold: Option<[f32; 2]>,
+    pub angle: Option<f32>,
+    pub reverse: bool,
+}
+
+// rgb_split: channel offset `distance` in pixels along `angle` degrees
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct RgbSplitParams {
+    pub distance: Option<f32>,
+    pub angle: Option<f32>,
+}
+
+// block_displace and scanline_jitter: the largest shift `distance` in pixels,
+// with `block_size` and the 0..1 `density` of moved blocks or rows under `seed`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct DisplaceParams {
+    pub distance: Option<f32>,
+    pub block_size: Option<f32>,
+    pub density: Option<f32>,
+    pub seed: Option<u32>,
+}
+
+// crt: 0..1 `curvature`, a "none", "aperture", "slot" or "shadow" `phosphor`
+// mask, 0..1 strengths for the mask, scanlines and bloom, and a line every
+// `spacing` pixels
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct CrtParams {
+    pub curvature: Option<f32>,
+    pub phosphor: Option<String>,
+    pub mask_strength: Option<f32>,
+    pub scanlines: Option<f32>,
+    pub bloom: Option<f32>,
+    pub spacing: Option<f32>,
+}
+
+// vhs: colour smear and offset in pixels, 0..1 `tracking` noise, and row
+// `wobble` in pixels, all fixed by `seed`
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct VhsParams {
+    pub chroma_bleed: Option<f32>,
+    pub tracking: Option<f32>,
+    pub color_shift: Option<f32>,
+    pub wobble: Option<f32>,
+    pub seed: Option<u32>,
+}
+
+// scanlines: a line every `spacing` pixels, `thickness` (0..1) of the period
+// thick
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "camelCase")]
+pub struct ScanlineParams {
+    pub spacing: Option<f32>,
+    pub thickness: Option<f32>,
+}
+
 #[wasm_bindgen]
 pub struct FilterMetadata {
     name: String,
@@ -281,6 +453,110 @@ pub fn get_filter_metadata(filter_type: &str) -> Option<FilterMetadata> {
             0.0,
             1.0,
         )),
+        "pixel_sort" => Some(FilterMetadata::new(
+            "Pixel Sort".into(),
+            "Sort runs of pixels into streaks".into(),
+            "effect".into(),
+            1.0,
+            0.0,
+            1.0,
+        )),
+        "rgb_split" => Some(FilterMetadata::new(
+            "RGB Split".into(),
+            "Pull the color channels apart at an angle".into(),
+            "effect".into(),
+            1.0,
+            0.0,
+            1.0,
+        )),
+        "block_displace" => Some(FilterMetadata::new(
+            "Block Displace".into(),
+            "Tear random blocks of the image sideways".into(),
+            "effect".into(),
+            1.0,
+            0.0,
+            1.0,
+        )),
+        "scanline_jitter" => Some(FilterMetadata::new(
+            "Scanline Jitter".into(),
+            "Shift bands of rows as if sync was lost".into(),
+            "effect".into(),
+            1.0,
+            0.0,
+            1.0,
+        )),
+        "crt" => Some(FilterMetadata::new(
+            "CRT".into(),
+            "Curved tube with scanlines, phosphor mask and glow".into(),
+            "effect".into(),
+            1.0,
+            0.0,
+            1.0,
+        )),
+        "vhs" => Some(FilterMetadata::new(
+            "VHS".into(),
+            "Worn tape with color bleed and tracking noise".into(),
+            "effect".into(),
+            1.0,
+            0.0,
+            1.0,
+        )),
+        "scanlines" => Some(FilterMetadata::new(
+            "Scanlines".into(),
+            "Overlay dark horizontal lines".into(),
+            "effect".into(),
+            0.5,
+            0.0,
+            1.0,
+        )),
+        "halftone" => Some(FilterMetadata::new(
+            "Halftone".into(),
+            "Print with dot, line or cross screens".into(),
+            "artistic".into(),
+            1.0,
+            0.0,
+            1.0,
+        )),
+        "oil_paint" => Some(FilterMetadata::new(
+            "Oil Paint".into(),
+            "Paint with strokes that follow the image".into(),
+            "artistic".into(),
+            1.0,
+            0.0,
+            1.0,
+        )),
+        "watercolor" => Some(FilterMetadata::new(
+            "Watercolor".into(),
+            "Soft washes with darkened edges and paper grain".into(),
+            "artistic".into(),
+            1.0,
+            0.0,
+            1.0,
+        )),
+        "pencil_sketch" => Some(FilterMetadata::new(
+            "Pencil Sketch".into(),
+            "Graphite lines on white paper with optional hatching".into(),
+            "artistic".into(),
+            1.0,
+            0.0,
+            1.0,
+        )),
+        "cartoon" => Some(FilterMetadata::new(
+            "Cartoon".into(),
+            "Flat color bands with bold outlines".into(),
+            "artistic".into(),
+            1.0,
+            0.0,
+            1.0,
+        )),
+        "gradient_map" => Some(FilterMetadata::new(
+            "Gradient Map".into(),
+            "Map tones through a color gradient".into(),
+            "color".into(),
+            1.0,
+            0.0,
+            1.0,
+        )),
         _ => None,
     }
 }
@@ -306,6 +582,19 @@ pub fn get_all_filter_types() -> Vec<JsValue> {
         JsValue::from_str("noise"),
         JsValue::from_str("pixelate"),
         JsValue::from_str("chromatic_aberration"),
+        JsValue::from_str("gradient_map"),
+        JsValue::from_str("halftone"),
+        JsValue::from_str("oil_paint"),
+        JsValue::from_str("watercolor"),
+        JsValue::from_str("pencil_sketch"),
+        JsValue::from_str("cartoon"),
+        JsValue::from_str("pixel_sort"),
+        JsValue::from_str("rgb_split"),
+        JsValue::from_str("block_displace"),
+        JsValue::from_str("scanline_jitter"),
+        JsValue::from_str("crt"),
+        JsValue::from_str("vhs"),
+        JsValue::from_str("scanlines"),
     ]
 }
 
@@ -317,6 +606,7 @@ pub fn get_filters_by_category(category: &str) -> Vec<JsValue> {
         ("invert", "color"),
         ("warm", "color"),
         ("cool", "color"),
+        ("gradient_map", "color"),
         ("brightness", "adjustment"),
         ("contrast", "adjustment"),
         ("saturation", "adjustment"),
@@ -325,11 +615,23 @@ pub fn get_filters_by_category(category: &str) -> Vec<JsValue> {
         ("vignette", "effect"),
         ("noise", "effect"),
         ("chromatic_aberration", "effect"),
+        ("pixel_sort", "effect"),
+        ("rgb_split", "effect"),
+        ("block_displace", "effect"),
+        ("scanline_jitter", "effect"),
+        ("crt", "effect"),
+        ("vhs", "effect"),
+        ("scanlines", "effect"),
         ("vintage", "preset"),
         ("posterize", "artistic"),
         ("emboss", "artistic"),
         ("edge_detect", "artistic"),
         ("pixelate", "artistic"),
+        ("halftone", "artistic"),
+        ("oil_paint", "artistic"),
+        ("watercolor", "artistic"),
+        ("pencil_sketch", "artistic"),
+        ("cartoon", "artistic"),
     ];
 
     all_filters
@@ -0,0 +1,432 @@
+use std::ops::Range;
+
+use crate::rgb_to_hsl;
+use crate::utils::{luminance, Rng};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortKey {
+    Luminance,
+    Hue,
+}
+
+impl SortKey {
+    pub fn from_string(s: &str) -> Option<SortKey> {
+        match s {
+            "luminance" => Some(SortKey::Luminance),
+            "hue" => Some(SortKey::Hue),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelSort {
+    pub key: SortKey,
+    // luminance band, 0..1, whose runs of pixels get sorted
+    pub threshold: [f32; 2],
+    // direction of the sorted streaks in degrees, 0 runs left to right
+    pub angle: f32,
+    pub reverse: bool,
+}
+
+impl Default for PixelSort {
+    fn default() -> PixelSort {
+        PixelSort {
+            key: SortKey::Luminance,
+            threshold: [0.25, 0.8],
+            angle: 0.0,
+            reverse: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RgbSplit {
+    // offset of red and blue in pixels, in opposite directions
+    pub distance: f32,
+    // direction of the offset in degrees, 0 runs left to right
+    pub angle: f32,
+}
+
+impl Default for RgbSplit {
+    fn default() -> RgbSplit {
+        RgbSplit {
+            distance: 8.0,
+            angle: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Displace {
+    // largest shift in pixels
+    pub distance: f32,
+    // typical block height in pixels, or the tallest band of jittered rows
+    pub block_size: f32,
+    // 0..1 share of blocks or rows that move
+    pub density: f32,
+    pub seed: u32,
+}
+
+// the defaults move blocks, see jitter for scanline jitter
+impl Default for Displace {
+    fn default() -> Displace {
+        Displace {
+            distance: 24.0,
+            block_size: 16.0,
+            density: 0.3,
+            seed: 0,
+        }
+    }
+}
+
+impl Displace {
+    // scanline jitter moves thin bands of rows rather than blocks
+    pub fn jitter() -> Displace {
+        Displace {
+            block_size: 4.0,
+            ..Default::default()
+        }
+    }
+}
+
+fn luma(p: &[u8]) -> f32 {
+    luminance(p[0] as f32, p[1] as f32, p[2] as f32) / 255.0
+}
+
+// mixes the glitched pixels into the image by `amount`, alpha comes along
+// with the moved pixels
+fn finish(data: &[u8], glitched: Vec<u8>, amount: f32) -> Vec<u8> {
+    let amount = amount.clamp(0.0, 1.0);
+    if amount >= 1.0 {
+        return glitched;
+    }
+    data.iter()
+        .zip(&glitched)
+        .map(|(&a, &b)| (a as f32 + (b as f32 - a as f32) * amount).round() as u8)
+        .collect()
+}
+
+// pixel indices of parallel lines at `angle` degrees covering every pixel
+// exactly once; each line steps one pixel along the dominant axis and rounds
+// across it, in the direction of the angle
+fn lines(width: u32, height: u32, angle: f32) -> Vec<Vec<usize>> {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (w, h) = (width as i64, height as i64);
+    let along_x = cos.abs() >= sin.abs();
+    let (major, minor, slope, backwards) = if along_x {
+        (w, h, sin / cos, cos < 0.0)
+    } else {
+        (h, w, cos / sin, sin < 0.0)
+    };
+
+    let shifts: Vec<i64> = (0..major)
+        .map(|m| (m as f32 * slope).round() as i64)
+        .collect();
+    let lo = -shifts.iter().max().copied().unwrap_or(0);
+    let hi = minor - 1 - shifts.iter().min().copied().unwrap_or(0);
+
+    let mut result = Vec::new();
+    for k in lo..=hi {
+        let mut line: Vec<usize> = (0..major)
+            .filter_map(|m| {
+                let n = k + shifts[m as usize];
+                (0..minor).contains(&n).then(|| {
+                    let (x, y) = if along_x { (m, n) } else { (n, m) };
+                    (y * w + x) as usize
+                })
+            })
+            .collect();
+        if backwards {
+            line.reverse();
+        }
+        if !line.is_empty() {
+            result.push(line);
+        }
+    }
+    result
+}
+
+// sorts each run of pixels whose luminance lies inside the threshold band,
+// along lines at the given angle, so bright or dark areas smear into streaks
+pub fn pixel_sort(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    settings: &PixelSort,
+    amount: f32,
+) -> Vec<u8> {
+    let [lo, hi] = settings.threshold;
+    let pixel = |i: usize| &data[i * 4..i * 4 + 4];
+    let key = |i: usize| {
+        let p = pixel(i);
+        match settings.key {
+            SortKey::Luminance => luma(p),
+            SortKey::Hue => {
+                let [r, g, b] = [p[0], p[1], p[2]].map(|c| c as f32 / 255.0);
+                rgb_to_hsl(r, g, b).0 / 360.0
+            }
+        }
+    };
+
+    let mut result = data.to_vec();
+    for line in lines(width, height, settings.angle) {
+        let mut start = 0;
+        while start < line.len() {
+            let inside = |i: usize| (lo..=hi).contains(&luma(pixel(i)));
+            if !inside(line[start]) {
+                start += 1;
+                continue;
+            }
+            let mut end = start;
+            while end < line.len() && inside(line[end]) {
+                end += 1;
+            }
+
+            let run = &line[start..end];
+            let mut sorted: Vec<(f32, usize)> = run.iter().map(|&i| (key(i), i)).collect();
+            sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+            if settings.reverse {
+                sorted.reverse();
+            }
+            for (&to, &(_, from)) in run.iter().zip(&sorted) {
+                result[to * 4..to * 4 + 4].copy_from_slice(pixel(from));
+            }
+            start = end;
+        }
+    }
+    finish(data, result, amount)
+}
+
+// bilinear read of one channel with the edges clamped
+fn sample(data: &[u8], width: u32, height: u32, x: f32, y: f32, channel: usize) -> f32 {
+    let x = x.clamp(0.0, (width - 1) as f32);
+    let y = y.clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+    let at = |x: u32, y: u32| data[((y * width + x) * 4) as usize + channel] as f32;
+    let top = at(x0, y0) + (at(x1, y0) - at(x0, y0)) * fx;
+    let bottom = at(x0, y1) + (at(x1, y1) - at(x0, y1)) * fx;
+    top + (bottom - top) * fy
+}
+
+// pulls red one way and blue the other, green stays put
+pub fn rgb_split(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    settings: &RgbSplit,
+    amount: f32,
+) -> Vec<u8> {
+    let (sin, cos) = settings.angle.to_radians().sin_cos();
+    let (dx, dy) = (cos * settings.distance, sin * settings.distance);
+    let mut result = data.to_vec();
+    for y in 0..height {
+        for x in 0..width {
+            let (fx, fy) = (x as f32, y as f32);
+            let i = ((y * width + x) * 4) as usize;
+            result[i] = sample(data, width, height, fx - dx, fy - dy, 0).round() as u8;
+            result[i + 2] = sample(data, width, height, fx + dx, fy + dy, 2).round() as u8;
+        }
+    }
+    finish(data, result, amount)
+}
+
+// copies one row span from the source shifted sideways, wrapping around
+fn shift_span(
+    source: &[u8],
+    target: &mut [u8],
+    width: u32,
+    y: u32,
+    span: Range<u32>,
+    shift: i64,
+    channels: Range<usize>,
+) {
+    let w = width as i64;
+    for x in span {
+        let from = (x as i64 - shift).rem_euclid(w) as u32;
+        let (t, s) = (
+            ((y * width + x) * 4) as usize,
+            ((y * width + from) * 4) as usize,
+        );
+        for c in channels.clone() {
+            target[t + c] = source[s + c];
+        }
+    }
+}
+
+// seeded rectangles of the image torn sideways; some of them only move one
+// colour channel, like a broken stream
+pub fn block_displace(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    settings: &Displace,
+    amount: f32,
+) -> Vec<u8> {
+    let mut rng = Rng::new(settings.seed);
+    let size = settings.block_size.max(1.0);
+    let area = (width * height) as f32;
+    let blocks = (settings.density.clamp(0.0, 1.0) * area / (size * size * 2.0)).round() as u32;
+
+    let mut result = data.to_vec();
+    for _ in 0..blocks {
+        let block_h = ((size * (0.5 + rng.next_f32())) as u32).clamp(1, height);
+        let block_w = ((size * (1.0 + 3.0 * rng.next_f32())) as u32).clamp(1, width);
+        let x0 = rng.below(width - block_w + 1);
+        let y0 = rng.below(height - block_h + 1);
+        let shift = ((rng.next_f32() * 2.0 - 1.0) * settings.distance).round() as i64;
+        let channels = match rng.below(4) {
+            0 => {
+                let c = rng.below(3) as usize;
+                c..c + 1
+            }
+            _ => 0..4,
+        };
+        for y in y0..y0 + block_h {
+            shift_span(
+                data,
+                &mut result,
+                width,
+                y,
+                x0..x0 + block_w,
+                shift,
+                channels.clone(),
+            );
+        }
+    }
+    finish(data, result, amount)
+}
+
+// bands of a few rows each, a seeded share of them slid sideways as if the
+// signal lost horizontal sync
+pub fn scanline_jitter(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    settings: &Displace,
+    amount: f32,
+) -> Vec<u8> {
+    let mut rng = Rng::new(settings.seed);
+    let tallest = (settings.block_size.max(1.0) as u32).max(1);
+    let density = settings.density.clamp(0.0, 1.0);
+
+    let mut result = data.to_vec();
+    let mut y = 0;
+    while y < height {
+        let band = (1 + rng.below(tallest)).min(height - y);
+        let moves = rng.next_f32() < density;
+        // drawn even for still bands so each band's shift depends only on the seed
+        let shift = ((rng.next_f32() * 2.0 - 1.0) * settings.distance).round() as i64;
+        if moves {
+            for row in y..y + band {
+                shift_span(data, &mut result, width, row, 0..width, shift, 0..4);
+            }
+        }
+        y += band;
+    }
+    finish(data, result, amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(width: u32, height: u32, seed: u32) -> Vec<u8> {
+        let mut rng = Rng::new(seed);
+        (0..width * height)
+            .flat_map(|_| {
+                let v = rng.below(256) as u8;
+                [v, 255 - v, rng.below(256) as u8, 255]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_lines_cover_every_pixel_once() {
+        for angle in [0.0, 30.0, 90.0, 135.0, 200.0, -60.0] {
+            let mut seen = [0u8; 13 * 9];
+            for line in lines(13, 9, angle) {
+                for i in line {
+                    seen[i] += 1;
+                }
+            }
+            assert!(seen.iter().all(|&n| n == 1), "{}", angle);
+        }
+    }
+
+    #[test]
+    fn test_pixel_sort_orders_runs_in_the_band() {
+        let data = noise(16, 4, 1);
+        let settings = PixelSort {
+            key: SortKey::Luminance,
+            threshold: [0.0, 1.0],
+            angle: 0.0,
+            reverse: false,
+        };
+        let out = pixel_sort(&data, 16, 4, &settings, 1.0);
+        for row in out.chunks_exact(16 * 4) {
+            let lum: Vec<f32> = row.chunks_exact(4).map(luma).collect();
+            assert!(lum.windows(2).all(|w| w[0] <= w[1]));
+        }
+        // sorting moves pixels, it never makes new ones
+        let mut a: Vec<&[u8]> = data.chunks_exact(4).collect();
+        let mut b: Vec<&[u8]> = out.chunks_exact(4).collect();
+        a.sort();
+        b.sort();
+        assert_eq!(a, b);
+
+        // upwards, and with an empty band nothing moves
+        let up = PixelSort {
+            angle: 270.0,
+            ..settings
+        };
+        let out = pixel_sort(&data, 16, 4, &up, 1.0);
+        let column: Vec<f32> = (0..4).map(|y| luma(&out[y * 64..y * 64 + 4])).collect();
+        assert!(column.windows(2).all(|w| w[0] >= w[1]));
+        let none = PixelSort {
+            threshold: [2.0, 3.0],
+            ..settings
+        };
+        assert_eq!(pixel_sort(&data, 16, 4, &none, 1.0), data);
+    }
+
+    #[test]
+    fn test_split_and_displacement_are_seeded() {
+        let mut data = vec![0u8; 9 * 3 * 4];
+        data[(9 + 4) * 4..(9 + 4) * 4 + 4].copy_from_slice(&[255, 255, 255, 255]);
+        let split = RgbSplit {
+            distance: 2.0,
+            angle: 0.0,
+        };
+        let out = rgb_split(&data, 9, 3, &split, 1.0);
+        let at = |x: usize| &out[(9 + x) * 4..(9 + x) * 4 + 4];
+        assert_eq!(at(6)[0], 255);
+        assert_eq!(at(4), &[0, 255, 0, 255]);
+        assert_eq!(at(2)[2], 255);
+
+        let data = noise(64, 48, 2);
+        let settings = Displace {
+            distance: 12.0,
+            block_size: 8.0,
+            density: 0.5,
+            seed: 9,
+        };
+        for effect in [block_displace, scanline_jitter] {
+            let a = effect(&data, 64, 48, &settings, 1.0);
+            assert_eq!(a, effect(&data, 64, 48, &settings, 1.0));
+            assert_ne!(a, data);
+            let other = Displace {
+                seed: 10,
+                ..settings
+            };
+            assert_ne!(a, effect(&data, 64, 48, &other, 1.0));
+            let still = Displace {
+                density: 0.0,
+                ..settings
+            };
+            assert_eq!(effect(&data, 64, 48, &still, 1.0), data);
+        }
+    }
+}
@@ -0,0 +1,554 @@
+use crate::{mask, resize_image};
+
+// segmentation runs on a copy no larger than this, the mask is scaled back up
+const MAX_WORK_PIXELS: u64 = 512 * 512;
+const COMPONENTS: usize = 5;
+const KMEANS_ROUNDS: usize = 10;
+// smoothness weight from the original grabcut paper
+const GAMMA: f64 = 50.0;
+// larger than the n-links of any pixel, so a hard label is never cut
+const HARD: f64 = 8.0 * GAMMA + 1.0;
+const EPS: f64 = 1e-9;
+
+fn luma(c: [f64; 3]) -> f64 {
+    0.299 * c[0] + 0.587 * c[1] + 0.114 * c[2]
+}
+
+fn dist2(a: [f64; 3], b: [f64; 3]) -> f64 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+}
+
+// lloyd's k-means seeded at luminance quantiles, so it needs no random state
+fn initial_assignment(samples: &[[f64; 3]]) -> Vec<usize> {
+    let n = samples.len();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&a, &b| luma(samples[a]).total_cmp(&luma(samples[b])));
+    let mut centres: Vec<[f64; 3]> = (0..COMPONENTS)
+        .map(|k| samples[order[(2 * k + 1) * n / (2 * COMPONENTS)]])
+        .collect();
+
+    let mut assignment = vec![0usize; n];
+    for _ in 0..KMEANS_ROUNDS {
+        for (a, &s) in assignment.iter_mut().zip(samples) {
+            *a = (0..COMPONENTS)
+                .min_by(|&i, &j| dist2(s, centres[i]).total_cmp(&dist2(s, centres[j])))
+                .unwrap();
+        }
+        let mut sums = [[0.0f64; 3]; COMPONENTS];
+        let mut counts = [0usize; COMPONENTS];
+        for (&a, s) in assignment.iter().zip(samples) {
+            counts[a] += 1;
+            for c in 0..3 {
+                sums[a][c] += s[c];
+            }
+        }
+        for k in 0..COMPONENTS {
+            if counts[k] > 0 {
+                centres[k] = sums[k].map(|v| v / counts[k] as f64);
+            }
+        }
+    }
+    assignment
+}
+
+// gaussian mixture over rgb with full covariances
+struct Gmm {
+    means: [[f64; 3]; COMPONENTS],
+    inverse: [[f64; 9]; COMPONENTS],
+    // weight / sqrt(det), zero for empty components
+    scale: [f64; COMPONENTS],
+}
+
+impl Gmm {
+    fn fit(samples: &[[f64; 3]], assignment: &[usize]) -> Gmm {
+        let mut counts = [0usize; COMPONENTS];
+        let mut sums = [[0.0f64; 3]; COMPONENTS];
+        let mut products = [[0.0f64; 9]; COMPONENTS];
+        for (&k, s) in assignment.iter().zip(samples) {
+            counts[k] += 1;
+            for i in 0..3 {
+                sums[k][i] += s[i];
+                for j in 0..3 {
+                    products[k][i * 3 + j] += s[i] * s[j];
+                }
+            }
+        }
+
+        let mut gmm = Gmm {
+            means: [[0.0; 3]; COMPONENTS],
+            inverse: [[0.0; 9]; COMPONENTS],
+            scale: [0.0; COMPONENTS],
+        };
+        let total = samples.len() as f64;
+        for k in 0..COMPONENTS {
+            if counts[k] == 0 {
+                continue;
+            }
+            let n = counts[k] as f64;
+            let mean = sums[k].map(|v| v / n);
+            let mut cov = [0.0f64; 9];
+            for i in 0..3 {
+                for j in 0..3 {
+                    cov[i * 3 + j] = products[k][i * 3 + j] / n - mean[i] * mean[j];
+                }
+                // keeps flat colour regions invertible
+                cov[i * 4] += 0.01;
+            }
+            let det = cov[0] * (cov[4] * cov[8] - cov[5] * cov[7])
+                - cov[1] * (cov[3] * cov[8] - cov[5] * cov[6])
+                + cov[2] * (cov[3] * cov[7] - cov[4] * cov[6]);
+            if det <= 0.0 {
+                continue;
+            }
+            gmm.inverse[k] = [
+                (cov[4] * cov[8] - cov[5] * cov[7]) / det,
+                (cov[2] * cov[7] - cov[1] * cov[8]) / det,
+                (cov[1] * cov[5] - cov[2] * cov[4]) / det,
+                (cov[5] * cov[6] - cov[3] * cov[8]) / det,
+                (cov[0] * cov[8] - cov[2] * cov[6]) / det,
+                (cov[2] * cov[3] - cov[0] * cov[5]) / det,
+                (cov[3] * cov[7] - cov[4] * cov[6]) / det,
+                (cov[1] * cov[6] - cov[0] * cov[7]) / det,
+                (cov[0] * cov[4] - cov[1] * cov[3]) / det,
+            ];
+            gmm.means[k] = mean;
+            gmm.scale[k] = (n / total) / det.sqrt();
+        }
+        gmm
+    }
+
+    fn component_density(&self, k: usize, c: [f64; 3]) -> f64 {
+        if self.scale[k] == 0.0 {
+            return 0.0;
+        }
+        let d = [
+            c[0] - self.means[k][0],
+            c[1] - self.means[k][1],
+            c[2] - self.means[k][2],
+        ];
+        let inv = &self.inverse[k];
+        let mut m = 0.0;
+        for i in 0..3 {
+            for j in 0..3 {
+                m += d[i] * inv[i * 3 + j] * d[j];
+            }
+        }
+        self.scale[k] * (-0.5 * m).exp()
+    }
+
+    fn density(&self, c: [f64; 3]) -> f64 {
+        (0..COMPONENTS).map(|k| self.component_density(k, c)).sum()
+    }
+
+    fn most_likely(&self, c: [f64; 3]) -> usize {
+        (0..COMPONENTS)
+            .max_by(|&i, &j| {
+                self.component_density(i, c)
+                    .total_cmp(&self.component_density(j, c))
+            })
+            .unwrap()
+    }
+
+    // refits the mixture, starting from the previous one when there is one
+    fn learn(samples: &[[f64; 3]], previous: Option<&Gmm>) -> Gmm {
+        let assignment = match previous {
+            Some(gmm) => samples.iter().map(|&s| gmm.most_likely(s)).collect(),
+            None => initial_assignment(samples),
+        };
+        Gmm::fit(samples, &assignment)
+    }
+}
+
+// residual graph in compressed rows, each edge stores the index of its reverse
+struct FlowGraph {
+    start: Vec<usize>,
+    to: Vec<usize>,
+    cap: Vec<f64>,
+    rev: Vec<usize>,
+}
+
+impl FlowGraph {
+    // edges are (from, to, capacity, reverse capacity)
+    fn new(nodes: usize, edges: &[(usize, usize, f64, f64)]) -> FlowGraph {
+        let mut start = vec![0usize; nodes + 1];
+        for &(u, v, _, _) in edges {
+            start[u + 1] += 1;
+            start[v + 1] += 1;
+        }
+        for i in 0..nodes {
+            start[i + 1] += start[i];
+        }
+
+        let m = start[nodes];
+        let mut cursor = start.clone();
+        let mut graph = FlowGraph {
+            start,
+            to: vec![0; m],
+            cap: vec![0.0; m],
+            rev: vec![0; m],
+        };
+        for &(u, v, c, rc) in edges {
+            let (a, b) = (cursor[u], cursor[v]);
+            cursor[u] += 1;
+            cursor[v] += 1;
+            graph.to[a] = v;
+            graph.cap[a] = c;
+            graph.rev[a] = b;
+            graph.to[b] = u;
+            graph.cap[b] = rc;
+            graph.rev[b] = a;
+        }
+        graph
+    }
+
+    // breadth-first levels over unsaturated edges, -1 when unreachable
+    fn levels(&self, source: usize) -> Vec<i32> {
+        let mut level = vec![-1i32; self.start.len() - 1];
+        let mut queue = std::collections::VecDeque::new();
+        level[source] = 0;
+        queue.push_back(source);
+        while let Some(u) = queue.pop_front() {
+            for e in self.start[u]..self.start[u + 1] {
+                let v = self.to[e];
+                if self.cap[e] > EPS && level[v] < 0 {
+                    level[v] = level[u] + 1;
+                    queue.push_back(v);
+                }
+            }
+        }
+        level
+    }
+
+    // dinic's algorithm, returns the flow value; paths are walked iteratively
+    // because grid paths are far too long for recursion
+    fn max_flow(&mut self, source: usize, sink: usize) -> f64 {
+        let mut flow = 0.0;
+        loop {
+            let mut level = self.levels(source);
+            if level[sink] < 0 {
+                return flow;
+            }
+            let mut next = self.start.clone();
+            let mut path: Vec<usize> = Vec::new();
+            let mut u = source;
+            loop {
+                if u == sink {
+                    let f = path
+                        .iter()
+                        .map(|&e| self.cap[e])
+                        .fold(f64::INFINITY, f64::min);
+                    let mut cut = path.len();
+                    for (i, &e) in path.iter().enumerate() {
+                        self.cap[e] -= f;
+                        self.cap[self.rev[e]] += f;
+                        if self.cap[e] <= EPS && cut == path.len() {
+                            cut = i;
+                        }
+                    }
+                    flow += f;
+                    path.truncate(cut);
+                    u = path.last().map_or(source, |&e| self.to[e]);
+                    continue;
+                }
+
+                let mut advanced = false;
+                while next[u] < self.start[u + 1] {
+                    let e = next[u];
+                    let v = self.to[e];
+                    if self.cap[e] > EPS && level[v] == level[u] + 1 {
+                        path.push(e);
+                        u = v;
+                        advanced = true;
+                        break;
+                    }
+                    next[u] += 1;
+                }
+                if !advanced {
+                    if u == source {
+                        break;
+                    }
+                    // dead end, drop it from this phase
+                    level[u] = -1;
+                    let e = path.pop().unwrap();
+                    u = self.to[self.rev[e]];
+                    next[u] += 1;
+                }
+            }
+        }
+    }
+}
+
+// 8-connected neighbour pairs weighted by colour contrast
+fn neighbour_links(colors: &[[f64; 3]], width: usize, height: usize) -> Vec<(usize, usize, f64)> {
+    let mut pairs = Vec::with_capacity(colors.len() * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            if x + 1 < width {
+                pairs.push((i, i + 1, 1.0));
+            }
+            if y + 1 < height {
+                pairs.push((i, i + width, 1.0));
+                if x + 1 < width {
+                    pairs.push((i, i + width + 1, std::f64::consts::SQRT_2));
+                }
+                if x > 0 {
+                    pairs.push((i, i + width - 1, std::f64::consts::SQRT_2));
+                }
+            }
+        }
+    }
+
+    let mean = pairs
+        .iter()
+        .map(|&(a, b, _)| dist2(colors[a], colors[b]))
+        .sum::<f64>()
+        / pairs.len().max(1) as f64;
+    let beta = if mean > 0.0 { 1.0 / (2.0 * mean) } else { 0.0 };
+    pairs
+        .into_iter()
+        .map(|(a, b, d)| {
+            (
+                a,
+                b,
+                GAMMA / d * (-beta * dist2(colors[a], colors[b])).exp(),
+            )
+        })
+        .collect()
+}
+
+// hard labels are Some(true) for foreground and Some(false) for background,
+// None when the pixel is left to the colour models
+fn segment(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    hard: &[Option<bool>],
+    iterations: u32,
+) -> Option<Vec<bool>> {
+    let (w, h) = (width as usize, height as usize);
+    let colors: Vec<[f64; 3]> = data
+        .chunks_exact(4)
+        .map(|p| [p[0] as f64, p[1] as f64, p[2] as f64])
+        .collect();
+    let links = neighbour_links(&colors, w, h);
+    let n = w * h;
+    let (source, sink) = (n, n + 1);
+
+    let mut foreground: Vec<bool> = hard.iter().map(|l| l.unwrap_or(true)).collect();
+    if foreground.iter().all(|&f| f) || foreground.iter().all(|&f| !f) {
+        return None;
+    }
+    let mut models: Option<(Gmm, Gmm)> = None;
+    for _ in 0..iterations {
+        let (fg_samples, bg_samples): (Vec<_>, Vec<_>) =
+            colors.iter().zip(&foreground).partition(|(_, &f)| f);
+        // the previous cut settled on a single label, nothing left to refine
+        if fg_samples.is_empty() || bg_samples.is_empty() {
+            break;
+        }
+        let fg_samples: Vec<[f64; 3]> = fg_samples.into_iter().map(|(&c, _)| c).collect();
+        let bg_samples: Vec<[f64; 3]> = bg_samples.into_iter().map(|(&c, _)| c).collect();
+        let fg_model = Gmm::learn(&fg_samples, models.as_ref().map(|m| &m.0));
+        let bg_model = Gmm::learn(&bg_samples, models.as_ref().map(|m| &m.1));
+
+        let mut edges: Vec<(usize, usize, f64, f64)> = Vec::with_capacity(n + links.len());
+        for (i, label) in hard.iter().enumerate() {
+            // the source side is foreground, cutting a t-link pays for the other label
+            let (to_source, to_sink) = match label {
+                Some(true) => (HARD, 0.0),
+                Some(false) => (0.0, HARD),
+                None => (
+                    -bg_model.density(colors[i]).max(f64::MIN_POSITIVE).ln(),
+                    -fg_model.density(colors[i]).max(f64::MIN_POSITIVE).ln(),
+                ),
+            };
+            // only the difference matters for the cut
+            let common = to_source.min(to_sink);
+            if to_source - common > EPS {
+                edges.push((source, i, to_source - common, 0.0));
+            } else if to_sink - common > EPS {
+                edges.push((i, sink, to_sink - common, 0.0));
+            }
+        }
+        edges.extend(links.iter().map(|&(a, b, wt)| (a, b, wt, wt)));
+
+        let mut graph = FlowGraph::new(n + 2, &edges);
+        graph.max_flow(source, sink);
+        let reachable = graph.levels(source);
+        for (f, &l) in foreground.iter_mut().zip(&reachable[..n]) {
+            *f = l >= 0;
+        }
+        models = Some((fg_model, bg_model));
+    }
+    Some(foreground)
+}
+
+// alpha mask of the object inside the rectangle; scribbles are hard labels,
+// with background strokes winning where both are drawn
+#[allow(clippy::too_many_arguments)]
+pub fn grab_cut(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    rect: (u32, u32, u32, u32),
+    foreground: Option<&[u8]>,
+    background: Option<&[u8]>,
+    iterations: u32,
+) -> Option<Vec<u8>> {
+    let pixels = width as u64 * height as u64;
+    let (work_w, work_h) = if pixels > MAX_WORK_PIXELS {
+        let scale = (MAX_WORK_PIXELS as f64 / pixels as f64).sqrt();
+        (
+            ((width as f64 * scale).round() as u32).max(1),
+            ((height as f64 * scale).round() as u32).max(1),
+        )
+    } else {
+        (width, height)
+    };
+    let work = if (work_w, work_h) == (width, height) {
+        data.to_vec()
+    } else {
+        resize_image(data, width, height, work_w, work_h)
+    };
+
+    // any marked source pixel marks the working pixel it falls in, so thin strokes survive
+    let to_work = |x: u32, y: u32| -> usize {
+        let wx = (x as u64 * work_w as u64 / width as u64) as usize;
+        let wy = (y as u64 * work_h as u64 / height as u64) as usize;
+        wy * work_w as usize + wx
+    };
+    let downsample = |m: Option<&[u8]>| -> Vec<bool> {
+        let mut marked = vec![false; (work_w * work_h) as usize];
+        if let Some(m) = m {
+            for y in 0..height {
+                for x in 0..width {
+                    if m[(y * width + x) as usize] >= 128 {
+                        marked[to_work(x, y)] = true;
+                    }
+                }
+            }
+        }
+        marked
+    };
+    let fg_marks = downsample(foreground);
+    let bg_marks = downsample(background);
+
+    let (rx, ry, rw, rh) = rect;
+    let x0 = rx as u64 * work_w as u64 / width as u64;
+    let y0 = ry as u64 * work_h as u64 / height as u64;
+    let x1 = ((rx + rw) as u64 * work_w as u64).div_ceil(width as u64);
+    let y1 = ((ry + rh) as u64 * work_h as u64).div_ceil(height as u64);
+
+    let hard: Vec<Option<bool>> = (0..work_w as u64 * work_h as u64)
+        .map(|i| {
+            let (x, y) = (i % work_w as u64, i / work_w as u64);
+            let i = i as usize;
+            if bg_marks[i] {
+                Some(false)
+            } else if fg_marks[i] {
+                Some(true)
+            } else if x < x0 || x >= x1 || y < y0 || y >= y1 {
+                Some(false)
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let labels = segment(&work, work_w, work_h, &hard, iterations)?;
+    let small: Vec<u8> = labels.iter().map(|&f| if f { 255 } else { 0 }).collect();
+    let mut alpha = if (work_w, work_h) == (width, height) {
+        small
+    } else {
+        mask::resize(&small, work_w, work_h, width, height)
+    };
+
+    // the scaled mask can bleed past what the user fixed at full resolution
+    for y in 0..height {
+        for x in 0..width {
+            let i = (y * width + x) as usize;
+            let outside = x < rx || x >= rx + rw || y < ry || y >= ry + rh;
+            let fg = foreground.is_some_and(|m| m[i] >= 128);
+            let bg = background.is_some_and(|m| m[i] >= 128);
+            if bg || (outside && !fg) {
+                alpha[i] = 0;
+            } else if fg {
+                alpha[i] = 255;
+            }
+        }
+    }
+    Some(alpha)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // blue canvas with a red square covering (6, 6)..(14, 14)
+    fn scene() -> Vec<u8> {
+        let mut data = Vec::new();
+        for y in 0..20 {
+            for x in 0..20 {
+                let inside = (6..14).contains(&x) && (6..14).contains(&y);
+                // a little texture so the models are not degenerate
+                let t = ((x * 7 + y * 13) % 5) as u8;
+                data.extend_from_slice(&if inside {
+                    [220 + t, 30, 20 + t, 255]
+                } else {
+                    [20, 40 + t, 200 + t, 255]
+                });
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_max_flow() {
+        // two disjoint paths of capacity 3 and 2 plus a cross edge
+        let edges = [
+            (0, 1, 3.0, 0.0),
+            (0, 2, 2.0, 0.0),
+            (1, 3, 2.0, 0.0),
+            (2, 3, 3.0, 0.0),
+            (1, 2, 1.0, 0.0),
+        ];
+        let mut graph = FlowGraph::new(4, &edges);
+        assert!((graph.max_flow(0, 3) - 5.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_segments_the_object_in_the_rectangle() {
+        let data = scene();
+        let alpha = grab_cut(&data, 20, 20, (3, 3, 14, 14), None, None, 3).unwrap();
+        for y in 0..20 {
+            for x in 0..20 {
+                let inside = (6..14).contains(&x) && (6..14).contains(&y);
+                assert_eq!(alpha[y * 20 + x], if inside { 255 } else { 0 });
+            }
+        }
+        // same input, same mask
+        assert_eq!(
+            grab_cut(&data, 20, 20, (3, 3, 14, 14), None, None, 3).unwrap(),
+            alpha
+        );
+    }
+
+    #[test]
+    fn test_scribbles_and_missing_background() {
+        let data = scene();
+        let mut fg = vec![0u8; 400];
+        let mut bg = vec![0u8; 400];
+        fg[10 * 20 + 10] = 255;
+        bg[4 * 20 + 4] = 255;
+        // a foreground stroke outside the rectangle is kept as well
+        fg[19 * 20 + 19] = 255;
+        let alpha = grab_cut(&data, 20, 20, (3, 3, 14, 14), Some(&fg), Some(&bg), 2).unwrap();
+        assert_eq!(alpha[4 * 20 + 4], 0);
+        assert_eq!(alpha[10 * 20 + 10], 255);
+        assert_eq!(alpha[7 * 20 + 7], 255);
+        assert_eq!(alpha[19 * 20 + 19], 255);
+
+        assert!(grab_cut(&data, 20, 20, (0, 0, 20, 20), None, None, 2).is_none());
+    }
+}
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+
+use crate::color::{oklab_to_rgb, rgb_to_oklab};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GradientStop {
+    pub position: f32,
+    pub color: [u8; 3],
+}
+
+// shadows to highlights in two or three evenly spaced stops
+pub fn duotone(shadow: [u8; 3], highlight: [u8; 3]) -> Vec<GradientStop> {
+    vec![
+        GradientStop {
+            position: 0.0,
+            color: shadow,
+        },
+        GradientStop {
+            position: 1.0,
+            color: highlight,
+        },
+    ]
+}
+
+pub fn tritone(shadow: [u8; 3], midtone: [u8; 3], highlight: [u8; 3]) -> Vec<GradientStop> {
+    let mut stops = duotone(shadow, highlight);
+    stops.insert(
+        1,
+        GradientStop {
+            position: 0.5,
+            color: midtone,
+        },
+    );
+    stops
+}
+
+// colour for every luma level, blended between neighbouring stops in oklab so
+// the ramp keeps an even perceived lightness; an empty gradient is black to white
+fn lookup_table(stops: &[GradientStop]) -> Vec<[u8; 3]> {
+    let mut stops = stops.to_vec();
+    if stops.is_empty() {
+        stops = duotone([0, 0, 0], [255, 255, 255]);
+    }
+    for stop in &mut stops {
+        stop.position = stop.position.clamp(0.0, 1.0);
+    }
+    stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+    let labs: Vec<[f32; 3]> = stops
+        .iter()
+        .map(|s| rgb_to_oklab(s.color[0], s.color[1], s.color[2]))
+        .collect();
+
+    (0..256)
+        .map(|level| {
+            let t = level as f32 / 255.0;
+            let next = stops.partition_point(|s| s.position <= t);
+            if next == 0 {
+                return stops[0].color;
+            }
+            if next == stops.len() {
+                return stops[next - 1].color;
+            }
+            let (a, b) = (&stops[next - 1], &stops[next]);
+            let span = b.position - a.position;
+            let f = if span > 0.0 {
+                (t - a.position) / span
+            } else {
+                1.0
+            };
+            let (la, lb) = (labs[next - 1], labs[next]);
+            oklab_to_rgb([
+                la[0] + (lb[0] - la[0]) * f,
+                la[1] + (lb[1] - la[1]) * f,
+                la[2] + (lb[2] - la[2]) * f,
+            ])
+        })
+        .collect()
+}
+
+// replaces each pixel by the gradient colour at its luma, mixed with the
+// original by `amount`; alpha is kept
+pub fn apply(data: &[u8], stops: &[GradientStop], amount: f32) -> Vec<u8> {
+    let table = lookup_table(stops);
+    let amount = amount.clamp(0.0, 1.0);
+    let mut result = data.to_vec();
+    for p in result.chunks_exact_mut(4) {
+        let luma = 0.2126 * p[0] as f32 + 0.7152 * p[1] as f32 + 0.0722 * p[2] as f32;
+        let mapped = table[luma.round().clamp(0.0, 255.0) as usize];
+        for c in 0..3 {
+            let v = p[c] as f32 + (mapped[c] as f32 - p[c] as f32) * amount;
+            p[c] = v.round() as u8;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duotone_end_points_and_alpha() {
+        let navy = [20, 30, 90];
+        let peach = [250, 200, 160];
+        let data = [[0, 0, 0, 255], [255, 255, 255, 128]].concat();
+        let out = apply(&data, &duotone(navy, peach), 1.0);
+        assert_eq!(&out[..4], &[20, 30, 90, 255]);
+        assert_eq!(&out[4..], &[250, 200, 160, 128]);
+
+        let half = apply(&data, &duotone(navy, peach), 0.5);
+        assert_eq!(&half[..3], &[10, 15, 45]);
+    }
+
+    #[test]
+    fn test_tritone_midpoint_and_stop_order() {
+        let stops = tritone([0, 0, 0], [200, 40, 40], [255, 255, 255]);
+        let mut shuffled = stops.clone();
+        shuffled.reverse();
+        assert_eq!(lookup_table(&stops), lookup_table(&shuffled));
+
+        let table = lookup_table(&stops);
+        assert_eq!(table[0], [0, 0, 0]);
+        assert_eq!(table[255], [255, 255, 255]);
+        for level in [127, 128] {
+            let close = table[level]
+                .iter()
+                .zip([200u8, 40, 40])
+                .all(|(&a, b)| a.abs_diff(b) <= 4);
+            assert!(close, "{:?}", table[level]);
+        }
+    }
+
+    #[test]
+    fn test_blends_in_oklab() {
+        // halfway between black and white is oklab l = 0.5, darker than rgb's 128
+        let grey = lookup_table(&[])[128][0];
+        assert!(grey.abs_diff(99) <= 2, "{}", grey);
+    }
+}
@@ -0,0 +1,355 @@
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+// traditional screen angles for cyan, magenta, yellow and black
+pub const CMYK_ANGLES: [f32; 4] = [15.0, 75.0, 0.0, 45.0];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Pattern {
+    Dot,
+    Line,
+    Cross,
+}
+
+impl Pattern {
+    pub fn from_string(s: &str) -> Option<Pattern> {
+        match s {
+            "dot" => Some(Pattern::Dot),
+            "line" => Some(Pattern::Line),
+            "cross" => Some(Pattern::Cross),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Halftone {
+    pub pattern: Pattern,
+    // cell size in pixels and screen angle in degrees
+    pub cell_size: f32,
+    pub angle: f32,
+    // per-ink angles when printing in cmyk instead of a single black screen
+    pub cmyk: Option<[f32; 4]>,
+    // ink coverage as white on black instead of an image
+    pub mask: bool,
+}
+
+impl Default for Halftone {
+    fn default() -> Halftone {
+        Halftone {
+            pattern: Pattern::Dot,
+            cell_size: 8.0,
+            angle: 45.0,
+            cmyk: None,
+            mask: false,
+        }
+    }
+}
+
+// the screen as a rotated grid of cells covering the whole image
+struct Lattice {
+    cos: f32,
+    sin: f32,
+    cell: f32,
+    u0: i32,
+    v0: i32,
+    cols: i32,
+    rows: i32,
+}
+
+impl Lattice {
+    fn new(width: u32, height: u32, cell: f32, angle: f32) -> Lattice {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let corners = [
+            (0.0, 0.0),
+            (width as f32, 0.0),
+            (0.0, height as f32),
+            (width as f32, height as f32),
+        ];
+        let us = corners.map(|(x, y)| x * cos + y * sin);
+        let vs = corners.map(|(x, y)| -x * sin + y * cos);
+        let lo = |v: [f32; 4]| (v.iter().cloned().fold(f32::MAX, f32::min) / cell).floor() as i32;
+        let hi = |v: [f32; 4]| (v.iter().cloned().fold(f32::MIN, f32::max) / cell).floor() as i32;
+        Lattice {
+            cos,
+            sin,
+            cell,
+            u0: lo(us),
+            v0: lo(vs),
+            cols: hi(us) - lo(us) + 1,
+            rows: hi(vs) - lo(vs) + 1,
+        }
+    }
+
+    // screen coordinates of a pixel centre
+    fn project(&self, x: u32, y: u32) -> (f32, f32) {
+        let (x, y) = (x as f32 + 0.5, y as f32 + 0.5);
+        (x * self.cos + y * self.sin, -x * self.sin + y * self.cos)
+    }
+
+    fn index(&self, cu: i32, cv: i32) -> Option<usize> {
+        let (col, row) = (cu - self.u0, cv - self.v0);
+        (col >= 0 && col < self.cols && row >= 0 && row < self.rows)
+            .then(|| (row * self.cols + col) as usize)
+    }
+
+    // mean of a per-pixel plane over every cell
+    fn cell_means(&self, plane: &[f32], width: u32, height: u32) -> Vec<f32> {
+        let cells = (self.cols * self.rows) as usize;
+        let mut sums = vec![0.0f32; cells];
+        let mut counts = vec![0u32; cells];
+        for y in 0..height {
+            for x in 0..width {
+                let (u, v) = self.project(x, y);
+                let cu = (u / self.cell).floor() as i32;
+                let cv = (v / self.cell).floor() as i32;
+                if let Some(i) = self.index(cu, cv) {
+                    sums[i] += plane[(y * width + x) as usize];
+                    counts[i] += 1;
+                }
+            }
+        }
+        sums.iter()
+            .zip(&counts)
+            .map(|(&s, &n)| if n > 0 { s / n as f32 } else { 0.0 })
+            .collect()
+    }
+}
+
+// area of a circle of radius r clipped to the unit cell around its centre
+fn dot_area(r: f32) -> f32 {
+    if r <= 0.5 {
+        PI * r * r
+    } else if r < FRAC_1_SQRT_2 {
+        let segment = r * r * (0.5 / r).acos() - 0.5 * (r * r - 0.25).sqrt();
+        PI * r * r - 4.0 * segment
+    } else {
+        1.0
+    }
+}
+
+// dot radius, in cells, whose clipped area matches the tone
+fn dot_radius(tone: f32) -> f32 {
+    let (mut lo, mut hi) = (0.0f32, FRAC_1_SQRT_2);
+    for _ in 0..24 {
+        let mid = (lo + hi) / 2.0;
+        if dot_area(mid) < tone {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    hi
+}
+
+// ink coverage in 0..1 for one screen over a darkness plane; each cell's ink
+// covers the share of the cell given by its mean darkness
+fn screen(
+    plane: &[f32],
+    width: u32,
+    height: u32,
+    pattern: Pattern,
+    cell: f32,
+    angle: f32,
+) -> Vec<f32> {
+    let lattice = Lattice::new(width, height, cell, angle);
+    let tones = lattice.cell_means(plane, width, height);
+    // the extent of each cell's ink, in pixels
+    let sizes: Vec<f32> = tones
+        .iter()
+        .map(|&t| match pattern {
+            Pattern::Dot => dot_radius(t) * cell,
+            Pattern::Line => t * cell / 2.0,
+            Pattern::Cross => (1.0 - (1.0 - t).max(0.0).sqrt()) * cell / 2.0,
+        })
+        .collect();
+
+    let mut coverage = vec![0.0f32; (width * height) as usize];
+    for y in 0..height {
+        for x in 0..width {
+            let (u, v) = lattice.project(x, y);
+            let cu = (u / cell).floor() as i32;
+            let cv = (v / cell).floor() as i32;
+            let fu = u - (cu as f32 + 0.5) * cell;
+            let fv = v - (cv as f32 + 0.5) * cell;
+
+            // neighbouring cells can reach in, overlapping ink saturates at 1
+            let mut ink = 0.0f32;
+            for dv in -1..=1 {
+                for du in -1..=1 {
+                    let Some(i) = lattice.index(cu + du, cv + dv) else {
+                        continue;
+                    };
+                    if tones[i] <= 0.0 {
+                        continue;
+                    }
+                    let ou = fu - du as f32 * cell;
+                    let ov = fv - dv as f32 * cell;
+                    // signed distance from the ink edge, anti-aliased over one pixel
+                    let edge = |d: f32| (sizes[i] - d + 0.5).clamp(0.0, 1.0);
+                    ink += match pattern {
+                        Pattern::Dot => edge((ou * ou + ov * ov).sqrt()),
+                        Pattern::Line if du == 0 => edge(ov.abs()),
+                        Pattern::Cross if du == 0 && dv == 0 => edge(ou.abs()).max(edge(ov.abs())),
+                        Pattern::Cross if du == 0 => edge(ov.abs()),
+                        Pattern::Cross if dv == 0 => edge(ou.abs()),
+                        _ => 0.0,
+                    };
+                }
+            }
+            coverage[(y * width + x) as usize] = ink.min(1.0);
+        }
+    }
+    coverage
+}
+
+// prints the image with round dots, lines or a cross hatch; black ink on white
+// paper, or four inks in cmyk mode. alpha is kept
+pub fn halftone(data: &[u8], width: u32, height: u32, settings: &Halftone, amount: f32) -> Vec<u8> {
+    let cell = settings.cell_size;
+    let mut printed: Vec<[f32; 3]> = vec![[1.0; 3]; (width * height) as usize];
+
+    match settings.cmyk {
+        Some(angles) if !settings.mask => {
+            let mut planes: [Vec<f32>; 4] = Default::default();
+            for p in data.chunks_exact(4) {
+                let [r, g, b] = [p[0], p[1], p[2]].map(|c| c as f32 / 255.0);
+                let k = 1.0 - r.max(g).max(b);
+                let ink = |c: f32| {
+                    if k < 1.0 {
+                        (1.0 - c - k) / (1.0 - k)
+                    } else {
+                        0.0
+                    }
+                };
+                for (plane, value) in planes.iter_mut().zip([ink(r), ink(g), ink(b), k]) {
+                    plane.push(value);
+                }
+            }
+            let inks: Vec<Vec<f32>> = planes
+                .iter()
+                .zip(angles)
+                .map(|(plane, angle)| screen(plane, width, height, settings.pattern, cell, angle))
+                .collect();
+            for (i, out) in printed.iter_mut().enumerate() {
+                let paper = 1.0 - inks[3][i];
+                for c in 0..3 {
+                    out[c] = (1.0 - inks[c][i]) * paper;
+                }
+            }
+        }
+        _ => {
+            let darkness: Vec<f32> = data
+                .chunks_exact(4)
+                .map(|p| {
+                    1.0 - (0.2126 * p[0] as f32 + 0.7152 * p[1] as f32 + 0.0722 * p[2] as f32)
+                        / 255.0
+                })
+                .collect();
+            let ink = screen(
+                &darkness,
+                width,
+                height,
+                settings.pattern,
+                cell,
+                settings.angle,
+            );
+            for (out, &k) in printed.iter_mut().zip(&ink) {
+                *out = [if settings.mask { k } else { 1.0 - k }; 3];
+            }
+        }
+    }
+
+    // the mask is a result of its own, only images blend with the original
+    let amount = if settings.mask {
+        1.0
+    } else {
+        amount.clamp(0.0, 1.0)
+    };
+    let mut result = data.to_vec();
+    for (p, out) in result.chunks_exact_mut(4).zip(&printed) {
+        for c in 0..3 {
+            let v = p[c] as f32 + (out[c] * 255.0 - p[c] as f32) * amount;
+            p[c] = v.round().clamp(0.0, 255.0) as u8;
+        }
+        if settings.mask {
+            p[3] = 255;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grey(level: u8, size: u32) -> Vec<u8> {
+        [level, level, level, 255].repeat((size * size) as usize)
+    }
+
+    fn mean_ink(out: &[u8]) -> f32 {
+        out.chunks_exact(4)
+            .map(|p| 1.0 - p[0] as f32 / 255.0)
+            .sum::<f32>()
+            / (out.len() / 4) as f32
+    }
+
+    #[test]
+    fn test_patterns_keep_the_tone() {
+        for pattern in [Pattern::Dot, Pattern::Line, Pattern::Cross] {
+            for level in [64u8, 128, 200] {
+                let settings = Halftone {
+                    pattern,
+                    cell_size: 10.0,
+                    angle: 0.0,
+                    ..Halftone::default()
+                };
+                let out = halftone(&grey(level, 60), 60, 60, &settings, 1.0);
+                let expected = 1.0 - level as f32 / 255.0;
+                let ink = mean_ink(&out);
+                assert!(
+                    (ink - expected).abs() < 0.04,
+                    "{:?} {}: {}",
+                    pattern,
+                    level,
+                    ink
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_solid_tones_and_rotation() {
+        let settings = Halftone {
+            angle: 30.0,
+            ..Halftone::default()
+        };
+        let black = halftone(&grey(0, 40), 40, 40, &settings, 1.0);
+        assert!(black.chunks_exact(4).all(|p| p[0] == 0));
+        let white = halftone(&grey(255, 40), 40, 40, &settings, 1.0);
+        assert!(white.chunks_exact(4).all(|p| p[0] == 255));
+        let mid = halftone(&grey(128, 40), 40, 40, &settings, 1.0);
+        // anti-aliased edges leave grey pixels between ink and paper
+        assert!(mid.chunks_exact(4).any(|p| p[0] > 20 && p[0] < 235));
+    }
+
+    #[test]
+    fn test_cmyk_and_mask_output() {
+        let red = [255, 0, 0, 255].repeat(32 * 32);
+        let settings = Halftone {
+            cmyk: Some(CMYK_ANGLES),
+            ..Halftone::default()
+        };
+        let out = halftone(&red, 32, 32, &settings, 1.0);
+        // red prints as solid magenta and yellow with no cyan or black
+        assert!(out
+            .chunks_exact(4)
+            .all(|p| p[0] == 255 && p[1] == 0 && p[2] == 0));
+
+        let mask = Halftone {
+            mask: true,
+            ..Halftone::default()
+        };
+        let out = halftone(&[0, 0, 0, 10].repeat(16 * 16), 16, 16, &mask, 0.2);
+        assert!(out.chunks_exact(4).all(|p| p == [255, 255, 255, 255]));
+    }
+}
@@ -0,0 +1,664 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::mask::distance_transform;
+use crate::utils::{integral, window_sum, Rng};
+
+// neighbourhood telea averages over when filling a pixel
+const TELEA_RADIUS: i64 = 5;
+// patches are (2r+1)^2 pixels
+const PATCH_RADIUS: i64 = 3;
+// under auto, holes whose centre is at most this far from known pixels use telea
+const AUTO_TELEA_DEPTH: f32 = 4.0;
+// the pyramid stops once the hole is this shallow or the image this small
+const COARSEST_DEPTH: f32 = 2.0;
+const COARSEST_SIZE: u32 = 16;
+const COARSE_EM_ROUNDS: usize = 6;
+const FINE_EM_ROUNDS: usize = 3;
+// patchmatch passes per em round, alternating scan direction
+const PATCHMATCH_PASSES: usize = 2;
+// how sharply the vote favours close patch matches, per squared 0..1 error
+const VOTE_SHARPNESS: f32 = 50.0;
+const NONE: usize = usize::MAX;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InpaintMethod {
+    Telea,
+    PatchMatch,
+    // telea for thin holes, patchmatch for everything else
+    Auto,
+}
+
+impl InpaintMethod {
+    pub fn from_string(s: &str) -> Option<InpaintMethod> {
+        match s {
+            "telea" => Some(InpaintMethod::Telea),
+            "patchmatch" => Some(InpaintMethod::PatchMatch),
+            "auto" => Some(InpaintMethod::Auto),
+            _ => None,
+        }
+    }
+}
+
+// straight rgba in 0..1 plus which pixels still need filling
+struct Level {
+    pixels: Vec<[f32; 4]>,
+    hole: Vec<bool>,
+    width: u32,
+    height: u32,
+}
+
+impl Level {
+    fn neighbours(&self, idx: usize) -> impl Iterator<Item = usize> {
+        let (w, h) = (self.width as usize, self.height as usize);
+        let (x, y) = (idx % w, idx / w);
+        [
+            (x > 0).then(|| idx - 1),
+            (x + 1 < w).then(|| idx + 1),
+            (y > 0).then(|| idx - w),
+            (y + 1 < h).then(|| idx + w),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Flag {
+    Known,
+    Band,
+    Inside,
+}
+
+// min-heap entry, ties broken by index so the march order is fixed
+#[derive(PartialEq)]
+struct Front {
+    t: f32,
+    idx: usize,
+}
+
+impl Eq for Front {}
+
+impl Ord for Front {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .t
+            .total_cmp(&self.t)
+            .then_with(|| other.idx.cmp(&self.idx))
+    }
+}
+
+impl PartialOrd for Front {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// eikonal update from one horizontal and one vertical neighbour
+fn solve(t1: Option<f32>, t2: Option<f32>) -> f32 {
+    match (t1, t2) {
+        (Some(a), Some(b)) => {
+            let d = a - b;
+            if d * d >= 2.0 {
+                return 1.0 + a.min(b);
+            }
+            let r = (2.0 - d * d).sqrt();
+            (a + b + r) / 2.0
+        }
+        (Some(a), None) | (None, Some(a)) => 1.0 + a,
+        (None, None) => f32::INFINITY,
+    }
+}
+
+// per-channel colour gradient at a filled pixel, one-sided next to the hole
+fn image_gradient(level: &Level, flags: &[Flag], x: i64, y: i64) -> ([f32; 4], [f32; 4]) {
+    let (w, h) = (level.width as i64, level.height as i64);
+    let at = |x: i64, y: i64| -> Option<[f32; 4]> {
+        (x >= 0 && y >= 0 && x < w && y < h && flags[(y * w + x) as usize] != Flag::Inside)
+            .then(|| level.pixels[(y * w + x) as usize])
+    };
+    let centre = level.pixels[(y * w + x) as usize];
+    let diff = |a: Option<[f32; 4]>, b: Option<[f32; 4]>| -> [f32; 4] {
+        match (a, b) {
+            (Some(a), Some(b)) => std::array::from_fn(|c| (b[c] - a[c]) / 2.0),
+            (None, Some(b)) => std::array::from_fn(|c| b[c] - centre[c]),
+            (Some(a), None) => std::array::from_fn(|c| centre[c] - a[c]),
+            (None, None) => [0.0; 4],
+        }
+    };
+    (
+        diff(at(x - 1, y), at(x + 1, y)),
+        diff(at(x, y - 1), at(x, y + 1)),
+    )
+}
+
+// fast marching inpainting (telea 2004): pixels are filled in order of their
+// distance from the hole boundary, each from a weighted average of the known
+// pixels around it
+fn telea(level: &mut Level) {
+    let (w, h) = (level.width as i64, level.height as i64);
+    let n = level.pixels.len();
+    let mut flags: Vec<Flag> = level
+        .hole
+        .iter()
+        .map(|&hole| if hole { Flag::Inside } else { Flag::Known })
+        .collect();
+    let mut t: Vec<f32> = level
+        .hole
+        .iter()
+        .map(|&hole| if hole { f32::INFINITY } else { 0.0 })
+        .collect();
+
+    let mut heap = BinaryHeap::new();
+    for idx in 0..n {
+        if flags[idx] == Flag::Known && level.neighbours(idx).any(|nb| flags[nb] == Flag::Inside) {
+            flags[idx] = Flag::Band;
+            heap.push(Front { t: 0.0, idx });
+        }
+    }
+
+    let settled = |flags: &[Flag], x: i64, y: i64| -> bool {
+        x >= 0 && y >= 0 && x < w && y < h && flags[(y * w + x) as usize] != Flag::Inside
+    };
+
+    while let Some(Front { idx, .. }) = heap.pop() {
+        if flags[idx] == Flag::Known {
+            continue;
+        }
+        flags[idx] = Flag::Known;
+
+        let neighbours: Vec<usize> = level.neighbours(idx).collect();
+        for nb in neighbours {
+            if flags[nb] != Flag::Inside {
+                continue;
+            }
+            let (x, y) = (nb as i64 % w, nb as i64 / w);
+            let time = |dx: i64, dy: i64| -> Option<f32> {
+                settled(&flags, x + dx, y + dy).then(|| t[((y + dy) * w + x + dx) as usize])
+            };
+            let (left, right) = (time(-1, 0), time(1, 0));
+            let (up, down) = (time(0, -1), time(0, 1));
+            let tn = solve(left, up)
+                .min(solve(right, up))
+                .min(solve(left, down))
+                .min(solve(right, down));
+            t[nb] = tn;
+
+            // gradient of the arrival time points away from the boundary
+            let grad = |a: Option<f32>, b: Option<f32>| match (a, b) {
+                (Some(a), Some(b)) => (b - a) / 2.0,
+                (None, Some(b)) => b - tn,
+                (Some(a), None) => tn - a,
+                (None, None) => 0.0,
+            };
+            let gx = grad(left, right);
+            let gy = grad(up, down);
+
+            let mut sum = [0.0f32; 4];
+            let mut weight = 0.0f32;
+            for qy in (y - TELEA_RADIUS).max(0)..=(y + TELEA_RADIUS).min(h - 1) {
+                for qx in (x - TELEA_RADIUS).max(0)..=(x + TELEA_RADIUS).min(w - 1) {
+                    let q = (qy * w + qx) as usize;
+                    let (rx, ry) = ((x - qx) as f32, (y - qy) as f32);
+                    let len2 = rx * rx + ry * ry;
+                    if flags[q] != Flag::Known || len2 > (TELEA_RADIUS * TELEA_RADIUS) as f32 {
+                        continue;
+                    }
+                    let dir = ((rx * gx + ry * gy) / len2.sqrt()).abs().max(1e-6);
+                    let dst = 1.0 / len2;
+                    let lev = 1.0 / (1.0 + (t[q] - tn).abs());
+                    let wt = dir * dst * lev;
+                    // first-order estimate from q, using its colour gradient
+                    let (ix, iy) = image_gradient(level, &flags, qx, qy);
+                    for c in 0..4 {
+                        sum[c] += (level.pixels[q][c] + ix[c] * rx + iy[c] * ry) * wt;
+                    }
+                    weight += wt;
+                }
+            }
+            if weight > 0.0 {
+                level.pixels[nb] = sum.map(|v| (v / weight).clamp(0.0, 1.0));
+            }
+            flags[nb] = Flag::Band;
+            heap.push(Front { t: tn, idx: nb });
+        }
+    }
+    level.hole.fill(false);
+}
+
+// halves the level, averaging only known pixels; a coarse pixel is a hole
+// if any of its children is, so coarse sources never contain hole colours
+fn downsample(level: &Level) -> Level {
+    let (w, h) = (level.width, level.height);
+    let (cw, ch) = (w.div_ceil(2), h.div_ceil(2));
+    let mut pixels = vec![[0.0f32; 4]; (cw * ch) as usize];
+    let mut hole = vec![false; (cw * ch) as usize];
+    for cy in 0..ch {
+        for cx in 0..cw {
+            let ci = (cy * cw + cx) as usize;
+            let mut sum = [0.0f32; 4];
+            let mut count = 0.0;
+            for y in cy * 2..(cy * 2 + 2).min(h) {
+                for x in cx * 2..(cx * 2 + 2).min(w) {
+                    let i = (y * w + x) as usize;
+                    if level.hole[i] {
+                        hole[ci] = true;
+                    } else {
+                        for (acc, v) in sum.iter_mut().zip(level.pixels[i]) {
+                            *acc += v;
+                        }
+                        count += 1.0;
+                    }
+                }
+            }
+            if count > 0.0 {
+                pixels[ci] = sum.map(|v| v / count);
+            }
+        }
+    }
+    Level {
+        pixels,
+        hole,
+        width: cw,
+        height: ch,
+    }
+}
+
+fn hole_depth(level: &Level) -> f32 {
+    let known: Vec<bool> = level.hole.iter().map(|&h| !h).collect();
+    distance_transform(&known, level.width, level.height)
+        .iter()
+        .zip(&level.hole)
+        .filter(|(_, &h)| h)
+        .map(|(&d, _)| d)
+        .fold(0.0, f32::max)
+}
+
+// exemplar filling for one pyramid level
+struct PatchMatch<'a> {
+    level: &'a mut Level,
+    // patch centres whose whole patch is inside the image and outside the hole
+    valid: Vec<bool>,
+    valid_list: Vec<usize>,
+    // pixels whose patch touches the hole, the ones that need a match
+    targets: Vec<usize>,
+    // hole mask at this level, kept after the level is filled
+    hole: Vec<bool>,
+}
+
+impl<'a> PatchMatch<'a> {
+    fn new(level: &'a mut Level) -> PatchMatch<'a> {
+        let (w, h) = (level.width, level.height);
+        let r = PATCH_RADIUS as u32;
+        let holes: Vec<f64> = level.hole.iter().map(|&b| b as u8 as f64).collect();
+        let table = integral(&holes, w, h);
+        let patch = 2 * r + 1;
+
+        let mut valid = vec![false; level.pixels.len()];
+        let mut targets = Vec::new();
+        for y in 0..h {
+            for x in 0..w {
+                let i = (y * w + x) as usize;
+                if x >= r && y >= r && x + r < w && y + r < h {
+                    valid[i] = window_sum(&table, w, x - r, y - r, patch, patch) == 0.0;
+                }
+                let (x0, y0) = (x.saturating_sub(r), y.saturating_sub(r));
+                let (x1, y1) = ((x + r + 1).min(w), (y + r + 1).min(h));
+                if window_sum(&table, w, x0, y0, x1 - x0, y1 - y0) > 0.0 {
+                    targets.push(i);
+                }
+            }
+        }
+        let valid_list = (0..valid.len()).filter(|&i| valid[i]).collect();
+        let hole = level.hole.clone();
+        PatchMatch {
+            level,
+            valid,
+            valid_list,
+            targets,
+            hole,
+        }
+    }
+
+    // mean squared difference over the part of the target patch inside the image
+    fn distance(&self, target: usize, source: usize) -> f32 {
+        let (w, h) = (self.level.width as i64, self.level.height as i64);
+        let (tx, ty) = (target as i64 % w, target as i64 / w);
+        let (sx, sy) = (source as i64 % w, source as i64 / w);
+        let mut sum = 0.0f32;
+        let mut count = 0;
+        for dy in -PATCH_RADIUS..=PATCH_RADIUS {
+            let y = ty + dy;
+            if y < 0 || y >= h {
+                continue;
+            }
+            for dx in -PATCH_RADIUS..=PATCH_RADIUS {
+                let x = tx + dx;
+                if x < 0 || x >= w {
+                    continue;
+                }
+                let a = self.level.pixels[(y * w + x) as usize];
+                let b = self.level.pixels[((sy + dy) * w + sx + dx) as usize];
+                for c in 0..4 {
+                    sum += (a[c] - b[c]) * (a[c] - b[c]);
+                }
+                count += 4;
+            }
+        }
+        sum / count as f32
+    }
+
+    fn shifted(&self, source: usize, dx: i64, dy: i64) -> Option<usize> {
+        let w = self.level.width as i64;
+        let (x, y) = (source as i64 % w + dx, source as i64 / w + dy);
+        if x < 0 || y < 0 || x >= w || y >= self.level.height as i64 {
+            return None;
+        }
+        let idx = (y * w + x) as usize;
+        self.valid[idx].then_some(idx)
+    }
+
+    fn improve(&mut self, nnf: &mut [usize], cost: &mut [f32], rng: &mut Rng, pass: usize) {
+        let w = self.level.width as i64;
+        let reverse = pass % 2 == 1;
+        let step: i64 = if reverse { -1 } else { 1 };
+        let order: Vec<usize> = if reverse {
+            self.targets.iter().rev().copied().collect()
+        } else {
+            self.targets.clone()
+        };
+
+        for t in order {
+            let (tx, ty) = (t as i64 % w, t as i64 / w);
+            let try_source = |pm: &Self, s: usize, nnf: &mut [usize], cost: &mut [f32]| {
+                let d = pm.distance(t, s);
+                if d < cost[t] {
+                    cost[t] = d;
+                    nnf[t] = s;
+                }
+            };
+
+            // propagation: reuse the already-scanned neighbours' matches, shifted
+            for (dx, dy) in [(-step, 0), (0, -step)] {
+                let (nx, ny) = (tx + dx, ty + dy);
+                if nx < 0 || ny < 0 || nx >= w || ny >= self.level.height as i64 {
+                    continue;
+                }
+                let neighbour = nnf[(ny * w + nx) as usize];
+                if neighbour == NONE {
+                    continue;
+                }
+                if let Some(s) = self.shifted(neighbour, -dx, -dy) {
+                    try_source(self, s, nnf, cost);
+                }
+            }
+
+            // random search in windows shrinking around the current match
+            let mut radius = self.level.width.max(self.level.height) as i64;
+            while radius >= 1 {
+                let current = nnf[t];
+                let dx = rng.below((2 * radius + 1) as u32) as i64 - radius;
+                let dy = rng.below((2 * radius + 1) as u32) as i64 - radius;
+                if let Some(s) = self.shifted(current, dx, dy) {
+                    try_source(self, s, nnf, cost);
+                }
+                radius /= 2;
+            }
+        }
+    }
+
+    // every hole pixel becomes the weighted average of what the overlapping
+    // patches' matches say it should be
+    fn vote(&mut self, nnf: &[usize], cost: &[f32]) {
+        let (w, h) = (self.level.width as i64, self.level.height as i64);
+        let mut sum = vec![[0.0f32; 4]; self.level.pixels.len()];
+        let mut weight = vec![0.0f32; self.level.pixels.len()];
+        for &t in &self.targets {
+            let s = nnf[t];
+            let wt = (-cost[t] * VOTE_SHARPNESS).exp().max(1e-6);
+            let (tx, ty) = (t as i64 % w, t as i64 / w);
+            let (sx, sy) = (s as i64 % w, s as i64 / w);
+            for dy in -PATCH_RADIUS..=PATCH_RADIUS {
+                for dx in -PATCH_RADIUS..=PATCH_RADIUS {
+                    let (x, y) = (tx + dx, ty + dy);
+                    if x < 0 || y < 0 || x >= w || y >= h {
+                        continue;
+                    }
+                    let i = (y * w + x) as usize;
+                    if !self.hole[i] {
+                        continue;
+                    }
+                    let src = self.level.pixels[((sy + dy) * w + sx + dx) as usize];
+                    for c in 0..4 {
+                        sum[i][c] += src[c] * wt;
+                    }
+                    weight[i] += wt;
+                }
+            }
+        }
+        for (i, px) in self.level.pixels.iter_mut().enumerate() {
+            if self.hole[i] && weight[i] > 0.0 {
+                *px = sum[i].map(|v| v / weight[i]);
+            }
+        }
+    }
+
+    // runs em rounds from the given starting matches and returns the final ones
+    fn run(&mut self, mut nnf: Vec<usize>, rounds: usize, rng: &mut Rng) -> Vec<usize> {
+        for _ in 0..rounds {
+            let mut cost = vec![f32::INFINITY; nnf.len()];
+            for &t in &self.targets {
+                if nnf[t] == NONE || !self.valid[nnf[t]] {
+                    nnf[t] = self.valid_list[rng.below(self.valid_list.len() as u32) as usize];
+                }
+                cost[t] = self.distance(t, nnf[t]);
+            }
+            for pass in 0..PATCHMATCH_PASSES {
+                self.improve(&mut nnf, &mut cost, rng, pass);
+            }
+            self.vote(&nnf, &cost);
+        }
+        nnf
+    }
+}
+
+// coarse-to-fine exemplar inpainting; the coarsest level is seeded by telea
+fn patch_match(full: Level, seed: u32) -> Level {
+    let mut levels = vec![full];
+    loop {
+        let last = levels.last().unwrap();
+        let smaller = last.width / 2 < COARSEST_SIZE || last.height / 2 < COARSEST_SIZE;
+        if smaller || hole_depth(last) <= COARSEST_DEPTH {
+            break;
+        }
+        let next = downsample(last);
+        levels.push(next);
+    }
+
+    let mut rng = Rng::new(seed);
+    let mut previous: Option<(Level, Vec<usize>)> = None;
+    while let Some(mut level) = levels.pop() {
+        let hole = level.hole.clone();
+        let (w, h) = (level.width, level.height);
+        let mut nnf = vec![NONE; level.pixels.len()];
+        let rounds = match &previous {
+            None => {
+                let mut seeded = Level {
+                    pixels: level.pixels.clone(),
+                    hole: hole.clone(),
+                    width: w,
+                    height: h,
+                };
+                telea(&mut seeded);
+                level.pixels = seeded.pixels;
+                COARSE_EM_ROUNDS
+            }
+            Some((coarse, coarse_nnf)) => {
+                // start from the coarse result and its matches scaled up
+                for y in 0..h {
+                    for x in 0..w {
+                        let i = (y * w + x) as usize;
+                        let ci = ((y / 2).min(coarse.height - 1) * coarse.width
+                            + (x / 2).min(coarse.width - 1))
+                            as usize;
+                        if hole[i] {
+                            level.pixels[i] = coarse.pixels[ci];
+                        }
+                        let m = coarse_nnf[ci];
+                        if m != NONE {
+                            let (mx, my) = (m as u32 % coarse.width, m as u32 / coarse.width);
+                            let (fx, fy) = (mx * 2 + x % 2, my * 2 + y % 2);
+                            if fx < w && fy < h {
+                                nnf[i] = (fy * w + fx) as usize;
+                            }
+                        }
+                    }
+                }
+                FINE_EM_ROUNDS
+            }
+        };
+
+        let mut pm = PatchMatch::new(&mut level);
+        if pm.valid_list.is_empty() || pm.targets.is_empty() {
+            // nothing to copy from at this scale, telea alone will have to do
+            drop(pm);
+            telea(&mut level);
+        } else {
+            nnf = pm.run(nnf, rounds, &mut rng);
+        }
+        level.hole.fill(false);
+        previous = Some((level, nnf));
+    }
+    previous.unwrap().0
+}
+
+// fills every pixel where the mask is set, returns None when nothing is left to fill from
+pub fn inpaint(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    mask: &[u8],
+    method: InpaintMethod,
+    seed: u32,
+) -> Option<Vec<u8>> {
+    let hole: Vec<bool> = mask.iter().map(|&m| m >= 128).collect();
+    if hole.iter().all(|&h| h) {
+        return None;
+    }
+    let level = Level {
+        pixels: data
+            .chunks_exact(4)
+            .map(|p| [p[0], p[1], p[2], p[3]].map(|v| v as f32 / 255.0))
+            .collect(),
+        hole: hole.clone(),
+        width,
+        height,
+    };
+
+    let method = match method {
+        InpaintMethod::Auto if hole_depth(&level) <= AUTO_TELEA_DEPTH => InpaintMethod::Telea,
+        InpaintMethod::Auto => InpaintMethod::PatchMatch,
+        m => m,
+    };
+    let filled = match method {
+        InpaintMethod::PatchMatch => patch_match(level, seed),
+        _ => {
+            let mut level = level;
+            telea(&mut level);
+            level
+        }
+    };
+
+    let mut result = data.to_vec();
+    for (i, px) in filled.pixels.iter().enumerate() {
+        if hole[i] {
+            for c in 0..4 {
+                result[i * 4 + c] = (px[c] * 255.0).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // horizontal gradient, which both methods should continue smoothly
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        (0..width * height)
+            .flat_map(|i| {
+                let v = (i % width * 255 / (width - 1)) as u8;
+                [v, 100, 255 - v, 255]
+            })
+            .collect()
+    }
+
+    fn square_mask(width: u32, height: u32, x0: u32, y0: u32, size: u32) -> Vec<u8> {
+        (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                if x >= x0 && x < x0 + size && y >= y0 && y < y0 + size {
+                    255
+                } else {
+                    0
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_telea_fills_small_hole() {
+        let data = gradient(20, 20);
+        let mask = square_mask(20, 20, 8, 8, 4);
+        let mut damaged = data.clone();
+        for (i, &m) in mask.iter().enumerate() {
+            if m == 255 {
+                damaged[i * 4..i * 4 + 4].copy_from_slice(&[255, 0, 255, 255]);
+            }
+        }
+        let out = inpaint(&damaged, 20, 20, &mask, InpaintMethod::Telea, 0).unwrap();
+        for (i, &m) in mask.iter().enumerate() {
+            if m == 255 {
+                assert!(out[i * 4].abs_diff(data[i * 4]) <= 16);
+                assert!(out[i * 4 + 1].abs_diff(100) <= 2);
+            } else {
+                assert_eq!(&out[i * 4..i * 4 + 4], &damaged[i * 4..i * 4 + 4]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_patchmatch_is_seeded_and_copies_texture() {
+        // vertical stripes two pixels wide
+        let (w, h) = (48u32, 40u32);
+        let data: Vec<u8> = (0..w * h)
+            .flat_map(|i| {
+                if (i % w / 2) % 2 == 0 {
+                    [0, 0, 0, 255]
+                } else {
+                    [255, 255, 255, 255]
+                }
+            })
+            .collect();
+        let mask = square_mask(w, h, 16, 12, 14);
+        let a = inpaint(&data, w, h, &mask, InpaintMethod::PatchMatch, 42).unwrap();
+        let b = inpaint(&data, w, h, &mask, InpaintMethod::PatchMatch, 42).unwrap();
+        assert_eq!(a, b);
+
+        // the hole is filled with stripes rather than a grey smear
+        let contrast = (16..30)
+            .filter(|&x| {
+                let v = a[((19 * w + x) * 4) as usize];
+                !(64..=192).contains(&v)
+            })
+            .count();
+        assert!(contrast >= 12, "only {} crisp pixels", contrast);
+    }
+
+    #[test]
+    fn test_nothing_to_fill_from() {
+        let data = gradient(4, 4);
+        assert!(inpaint(&data, 4, 4, &[255; 16], InpaintMethod::Auto, 0).is_none());
+    }
+}
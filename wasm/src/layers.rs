@@ -0,0 +1,477 @@
+use wasm_bindgen::prelude::*;
+
+use crate::blend::{composite_pixel, BlendMode};
+use crate::error::{
+    validate_dimensions, validate_image, validate_mask, ImageError, DEFAULT_MAX_PIXELS,
+    PIXEL_LIMIT_CEILING,
+};
+use crate::{filter_image, FilterType};
+
+// half-open canvas rectangle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Rect {
+    x0: i64,
+    y0: i64,
+    x1: i64,
+    y1: i64,
+}
+
+impl Rect {
+    fn union(self, other: Rect) -> Rect {
+        Rect {
+            x0: self.x0.min(other.x0),
+            y0: self.y0.min(other.y0),
+            x1: self.x1.max(other.x1),
+            y1: self.y1.max(other.y1),
+        }
+    }
+
+    fn intersect(self, other: Rect) -> Option<Rect> {
+        let r = Rect {
+            x0: self.x0.max(other.x0),
+            y0: self.y0.max(other.y0),
+            x1: self.x1.min(other.x1),
+            y1: self.y1.min(other.y1),
+        };
+        (r.x0 < r.x1 && r.y0 < r.y1).then_some(r)
+    }
+}
+
+struct Layer {
+    id: u32,
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    x: i32,
+    y: i32,
+    opacity: f32,
+    mode: BlendMode,
+    visible: bool,
+    mask: Option<Vec<u8>>,
+    filters: Vec<(FilterType, f32)>,
+    // pixels after the filter chain, rebuilt lazily
+    filtered: Option<Vec<u8>>,
+}
+
+impl Layer {
+    fn bounds(&self) -> Rect {
+        Rect {
+            x0: self.x as i64,
+            y0: self.y as i64,
+            x1: self.x as i64 + self.width as i64,
+            y1: self.y as i64 + self.height as i64,
+        }
+    }
+
+    fn pixels(&mut self) -> &[u8] {
+        if self.filters.is_empty() {
+            return &self.data;
+        }
+        if self.filtered.is_none() {
+            let mut data = self.data.clone();
+            for &(filter, intensity) in &self.filters {
+                data = filter_image(&data, self.width, self.height, filter, intensity);
+            }
+            self.filtered = Some(data);
+        }
+        self.filtered.as_deref().unwrap_or(&self.data)
+    }
+}
+
+#[wasm_bindgen]
+pub struct LayerStack {
+    width: u32,
+    height: u32,
+    layers: Vec<Layer>,
+    next_id: u32,
+    canvas: Vec<u8>,
+    dirty: Option<Rect>,
+    // pixel limit for layer data, starts at the default like the image processor
+    max_pixels: u64,
+}
+
+#[wasm_bindgen]
+impl LayerStack {
+    #[wasm_bindgen(constructor)]
+    pub fn new(width: u32, height: u32) -> Result<LayerStack, ImageError> {
+        validate_dimensions(width, height, DEFAULT_MAX_PIXELS)?;
+        Ok(LayerStack {
+            width,
+            height,
+            layers: Vec::new(),
+            next_id: 1,
+            canvas: vec![0u8; (width * height * 4) as usize],
+            dirty: None,
+            max_pixels: DEFAULT_MAX_PIXELS,
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // pixel limit for layer data, set the same way as on the image processor
+    #[wasm_bindgen(getter)]
+    pub fn max_pixels(&self) -> u32 {
+        self.max_pixels.min(u32::MAX as u64) as u32
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_pixels(&mut self, max_pixels: u32) {
+        self.max_pixels = (max_pixels as u64).min(PIXEL_LIMIT_CEILING);
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn layer_count(&self) -> u32 {
+        self.layers.len() as u32
+    }
+
+    // layer ids from bottom to top
+    pub fn layer_ids(&self) -> Vec<u32> {
+        self.layers.iter().map(|l| l.id).collect()
+    }
+
+    // adds a layer on top of the stack and returns its id
+    pub fn add_layer(
+        &mut self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        x: i32,
+        y: i32,
+    ) -> Result<u32, ImageError> {
+        validate_image(image_data, width, height, self.max_pixels)?;
+        let id = self.next_id;
+        self.next_id += 1;
+        self.layers.push(Layer {
+            id,
+            data: image_data.to_vec(),
+            width,
+            height,
+            x,
+            y,
+            opacity: 1.0,
+            mode: BlendMode::Normal,
+            visible: true,
+            mask: None,
+            filters: Vec::new(),
+            filtered: None,
+        });
+        let bounds = self.layers[self.layers.len() - 1].bounds();
+        self.invalidate(bounds);
+        Ok(id)
+    }
+
+    pub fn remove_layer(&mut self, id: u32) -> Result<(), ImageError> {
+        let index = self.index_of(id)?;
+        let layer = self.layers.remove(index);
+        self.invalidate(layer.bounds());
+        Ok(())
+    }
+
+    // moves a layer to `index` counted from the bottom
+    pub fn move_layer(&mut self, id: u32, index: u32) -> Result<(), ImageError> {
+        let from = self.index_of(id)?;
+        let layer = self.layers.remove(from);
+        let bounds = layer.bounds();
+        let to = (index as usize).min(self.layers.len());
+        self.layers.insert(to, layer);
+        self.invalidate(bounds);
+        Ok(())
+    }
+
+    // replaces the pixels of a layer, the size may change
+    pub fn set_layer_data(
+        &mut self,
+        id: u32,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<(), ImageError> {
+        validate_image(image_data, width, height, self.max_pixels)?;
+        let layer = self.layer_mut(id)?;
+        if layer.mask.is_some() && (layer.width, layer.height) != (width, height) {
+            return Err(ImageError::invalid(
+                "remove the mask before resizing a masked layer",
+            ));
+        }
+        let old = layer.bounds();
+        layer.data = image_data.to_vec();
+        layer.width = width;
+        layer.height = height;
+        layer.filtered = None;
+        let new = layer.bounds();
+        self.invalidate(old.union(new));
+        Ok(())
+    }
+
+    pub fn set_offset(&mut self, id: u32, x: i32, y: i32) -> Result<(), ImageError> {
+        let layer = self.layer_mut(id)?;
+        let old = layer.bounds();
+        layer.x = x;
+        layer.y = y;
+        let new = layer.bounds();
+        self.invalidate(old);
+        self.invalidate(new);
+        Ok(())
+    }
+
+    pub fn set_opacity(&mut self, id: u32, opacity: f32) -> Result<(), ImageError> {
+        let layer = self.layer_mut(id)?;
+        layer.opacity = opacity.clamp(0.0, 1.0);
+        let bounds = layer.bounds();
+        self.invalidate(bounds);
+        Ok(())
+    }
+
+    pub fn set_blend_mode(&mut self, id: u32, mode: &str) -> Result<(), ImageError> {
+        let mode = BlendMode::from_string(mode)
+            .ok_or_else(|| ImageError::invalid(format!("unknown blend mode: {}", mode)))?;
+        let layer = self.layer_mut(id)?;
+        layer.mode = mode;
+        let bounds = layer.bounds();
+        self.invalidate(bounds);
+        Ok(())
+    }
+
+    pub fn set_visible(&mut self, id: u32, visible: bool) -> Result<(), ImageError> {
+        let layer = self.layer_mut(id)?;
+        layer.visible = visible;
+        let bounds = layer.bounds();
+        self.invalidate(bounds);
+        Ok(())
+    }
+
+    // one byte per layer pixel, scales the layer alpha; pass nothing to clear it
+    pub fn set_mask(&mut self, id: u32, mask: Option<Vec<u8>>) -> Result<(), ImageError> {
+        let layer = self.layer_mut(id)?;
+        if let Some(mask) = &mask {
+            validate_mask(mask, layer.width, layer.height)?;
+        }
+        layer.mask = mask;
+        let bounds = layer.bounds();
+        self.invalidate(bounds);
+        Ok(())
+    }
+
+    // filter names and intensities are applied in order before masking and blending
+    pub fn set_filters(
+        &mut self,
+        id: u32,
+        filters: Vec<String>,
+        intensities: &[f32],
+    ) -> Result<(), ImageError> {
+        if filters.len() != intensities.len() {
+            return Err(ImageError::invalid("each filter needs one intensity"));
+        }
+        let chain = filters
+            .iter()
+            .zip(intensities)
+            .map(|(name, &intensity)| {
+                FilterType::from_string(name)
+                    .map(|filter| (filter, intensity))
+                    .ok_or_else(|| ImageError::UnknownFilter(name.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let layer = self.layer_mut(id)?;
+        layer.filters = chain;
+        layer.filtered = None;
+        let bounds = layer.bounds();
+        self.invalidate(bounds);
+        Ok(())
+    }
+
+    // renders whatever changed since the last call and returns the whole canvas
+    pub fn flatten(&mut self) -> Vec<u8> {
+        if let Some(region) = self.dirty.take() {
+            self.render(region);
+        }
+        self.canvas.clone()
+    }
+
+    // the pending region as [x, y, width, height], empty when nothing changed
+    pub fn dirty_region(&self) -> Vec<u32> {
+        match self.dirty {
+            Some(r) => vec![
+                r.x0 as u32,
+                r.y0 as u32,
+                (r.x1 - r.x0) as u32,
+                (r.y1 - r.y0) as u32,
+            ],
+            None => Vec::new(),
+        }
+    }
+}
+
+impl LayerStack {
+    fn index_of(&self, id: u32) -> Result<usize, ImageError> {
+        self.layers
+            .iter()
+            .position(|l| l.id == id)
+            .ok_or_else(|| ImageError::invalid(format!("unknown layer: {}", id)))
+    }
+
+    fn layer_mut(&mut self, id: u32) -> Result<&mut Layer, ImageError> {
+        let index = self.index_of(id)?;
+        Ok(&mut self.layers[index])
+    }
+
+    fn canvas_rect(&self) -> Rect {
+        Rect {
+            x0: 0,
+            y0: 0,
+            x1: self.width as i64,
+            y1: self.height as i64,
+        }
+    }
+
+    // marks the on-canvas part of `rect` for re-rendering
+    fn invalidate(&mut self, rect: Rect) {
+        if let Some(rect) = rect.intersect(self.canvas_rect()) {
+            self.dirty = Some(match self.dirty {
+                Some(dirty) => dirty.union(rect),
+                None => rect,
+            });
+        }
+    }
+
+    fn render(&mut self, region: Rect) {
+        let width = self.width as i64;
+        for y in region.y0..region.y1 {
+            let start = ((y * width + region.x0) * 4) as usize;
+            let end = ((y * width + region.x1) * 4) as usize;
+            self.canvas[start..end].fill(0);
+        }
+
+        for layer in self.layers.iter_mut() {
+            if !layer.visible || layer.opacity <= 0.0 {
+                continue;
+            }
+            let Some(area) = layer.bounds().intersect(region) else {
+                continue;
+            };
+            let (lx, ly, lw) = (layer.x as i64, layer.y as i64, layer.width as i64);
+            let (opacity, mode) = (layer.opacity, layer.mode);
+            let mask = layer.mask.take();
+            let pixels = layer.pixels();
+
+            for y in area.y0..area.y1 {
+                for x in area.x0..area.x1 {
+                    let li = ((y - ly) * lw + (x - lx)) as usize;
+                    let src = li * 4;
+                    let mut source = [
+                        pixels[src],
+                        pixels[src + 1],
+                        pixels[src + 2],
+                        pixels[src + 3],
+                    ];
+                    if let Some(mask) = &mask {
+                        source[3] = ((source[3] as u16 * mask[li] as u16 + 127) / 255) as u8;
+                    }
+
+                    let dst = ((y * width + x) * 4) as usize;
+                    let backdrop = [
+                        self.canvas[dst],
+                        self.canvas[dst + 1],
+                        self.canvas[dst + 2],
+                        self.canvas[dst + 3],
+                    ];
+                    self.canvas[dst..dst + 4]
+                        .copy_from_slice(&composite_pixel(backdrop, source, opacity, mode));
+                }
+            }
+            layer.mask = mask;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> Vec<u8> {
+        (0..width * height).flat_map(|_| color).collect()
+    }
+
+    #[test]
+    fn test_flatten_order_and_opacity() {
+        let mut stack = LayerStack::new(4, 4).unwrap();
+        stack
+            .add_layer(&solid(4, 4, [255, 0, 0, 255]), 4, 4, 0, 0)
+            .unwrap();
+        let top = stack
+            .add_layer(&solid(2, 2, [0, 0, 255, 255]), 2, 2, 1, 1)
+            .unwrap();
+        stack.set_opacity(top, 0.5).unwrap();
+
+        let out = stack.flatten();
+        assert_eq!(&out[0..4], &[255, 0, 0, 255]);
+        let idx = ((4 + 1) * 4) as usize;
+        assert_eq!(&out[idx..idx + 4], &[128, 0, 128, 255]);
+        assert!(stack.dirty_region().is_empty());
+    }
+
+    #[test]
+    fn test_partial_render_matches_full_render() {
+        let mut stack = LayerStack::new(6, 5).unwrap();
+        stack
+            .add_layer(&solid(6, 5, [10, 200, 30, 255]), 6, 5, 0, 0)
+            .unwrap();
+        let moving = stack
+            .add_layer(&solid(2, 2, [250, 250, 0, 200]), 2, 2, 0, 0)
+            .unwrap();
+        stack.set_blend_mode(moving, "multiply").unwrap();
+        stack.flatten();
+
+        stack.set_offset(moving, 3, 2).unwrap();
+        assert_eq!(stack.dirty_region(), vec![0, 0, 5, 4]);
+        let incremental = stack.flatten();
+
+        let mut fresh = LayerStack::new(6, 5).unwrap();
+        fresh
+            .add_layer(&solid(6, 5, [10, 200, 30, 255]), 6, 5, 0, 0)
+            .unwrap();
+        let id = fresh
+            .add_layer(&solid(2, 2, [250, 250, 0, 200]), 2, 2, 3, 2)
+            .unwrap();
+        fresh.set_blend_mode(id, "multiply").unwrap();
+        assert_eq!(incremental, fresh.flatten());
+    }
+
+    #[test]
+    fn test_mask_and_visibility() {
+        let mut stack = LayerStack::new(2, 1).unwrap();
+        let id = stack
+            .add_layer(&solid(2, 1, [255, 255, 255, 255]), 2, 1, 0, 0)
+            .unwrap();
+        stack.set_mask(id, Some(vec![255, 0])).unwrap();
+        assert_eq!(stack.flatten(), vec![255, 255, 255, 255, 0, 0, 0, 0]);
+
+        stack.set_visible(id, false).unwrap();
+        assert_eq!(stack.flatten(), vec![0; 8]);
+        assert!(stack.set_visible(99, true).is_err());
+    }
+
+    #[test]
+    fn test_pixel_limit_applies_to_layers() {
+        let mut stack = LayerStack::new(4, 4).unwrap();
+        assert_eq!(stack.max_pixels() as u64, DEFAULT_MAX_PIXELS);
+        stack.set_max_pixels(16);
+        assert!(stack.add_layer(&solid(4, 4, [0; 4]), 4, 4, 0, 0).is_ok());
+        assert!(stack.add_layer(&solid(5, 4, [0; 4]), 5, 4, 0, 0).is_err());
+        let id = stack.layer_ids()[0];
+        assert!(stack
+            .set_layer_data(id, &solid(1, 17, [0; 4]), 1, 17)
+            .is_err());
+
+        stack.set_max_pixels(u32::MAX);
+        assert_eq!(stack.max_pixels() as u64, PIXEL_LIMIT_CEILING);
+    }
+}
@@ -0,0 +1,395 @@
+use crate::{box_blur, crop_image, resize_image};
+
+// longest edge of the downscaled copy used for blurred fills
+const BLUR_PREVIEW_SIZE: f32 = 32.0;
+const BLUR_PREVIEW_RADIUS: i32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fill {
+    Color([u8; 4]),
+    Blur,
+    Mirror,
+}
+
+impl Fill {
+    pub fn from_string(s: &str, color: [u8; 4]) -> Option<Fill> {
+        match s {
+            "color" => Some(Fill::Color(color)),
+            "blur" => Some(Fill::Blur),
+            "mirror" => Some(Fill::Mirror),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FitMode {
+    // scale to fit inside the target, output keeps the scaled size
+    Fit,
+    // stretch to the exact target size
+    Fill,
+    // scale to fit inside the target and pad the rest
+    Contain,
+    // scale to cover the target and crop the overflow
+    Cover,
+}
+
+impl FitMode {
+    pub fn from_string(s: &str) -> Option<FitMode> {
+        match s {
+            "fit" => Some(FitMode::Fit),
+            "fill" => Some(FitMode::Fill),
+            "contain" => Some(FitMode::Contain),
+            "cover" => Some(FitMode::Cover),
+            _ => None,
+        }
+    }
+}
+
+// horizontal and vertical placement, 0 is left/top and 1 is right/bottom
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Anchor {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Anchor {
+    pub fn from_string(s: &str) -> Option<Anchor> {
+        let (x, y) = match s {
+            "center" => (0.5, 0.5),
+            "top" => (0.5, 0.0),
+            "bottom" => (0.5, 1.0),
+            "left" => (0.0, 0.5),
+            "right" => (1.0, 0.5),
+            "top-left" => (0.0, 0.0),
+            "top-right" => (1.0, 0.0),
+            "bottom-left" => (0.0, 1.0),
+            "bottom-right" => (1.0, 1.0),
+            _ => return None,
+        };
+        Some(Anchor { x, y })
+    }
+
+    fn offset_x(&self, free: u32) -> u32 {
+        (free as f32 * self.x).round() as u32
+    }
+
+    fn offset_y(&self, free: u32) -> u32 {
+        (free as f32 * self.y).round() as u32
+    }
+}
+
+// reflects an out-of-range coordinate back into 0..len without repeating the edge twice
+fn reflect(i: i64, len: u32) -> u32 {
+    let len = len as i64;
+    if len == 1 {
+        return 0;
+    }
+    let period = 2 * len;
+    let m = i.rem_euclid(period);
+    (if m >= len { period - 1 - m } else { m }) as u32
+}
+
+// builds a canvas background for an image that will sit at (x, y)
+#[allow(clippy::too_many_arguments)]
+pub fn background(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    canvas_width: u32,
+    canvas_height: u32,
+    x: u32,
+    y: u32,
+    fill: Fill,
+) -> Vec<u8> {
+    match fill {
+        Fill::Color(color) => (0..canvas_width * canvas_height)
+            .flat_map(|_| color)
+            .collect(),
+        Fill::Blur => {
+            // blur a tiny copy and let the bilinear upscale soften it further
+            let scale = BLUR_PREVIEW_SIZE / canvas_width.max(canvas_height) as f32;
+            let small_w = ((canvas_width as f32 * scale).round() as u32).max(1);
+            let small_h = ((canvas_height as f32 * scale).round() as u32).max(1);
+            let centre = Anchor { x: 0.5, y: 0.5 };
+            let (cx, cy, cw, ch) = cover_window(width, height, small_w, small_h, centre);
+            let visible = crop_image(data, width, cx, cy, cw, ch);
+            let small = resize_image(&visible, cw, ch, small_w, small_h);
+            let blurred = box_blur(&small, small_w, small_h, BLUR_PREVIEW_RADIUS);
+            resize_image(&blurred, small_w, small_h, canvas_width, canvas_height)
+        }
+        Fill::Mirror => {
+            let mut result = vec![0u8; (canvas_width * canvas_height * 4) as usize];
+            for cy in 0..canvas_height {
+                let sy = reflect(cy as i64 - y as i64, height);
+                for cx in 0..canvas_width {
+                    let sx = reflect(cx as i64 - x as i64, width);
+                    let src = ((sy * width + sx) * 4) as usize;
+                    let dst = ((cy * canvas_width + cx) * 4) as usize;
+                    result[dst..dst + 4].copy_from_slice(&data[src..src + 4]);
+                }
+            }
+            result
+        }
+    }
+}
+
+// draws the image over the canvas at (x, y) with source-over alpha
+pub fn place(
+    canvas: &mut [u8],
+    canvas_width: u32,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+) {
+    for row in 0..height {
+        for col in 0..width {
+            let src = ((row * width + col) * 4) as usize;
+            let dst = (((y + row) * canvas_width + x + col) * 4) as usize;
+
+            let sa = data[src + 3] as f32 / 255.0;
+            if sa >= 1.0 {
+                canvas[dst..dst + 4].copy_from_slice(&data[src..src + 4]);
+                continue;
+            }
+            let da = canvas[dst + 3] as f32 / 255.0;
+            let out_a = sa + da * (1.0 - sa);
+            if out_a <= 0.0 {
+                canvas[dst..dst + 4].copy_from_slice(&[0, 0, 0, 0]);
+                continue;
+            }
+            for c in 0..3 {
+                let value =
+                    (data[src + c] as f32 * sa + canvas[dst + c] as f32 * da * (1.0 - sa)) / out_a;
+                canvas[dst + c] = value.round().clamp(0.0, 255.0) as u8;
+            }
+            canvas[dst + 3] = (out_a * 255.0).round() as u8;
+        }
+    }
+}
+
+// pads each side of the image, returns the data and the new size
+#[allow(clippy::too_many_arguments)]
+pub fn extend(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+    left: u32,
+    fill: Fill,
+) -> (Vec<u8>, u32, u32) {
+    let canvas_width = width + left + right;
+    let canvas_height = height + top + bottom;
+    let mut canvas = background(
+        data,
+        width,
+        height,
+        canvas_width,
+        canvas_height,
+        left,
+        top,
+        fill,
+    );
+    place(&mut canvas, canvas_width, data, width, height, left, top);
+    (canvas, canvas_width, canvas_height)
+}
+
+// size of the image once scaled for fit, fill or contain, before any padding;
+// cover never scales the whole image, see cover_window
+fn scaled_size(
+    width: u32,
+    height: u32,
+    target_width: u32,
+    target_height: u32,
+    mode: FitMode,
+) -> (u32, u32) {
+    let sx = target_width as f32 / width as f32;
+    let sy = target_height as f32 / height as f32;
+    match mode {
+        FitMode::Fill => (target_width, target_height),
+        _ => {
+            let scale = sx.min(sy);
+            (
+                ((width as f32 * scale).round() as u32).clamp(1, target_width),
+                ((height as f32 * scale).round() as u32).clamp(1, target_height),
+            )
+        }
+    }
+}
+
+// part of the source that stays visible once cover scales it past the target
+fn cover_window(
+    width: u32,
+    height: u32,
+    target_width: u32,
+    target_height: u32,
+    anchor: Anchor,
+) -> (u32, u32, u32, u32) {
+    let scale = (target_width as f32 / width as f32).max(target_height as f32 / height as f32);
+    let w = ((target_width as f32 / scale).round() as u32).clamp(1, width);
+    let h = ((target_height as f32 / scale).round() as u32).clamp(1, height);
+    (
+        anchor.offset_x(width - w),
+        anchor.offset_y(height - h),
+        w,
+        h,
+    )
+}
+
+// lays the image out in the target box, returns the data and the output size
+#[allow(clippy::too_many_arguments)]
+pub fn fit(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    target_width: u32,
+    target_height: u32,
+    mode: FitMode,
+    anchor: Anchor,
+    fill: Fill,
+) -> (Vec<u8>, u32, u32) {
+    // cover crops first so only the target-sized result is ever scaled
+    if mode == FitMode::Cover {
+        let (x, y, w, h) = cover_window(width, height, target_width, target_height, anchor);
+        let visible = crop_image(data, width, x, y, w, h);
+        let scaled = if (w, h) == (target_width, target_height) {
+            visible
+        } else {
+            resize_image(&visible, w, h, target_width, target_height)
+        };
+        return (scaled, target_width, target_height);
+    }
+
+    let (scaled_w, scaled_h) = scaled_size(width, height, target_width, target_height, mode);
+    let scaled = if (scaled_w, scaled_h) == (width, height) {
+        data.to_vec()
+    } else {
+        resize_image(data, width, height, scaled_w, scaled_h)
+    };
+
+    if mode != FitMode::Contain {
+        return (scaled, scaled_w, scaled_h);
+    }
+    let x = anchor.offset_x(target_width - scaled_w);
+    let y = anchor.offset_y(target_height - scaled_h);
+    let mut canvas = background(
+        &scaled,
+        scaled_w,
+        scaled_h,
+        target_width,
+        target_height,
+        x,
+        y,
+        fill,
+    );
+    place(&mut canvas, target_width, &scaled, scaled_w, scaled_h, x, y);
+    (canvas, target_width, target_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reflect() {
+        let row: Vec<u32> = (-3..7).map(|i| reflect(i, 3)).collect();
+        assert_eq!(row, vec![2, 1, 0, 0, 1, 2, 2, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_scaled_size() {
+        assert_eq!(
+            scaled_size(200, 100, 1080, 1350, FitMode::Contain),
+            (1080, 540)
+        );
+        assert_eq!(scaled_size(200, 100, 50, 50, FitMode::Fit), (50, 25));
+        assert_eq!(scaled_size(200, 100, 50, 50, FitMode::Fill), (50, 50));
+    }
+
+    #[test]
+    fn test_contain_pads_with_color() {
+        let data = vec![255u8; 4 * 2 * 4];
+        let anchor = Anchor::from_string("top").unwrap();
+        let (out, w, h) = fit(
+            &data,
+            4,
+            2,
+            4,
+            4,
+            FitMode::Contain,
+            anchor,
+            Fill::Color([0, 0, 0, 255]),
+        );
+        assert_eq!((w, h), (4, 4));
+        assert_eq!(&out[0..4], &[255, 255, 255, 255]);
+        assert_eq!(
+            &out[(3 * 4 * 4) as usize..(3 * 4 * 4 + 4) as usize],
+            &[0, 0, 0, 255]
+        );
+    }
+
+    #[test]
+    fn test_cover_crops_the_anchored_side() {
+        // one row of four colours, each in its red channel
+        let data: Vec<u8> = [10, 20, 30, 40]
+            .iter()
+            .flat_map(|&r| [r, 0, 0, 255])
+            .collect();
+        let cover = |anchor: &str| {
+            let anchor = Anchor::from_string(anchor).unwrap();
+            let (out, w, h) = fit(
+                &data,
+                4,
+                1,
+                2,
+                1,
+                FitMode::Cover,
+                anchor,
+                Fill::Color([0; 4]),
+            );
+            assert_eq!((w, h), (2, 1));
+            out.chunks_exact(4).map(|p| p[0]).collect::<Vec<u8>>()
+        };
+        assert_eq!(cover("left"), vec![10, 20]);
+        assert_eq!(cover("center"), vec![20, 30]);
+        assert_eq!(cover("right"), vec![30, 40]);
+
+        // a thin strip covering a square never builds the full scaled strip
+        let strip = vec![200u8; 4 * 4000];
+        let anchor = Anchor::from_string("center").unwrap();
+        let (out, w, h) = fit(
+            &strip,
+            1,
+            4000,
+            100,
+            100,
+            FitMode::Cover,
+            anchor,
+            Fill::Color([0; 4]),
+        );
+        assert_eq!((w, h, out.len()), (100, 100, 100 * 100 * 4));
+    }
+
+    #[test]
+    fn test_blur_fill_of_a_thin_strip() {
+        // scaling the whole strip to cover the preview would overflow
+        let strip = vec![90u8; 4 * 400_000];
+        let out = background(&strip, 1, 400_000, 100, 100, 0, 0, Fill::Blur);
+        assert_eq!(out.len(), 100 * 100 * 4);
+        assert!(out.iter().all(|&v| v == 90));
+    }
+
+    #[test]
+    fn test_extend_mirror() {
+        // one row: red, green
+        let data = vec![255, 0, 0, 255, 0, 255, 0, 255];
+        let (out, w, h) = extend(&data, 2, 1, 0, 1, 0, 1, Fill::Mirror);
+        assert_eq!((w, h), (4, 1));
+        let reds: Vec<u8> = out.chunks_exact(4).map(|p| p[0]).collect();
+        assert_eq!(reds, vec![255, 255, 0, 0]);
+    }
+}
@@ -0,0 +1,292 @@
+use crate::color::{linear_to_srgb, srgb_to_linear};
+use crate::utils::smoothstep;
+
+// extra weight of the brightest highlights at full boost
+const HIGHLIGHT_WEIGHT: f32 = 8.0;
+// share of a full-blur sample's weight that a sharp sample keeps
+const SHARP_WEIGHT: f32 = 0.001;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aperture {
+    Disc,
+    Hexagon,
+}
+
+impl Aperture {
+    pub fn from_string(s: &str) -> Option<Aperture> {
+        match s {
+            "disc" => Some(Aperture::Disc),
+            "hexagon" => Some(Aperture::Hexagon),
+            _ => None,
+        }
+    }
+
+    // half width of the aperture `dy` rows from its centre, for a shape that
+    // reaches `r` pixels up and down
+    fn half_width(self, r: f32, dy: f32) -> f32 {
+        let dy = dy.abs();
+        match self {
+            Aperture::Disc => (r * r - dy * dy).max(0.0).sqrt(),
+            // pointed at the top and bottom, upright sides in the middle
+            Aperture::Hexagon => {
+                if dy <= r / 2.0 {
+                    r * 3f32.sqrt() / 2.0
+                } else {
+                    (r - dy) * 3f32.sqrt()
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FocusShape {
+    // a sharp band across the image
+    Linear,
+    // a sharp ellipse around a point
+    Radial,
+}
+
+impl FocusShape {
+    pub fn from_string(s: &str) -> Option<FocusShape> {
+        match s {
+            "linear" => Some(FocusShape::Linear),
+            "radial" => Some(FocusShape::Radial),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FocusBand {
+    pub shape: FocusShape,
+    // centre of the band as a share of the width and height
+    pub center: [f32; 2],
+    // direction of a linear band in degrees, 0 runs left to right
+    pub angle: f32,
+    // half size of the sharp area and the width of the fade to full blur, as
+    // shares of the image height for a linear band or of the half size of the
+    // image for a radial one
+    pub focus: f32,
+    pub falloff: f32,
+}
+
+// blur amount per pixel for tilt-shift: 0 inside the focus band, easing up to
+// 255 past its falloff
+pub fn focus_band_mask(width: u32, height: u32, band: &FocusBand) -> Vec<u8> {
+    let (w, h) = (width as f32, height as f32);
+    let (cx, cy) = (band.center[0] * w, band.center[1] * h);
+    let (sin, cos) = band.angle.to_radians().sin_cos();
+    let falloff = band.falloff.max(1e-3);
+
+    let mut mask = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+            let d = match band.shape {
+                FocusShape::Linear => (-dx * sin + dy * cos).abs() / h,
+                FocusShape::Radial => {
+                    let (u, v) = (dx / (w / 2.0), dy / (h / 2.0));
+                    (u * u + v * v).sqrt()
+                }
+            };
+            let blur = smoothstep(band.focus, band.focus + falloff, d);
+            mask.push((blur * 255.0).round() as u8);
+        }
+    }
+    mask
+}
+
+// running sums along each row of the premultiplied, weighted linear colour,
+// the weighted alpha and the weights, so any span of a row adds up in o(1)
+struct RowSums {
+    width: usize,
+    sums: Vec<[f32; 5]>,
+}
+
+impl RowSums {
+    fn new(values: &[[f32; 5]], width: usize, height: usize) -> RowSums {
+        let mut sums = vec![[0.0f32; 5]; (width + 1) * height];
+        for y in 0..height {
+            let row = &values[y * width..(y + 1) * width];
+            let sums = &mut sums[y * (width + 1)..(y + 1) * (width + 1)];
+            for (x, v) in row.iter().enumerate() {
+                for c in 0..5 {
+                    sums[x + 1][c] = sums[x][c] + v[c];
+                }
+            }
+        }
+        RowSums { width, sums }
+    }
+
+    // total over the continuous span from a to b of one row, partial pixels
+    // at either end counting in proportion
+    fn span(&self, y: usize, a: f32, b: f32) -> [f32; 5] {
+        let row = &self.sums[y * (self.width + 1)..(y + 1) * (self.width + 1)];
+        let upto = |t: f32| {
+            let t = t.clamp(0.0, self.width as f32);
+            let i = (t as usize).min(self.width - 1);
+            let f = t - i as f32;
+            let (s0, s1) = (&row[i], &row[i + 1]);
+            [0, 1, 2, 3, 4].map(|c| s0[c] + (s1[c] - s0[c]) * f)
+        };
+        let (lo, hi) = (upto(a), upto(b));
+        [0, 1, 2, 3, 4].map(|c| hi[c] - lo[c])
+    }
+}
+
+// bokeh blur: each pixel averages the image over an aperture whose radius is
+// `radius` scaled by the blur mask (255 is full blur, none blurs everything).
+// the average runs in linear light with bright highlights weighted up by
+// `highlight_boost` (0..1) so they open into visible discs, and pixels that
+// are sharper than their neighbours do not spill into their blur
+pub fn lens_blur(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    blur_mask: Option<&[u8]>,
+    radius: f32,
+    aperture: Aperture,
+    highlight_boost: f32,
+) -> Vec<u8> {
+    let (w, h) = (width as usize, height as usize);
+    let radius = radius.max(0.0);
+    let boost = highlight_boost.clamp(0.0, 1.0) * HIGHLIGHT_WEIGHT;
+    let amount = |i: usize| blur_mask.map_or(1.0, |m| m[i] as f32 / 255.0);
+
+    let linear: Vec<f32> = (0..256).map(|v| srgb_to_linear(v as f32 / 255.0)).collect();
+    let values: Vec<[f32; 5]> = data
+        .chunks_exact(4)
+        .enumerate()
+        .map(|(i, p)| {
+            let [r, g, b] = [p[0], p[1], p[2]].map(|c| linear[c as usize]);
+            let a = p[3] as f32 / 255.0;
+            let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+            let weight = (1.0 + boost * smoothstep(0.5, 1.0, luma)) * amount(i).max(SHARP_WEIGHT);
+            let wa = weight * a;
+            [r * wa, g * wa, b * wa, wa, weight]
+        })
+        .collect();
+    let sums = RowSums::new(&values, w, h);
+
+    let mut result = data.to_vec();
+    // half widths of the aperture rows, kept while neighbouring pixels share a radius
+    let mut widths: Vec<f32> = Vec::new();
+    let mut widths_for = f32::NAN;
+    for y in 0..h {
+        for x in 0..w {
+            let i = y * w + x;
+            // the aperture always covers at least the pixel itself
+            let reach = radius * amount(i) + 0.5;
+            if reach <= 0.5 {
+                continue;
+            }
+            let rows = (reach - 1e-3).floor() as i64;
+            if reach != widths_for {
+                widths = (-rows..=rows)
+                    .map(|dy| aperture.half_width(reach, dy as f32).max(0.5))
+                    .collect();
+                widths_for = reach;
+            }
+
+            let mut total = [0.0f32; 5];
+            let centre = x as f32 + 0.5;
+            for (dy, half) in (-rows..=rows).zip(&widths) {
+                let sy = y as i64 + dy;
+                if sy < 0 || sy >= h as i64 {
+                    continue;
+                }
+                let part = sums.span(sy as usize, centre - half, centre + half);
+                for c in 0..5 {
+                    total[c] += part[c];
+                }
+            }
+
+            let p = &mut result[i * 4..i * 4 + 4];
+            if total[3] > 0.0 {
+                for c in 0..3 {
+                    let v = linear_to_srgb((total[c] / total[3]).clamp(0.0, 1.0));
+                    p[c] = (v * 255.0).round() as u8;
+                }
+            }
+            if total[4] > 0.0 {
+                p[3] = (total[3] / total[4] * 255.0).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spot(size: u32, level: u8) -> Vec<u8> {
+        let mut data = [level, level, level, 255].repeat((size * size) as usize);
+        let centre = ((size / 2 * size + size / 2) * 4) as usize;
+        data[centre..centre + 3].copy_from_slice(&[255, 255, 255]);
+        data
+    }
+
+    #[test]
+    fn test_flat_stays_flat_and_sharp_stays_sharp() {
+        let flat = [90, 140, 200, 255].repeat(20 * 20);
+        for aperture in [Aperture::Disc, Aperture::Hexagon] {
+            let out = lens_blur(&flat, 20, 20, None, 6.0, aperture, 1.0);
+            assert!(out.iter().zip(&flat).all(|(a, b)| a.abs_diff(*b) <= 1));
+        }
+
+        // a zero mask keeps every pixel, a full one spreads the spot
+        let data = spot(21, 0);
+        let sharp = vec![0u8; 21 * 21];
+        assert_eq!(
+            lens_blur(&data, 21, 21, Some(&sharp), 5.0, Aperture::Disc, 0.0),
+            data
+        );
+        let out = lens_blur(&data, 21, 21, None, 5.0, Aperture::Disc, 0.0);
+        let at = |x: u32, y: u32| out[((y * 21 + x) * 4) as usize];
+        assert!(at(10, 10) < 255 && at(13, 10) > 0 && at(10, 13) > 0);
+        assert_eq!(at(10, 17), 0);
+    }
+
+    #[test]
+    fn test_highlights_open_into_the_aperture_shape() {
+        let data = spot(41, 20);
+        let plain = lens_blur(&data, 41, 41, None, 10.0, Aperture::Disc, 0.0);
+        let boosted = lens_blur(&data, 41, 41, None, 10.0, Aperture::Disc, 1.0);
+        let at = |out: &[u8], x: u32, y: u32| out[((y * 41 + x) * 4) as usize];
+        assert!(at(&boosted, 26, 20) > at(&plain, 26, 20));
+
+        // a hexagon reaches its points straight up and is cut off on the diagonals
+        let hex = lens_blur(&data, 41, 41, None, 10.0, Aperture::Hexagon, 1.0);
+        let disc = boosted;
+        assert!(at(&hex, 20, 30) > at(&hex, 30, 30));
+        assert!(at(&hex, 27, 27) < at(&disc, 27, 27));
+    }
+
+    #[test]
+    fn test_focus_band_masks() {
+        let band = FocusBand {
+            shape: FocusShape::Linear,
+            center: [0.5, 0.5],
+            angle: 0.0,
+            focus: 0.1,
+            falloff: 0.2,
+        };
+        let mask = focus_band_mask(10, 100, &band);
+        let at = |x: usize, y: usize| mask[y * 10 + x];
+        assert_eq!(at(3, 50), 0);
+        assert_eq!(at(3, 2), 255);
+        assert!(at(3, 70) > 0 && at(3, 70) < 255);
+        assert_eq!(at(0, 20), at(9, 20));
+
+        let radial = FocusBand {
+            shape: FocusShape::Radial,
+            ..band
+        };
+        let mask = focus_band_mask(100, 100, &radial);
+        assert_eq!(mask[50 * 100 + 50], 0);
+        assert_eq!(mask[0], 255);
+        assert_eq!(mask[50 * 100 + 20], mask[20 * 100 + 50]);
+    }
+}
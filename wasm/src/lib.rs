@@ -1,10 +1,42 @@
 use wasm_bindgen::prelude::*;
 
+mod blend;
+mod chroma_key;
+mod color;
+mod effects;
+mod error;
 mod filters;
+mod glitch;
+mod grabcut;
+mod gradient_map;
+mod halftone;
+mod inpaint;
+mod layers;
+mod layout;
+mod lens_blur;
+mod mask;
+mod matting;
+mod painterly;
+mod palette;
+mod quantize;
+mod retro;
+mod seam_carving;
+mod selection;
+#[allow(dead_code)]
 mod shaders;
+mod sketch;
+mod smart_crop;
+mod transform;
+mod trim;
+#[allow(dead_code)]
 mod utils;
+mod warp;
 
+pub use error::ImageError;
 pub use filters::*;
+pub use layers::LayerStack;
+
+use error::{validate_dimensions, validate_image, validate_len, validate_mask, validate_rect};
 
 #[wasm_bindgen(start)]
 pub fn init_panic_hook() {
@@ -21,13 +53,27 @@ extern "C" {
 #[wasm_bindgen]
 pub struct ImageProcessor {
     initialized: bool,
+    max_pixels: u64,
 }
 
 #[wasm_bindgen]
 impl ImageProcessor {
     #[wasm_bindgen(constructor)]
     pub fn new() -> Result<ImageProcessor, JsValue> {
-        Ok(ImageProcessor { initialized: true })
+        Ok(ImageProcessor {
+            initialized: true,
+            max_pixels: error::DEFAULT_MAX_PIXELS,
+        })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn max_pixels(&self) -> u32 {
+        self.max_pixels.min(u32::MAX as u64) as u32
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_max_pixels(&mut self, max_pixels: u32) {
+        self.max_pixels = (max_pixels as u64).min(error::PIXEL_LIMIT_CEILING);
     }
 
     #[wasm_bindgen]
@@ -56,194 +102,122 @@ impl ImageProcessor {
         height: u32,
         filter_type: &str,
         intensity: f32,
-    ) -> Result<Vec<u8>, JsValue> {
+    ) -> Result<Vec<u8>, ImageError> {
         if !self.initialized {
-            return Err(JsValue::from_str("processor not initialized"));
+            return Err(ImageError::NotInitialized);
         }
+        let filter = FilterType::from_string(filter_type)
+            .ok_or_else(|| ImageError::UnknownFilter(filter_type.to_string()))?;
+        self.check_image(image_data, width, height)?;
 
-        let mut result = image_data.to_vec();
-        let len = result.len();
-
-        match filter_type {
-            "grayscale" => {
-                for i in (0..len).step_by(4) {
-                    let r = result[i] as f32;
-                    let g = result[i + 1] as f32;
-                    let b = result[i + 2] as f32;
-                    let gray = r * 0.2126 + g * 0.7152 + b * 0.0722;
-                    result[i] = lerp(r, gray, intensity) as u8;
-                    result[i + 1] = lerp(g, gray, intensity) as u8;
-                    result[i + 2] = lerp(b, gray, intensity) as u8;
-                }
-            }
-            "sepia" => {
-                for i in (0..len).step_by(4) {
-                    let r = result[i] as f32;
-                    let g = result[i + 1] as f32;
-                    let b = result[i + 2] as f32;
-                    let sepia_r = r * 0.393 + g * 0.769 + b * 0.189;
-                    let sepia_g = r * 0.349 + g * 0.686 + b * 0.168;
-                    let sepia_b = r * 0.272 + g * 0.534 + b * 0.131;
-                    result[i] = clamp(lerp(r, sepia_r, intensity)) as u8;
-                    result[i + 1] = clamp(lerp(g, sepia_g, intensity)) as u8;
-                    result[i + 2] = clamp(lerp(b, sepia_b, intensity)) as u8;
-                }
-            }
-            "invert" => {
-                for i in (0..len).step_by(4) {
-                    result[i] = lerp(result[i] as f32, 255.0 - result[i] as f32, intensity) as u8;
-                    result[i + 1] = lerp(
-                        result[i + 1] as f32,
-                        255.0 - result[i + 1] as f32,
-                        intensity,
-                    ) as u8;
-                    result[i + 2] = lerp(
-                        result[i + 2] as f32,
-                        255.0 - result[i + 2] as f32,
-                        intensity,
-                    ) as u8;
-                }
-            }
-            "brightness" => {
-                let adj = (intensity - 0.5) * 2.0 * 255.0;
-                for i in (0..len).step_by(4) {
-                    result[i] = clamp(result[i] as f32 + adj) as u8;
-                    result[i + 1] = clamp(result[i + 1] as f32 + adj) as u8;
-                    result[i + 2] = clamp(result[i + 2] as f32 + adj) as u8;
-                }
-            }
-            "contrast" => {
-                let factor = intensity * 2.0;
-                for i in (0..len).step_by(4) {
-                    result[i] = clamp((result[i] as f32 - 128.0) * factor + 128.0) as u8;
-                    result[i + 1] = clamp((result[i + 1] as f32 - 128.0) * factor + 128.0) as u8;
-                    result[i + 2] = clamp((result[i + 2] as f32 - 128.0) * factor + 128.0) as u8;
-                }
-            }
-            "saturation" => {
-                let sat = intensity * 2.0;
-                for i in (0..len).step_by(4) {
-                    let r = result[i] as f32;
-                    let g = result[i + 1] as f32;
-                    let b = result[i + 2] as f32;
-                    let gray = r * 0.2126 + g * 0.7152 + b * 0.0722;
-                    result[i] = clamp(gray + (r - gray) * sat) as u8;
-                    result[i + 1] = clamp(gray + (g - gray) * sat) as u8;
-                    result[i + 2] = clamp(gray + (b - gray) * sat) as u8;
-                }
-            }
-            "blur" => {
-                let radius = (intensity * 10.0) as i32 + 1;
-                result = box_blur(&result, width, height, radius);
-            }
-            "sharpen" => {
-                result = sharpen_image(&result, width, height, intensity);
-            }
-            "vignette" => {
-                let cx = width as f32 / 2.0;
-                let cy = height as f32 / 2.0;
-                let max_dist = (cx * cx + cy * cy).sqrt();
-                for y in 0..height {
-                    for x in 0..width {
-                        let dx = x as f32 - cx;
-                        let dy = y as f32 - cy;
-                        let dist = (dx * dx + dy * dy).sqrt() / max_dist;
-                        let vignette = 1.0 - (dist * intensity * 1.5).min(1.0);
-                        let idx = ((y * width + x) * 4) as usize;
-                        result[idx] = (result[idx] as f32 * vignette) as u8;
-                        result[idx + 1] = (result[idx + 1] as f32 * vignette) as u8;
-                        result[idx + 2] = (result[idx + 2] as f32 * vignette) as u8;
-                    }
-                }
-            }
-            "vintage" => {
-                for i in (0..len).step_by(4) {
-                    let r = result[i] as f32;
-                    let g = result[i + 1] as f32;
-                    let b = result[i + 2] as f32;
-                    let sepia_r = r * 0.393 + g * 0.769 + b * 0.189;
-                    let sepia_g = r * 0.349 + g * 0.686 + b * 0.168;
-                    let sepia_b = r * 0.272 + g * 0.534 + b * 0.131;
-                    let contrast_r = (sepia_r - 128.0) * 0.9 + 128.0;
-                    let contrast_g = (sepia_g - 128.0) * 0.9 + 128.0;
-                    let contrast_b = (sepia_b - 128.0) * 0.9 + 128.0;
-                    result[i] = clamp(lerp(r, contrast_r, intensity)) as u8;
-                    result[i + 1] = clamp(lerp(g, contrast_g, intensity)) as u8;
-                    result[i + 2] = clamp(lerp(b, contrast_b, intensity)) as u8;
-                }
-            }
-            "warm" => {
-                for i in (0..len).step_by(4) {
-                    result[i] = clamp(result[i] as f32 + 25.0 * intensity) as u8;
-                    result[i + 2] = clamp(result[i + 2] as f32 - 25.0 * intensity) as u8;
-                }
-            }
-            "cool" => {
-                for i in (0..len).step_by(4) {
-                    result[i] = clamp(result[i] as f32 - 25.0 * intensity) as u8;
-                    result[i + 2] = clamp(result[i + 2] as f32 + 25.0 * intensity) as u8;
-                }
-            }
-            "posterize" => {
-                let levels = (intensity * 10.0 + 2.0).max(2.0);
-                let step = 255.0 / (levels - 1.0);
-                for i in (0..len).step_by(4) {
-                    result[i] = ((result[i] as f32 / step).round() * step) as u8;
-                    result[i + 1] = ((result[i + 1] as f32 / step).round() * step) as u8;
-                    result[i + 2] = ((result[i + 2] as f32 / step).round() * step) as u8;
-                }
-            }
-            "emboss" => {
-                result = emboss_image(&result, width, height, intensity);
-            }
-            "edge_detect" => {
-                result = edge_detect_image(&result, width, height, intensity);
-            }
-            "noise" => {
-                let noise_intensity = intensity * 50.0;
-                for i in (0..len).step_by(4) {
-                    let noise = (pseudo_random(i as u32) - 0.5) * noise_intensity;
-                    result[i] = clamp(result[i] as f32 + noise) as u8;
-                    result[i + 1] = clamp(result[i + 1] as f32 + noise) as u8;
-                    result[i + 2] = clamp(result[i + 2] as f32 + noise) as u8;
-                }
-            }
-            "pixelate" => {
-                let block_size = (intensity * 20.0) as u32 + 1;
-                result = pixelate_image(&result, width, height, block_size);
-            }
-            "chromatic_aberration" => {
-                let offset = (intensity * 10.0) as i32;
-                result = chromatic_aberration(&result, width, height, offset);
-            }
-            _ => {}
-        }
+        Ok(filter_image(image_data, width, height, filter, intensity))
+    }
 
-        Ok(result)
+    // like apply_filter, with a params object for filters that take more settings
+    #[wasm_bindgen]
+    pub fn apply_filter_with_params(
+        &mut self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        filter_type: &str,
+        intensity: f32,
+        params: JsValue,
+    ) -> Result<Vec<u8>, ImageError> {
+        if !self.initialized {
+            return Err(ImageError::NotInitialized);
+        }
+        let filter = FilterType::from_string(filter_type)
+            .ok_or_else(|| ImageError::UnknownFilter(filter_type.to_string()))?;
+        self.check_image(image_data, width, height)?;
+        let settings = parse_filter_params(filter, params)?;
+
+        Ok(filter_image_with_params(
+            image_data, width, height, filter, intensity, &settings,
+        ))
     }
 
     #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
     pub fn crop(
         &self,
         image_data: &[u8],
         width: u32,
-        _height: u32,
+        height: u32,
         x: u32,
         y: u32,
         crop_width: u32,
         crop_height: u32,
-    ) -> Result<Vec<u8>, JsValue> {
-        let mut result = Vec::with_capacity((crop_width * crop_height * 4) as usize);
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        validate_rect(x, y, crop_width, crop_height, width, height)?;
 
-        for row in y..(y + crop_height) {
-            let start = ((row * width + x) * 4) as usize;
-            let end = start + (crop_width * 4) as usize;
-            if end <= image_data.len() {
-                result.extend_from_slice(&image_data[start..end]);
-            }
+        Ok(crop_image(image_data, width, x, y, crop_width, crop_height))
+    }
+
+    #[wasm_bindgen]
+    pub fn trim(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        tolerance: f32,
+        mode: &str,
+        color: Option<Vec<u8>>,
+    ) -> Result<TrimResult, ImageError> {
+        self.check_image(image_data, width, height)?;
+        let color = match color {
+            Some(c) => Some(parse_color(&c)?),
+            None => None,
+        };
+        if mode == "color" && color.is_none() {
+            return Err(ImageError::invalid("color trim mode needs a colour"));
         }
+        let mode = trim::TrimMode::from_string(mode, color)
+            .ok_or_else(|| ImageError::invalid(format!("unknown trim mode: {}", mode)))?;
+        let tolerance = (tolerance.clamp(0.0, 1.0) * 255.0).round() as u8;
+
+        let (x, y, trim_width, trim_height) =
+            trim::content_bounds(image_data, width, height, tolerance, mode)
+                .ok_or_else(|| ImageError::invalid("image has no content to trim to"))?;
+        let data = self.crop(image_data, width, height, x, y, trim_width, trim_height)?;
+
+        Ok(TrimResult {
+            data,
+            x,
+            y,
+            width: trim_width,
+            height: trim_height,
+        })
+    }
 
-        Ok(result)
+    #[wasm_bindgen]
+    pub fn suggest_crop(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        target_width: u32,
+        target_height: u32,
+    ) -> Result<CropSuggestion, ImageError> {
+        self.check_image(image_data, width, height)?;
+        if target_width == 0 || target_height == 0 {
+            return Err(ImageError::ZeroDimension {
+                width: target_width,
+                height: target_height,
+            });
+        }
+
+        let window =
+            smart_crop::suggest_crop(image_data, width, height, target_width, target_height);
+
+        Ok(CropSuggestion {
+            x: window.x,
+            y: window.y,
+            width: window.width,
+            height: window.height,
+            score: window.score,
+        })
     }
 
     #[wasm_bindgen]
@@ -254,42 +228,120 @@ impl ImageProcessor {
         src_height: u32,
         dst_width: u32,
         dst_height: u32,
-    ) -> Result<Vec<u8>, JsValue> {
-        let mut result = vec![0u8; (dst_width * dst_height * 4) as usize];
-
-        let x_ratio = src_width as f32 / dst_width as f32;
-        let y_ratio = src_height as f32 / dst_height as f32;
-
-        for y in 0..dst_height {
-            for x in 0..dst_width {
-                let src_x = x as f32 * x_ratio;
-                let src_y = y as f32 * y_ratio;
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, src_width, src_height)?;
+        self.check_output(dst_width, dst_height)?;
 
-                let x0 = src_x.floor() as u32;
-                let y0 = src_y.floor() as u32;
-                let x1 = (x0 + 1).min(src_width - 1);
-                let y1 = (y0 + 1).min(src_height - 1);
-
-                let x_diff = src_x - x0 as f32;
-                let y_diff = src_y - y0 as f32;
+        Ok(resize_image(
+            image_data, src_width, src_height, dst_width, dst_height,
+        ))
+    }
 
-                for c in 0..4 {
-                    let p00 = image_data[((y0 * src_width + x0) * 4 + c) as usize] as f32;
-                    let p10 = image_data[((y0 * src_width + x1) * 4 + c) as usize] as f32;
-                    let p01 = image_data[((y1 * src_width + x0) * 4 + c) as usize] as f32;
-                    let p11 = image_data[((y1 * src_width + x1) * 4 + c) as usize] as f32;
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn extend_canvas(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        top: u32,
+        right: u32,
+        bottom: u32,
+        left: u32,
+        fill: &str,
+        color: Option<Vec<u8>>,
+    ) -> Result<ImageResult, ImageError> {
+        self.check_image(image_data, width, height)?;
+        let canvas_width = width.checked_add(left).and_then(|w| w.checked_add(right));
+        let canvas_height = height.checked_add(top).and_then(|h| h.checked_add(bottom));
+        match (canvas_width, canvas_height) {
+            (Some(w), Some(h)) => self.check_output(w, h)?,
+            _ => {
+                return Err(ImageError::TooLarge {
+                    pixels: u64::MAX,
+                    max_pixels: self.max_pixels,
+                })
+            }
+        }
+        let fill = parse_fill(fill, color)?;
+
+        let (data, width, height) =
+            layout::extend(image_data, width, height, top, right, bottom, left, fill);
+        Ok(ImageResult {
+            data,
+            width,
+            height,
+        })
+    }
 
-                    let value = p00 * (1.0 - x_diff) * (1.0 - y_diff)
-                        + p10 * x_diff * (1.0 - y_diff)
-                        + p01 * (1.0 - x_diff) * y_diff
-                        + p11 * x_diff * y_diff;
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn fit(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        target_width: u32,
+        target_height: u32,
+        mode: &str,
+        anchor: &str,
+        fill: &str,
+        color: Option<Vec<u8>>,
+    ) -> Result<ImageResult, ImageError> {
+        self.check_image(image_data, width, height)?;
+        self.check_output(target_width, target_height)?;
+        let mode = layout::FitMode::from_string(mode)
+            .ok_or_else(|| ImageError::invalid(format!("unknown fit mode: {}", mode)))?;
+        let anchor = layout::Anchor::from_string(anchor)
+            .ok_or_else(|| ImageError::invalid(format!("unknown anchor: {}", anchor)))?;
+        let fill = parse_fill(fill, color)?;
+
+        let (data, width, height) = layout::fit(
+            image_data,
+            width,
+            height,
+            target_width,
+            target_height,
+            mode,
+            anchor,
+            fill,
+        );
+        Ok(ImageResult {
+            data,
+            width,
+            height,
+        })
+    }
 
-                    result[((y * dst_width + x) * 4 + c) as usize] = value.round() as u8;
-                }
-            }
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn seam_carve(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        target_width: u32,
+        target_height: u32,
+        protect_mask: Option<Vec<u8>>,
+        remove_mask: Option<Vec<u8>>,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        self.check_output(target_width, target_height)?;
+        for mask in [&protect_mask, &remove_mask].into_iter().flatten() {
+            validate_mask(mask, width, height)?;
         }
 
-        Ok(result)
+        let pixels = (width * height) as usize;
+        let bias = seam_carving::mask_bias(pixels, protect_mask.as_deref(), remove_mask.as_deref());
+
+        Ok(seam_carving::seam_carve(
+            image_data,
+            width,
+            height,
+            target_width,
+            target_height,
+            bias,
+        ))
     }
 
     #[wasm_bindgen]
@@ -299,7 +351,26 @@ impl ImageProcessor {
         width: u32,
         height: u32,
         degrees: f32,
-    ) -> Result<RotateResult, JsValue> {
+    ) -> Result<RotateResult, ImageError> {
+        self.check_image(image_data, width, height)?;
+        if !degrees.is_finite() {
+            return Err(ImageError::invalid("rotation angle must be finite"));
+        }
+
+        match transform::quarter_turns(degrees) {
+            Some(0) => {
+                return Ok(RotateResult {
+                    data: image_data.to_vec(),
+                    width,
+                    height,
+                })
+            }
+            Some(1) => return self.rotate90(image_data, width, height),
+            Some(2) => return self.rotate180(image_data, width, height),
+            Some(3) => return self.rotate270(image_data, width, height),
+            _ => {}
+        }
+
         let radians = degrees.to_radians();
         let cos_a = radians.cos();
         let sin_a = radians.sin();
@@ -308,6 +379,7 @@ impl ImageProcessor {
             ((width as f32 * cos_a.abs()) + (height as f32 * sin_a.abs())).ceil() as u32;
         let new_height =
             ((width as f32 * sin_a.abs()) + (height as f32 * cos_a.abs())).ceil() as u32;
+        self.check_output(new_width, new_height)?;
 
         let mut result = vec![0u8; (new_width * new_height * 4) as usize];
 
@@ -340,6 +412,86 @@ impl ImageProcessor {
         })
     }
 
+    #[wasm_bindgen]
+    pub fn rotate90(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<RotateResult, ImageError> {
+        self.check_image(image_data, width, height)?;
+        Ok(reoriented(
+            image_data,
+            width,
+            height,
+            transform::Orientation::Rotate90,
+        ))
+    }
+
+    #[wasm_bindgen]
+    pub fn rotate180(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<RotateResult, ImageError> {
+        self.check_image(image_data, width, height)?;
+        Ok(reoriented(
+            image_data,
+            width,
+            height,
+            transform::Orientation::Rotate180,
+        ))
+    }
+
+    #[wasm_bindgen]
+    pub fn rotate270(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<RotateResult, ImageError> {
+        self.check_image(image_data, width, height)?;
+        Ok(reoriented(
+            image_data,
+            width,
+            height,
+            transform::Orientation::Rotate270,
+        ))
+    }
+
+    #[wasm_bindgen]
+    pub fn transpose(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<RotateResult, ImageError> {
+        self.check_image(image_data, width, height)?;
+        Ok(reoriented(
+            image_data,
+            width,
+            height,
+            transform::Orientation::Transpose,
+        ))
+    }
+
+    #[wasm_bindgen]
+    pub fn transverse(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<RotateResult, ImageError> {
+        self.check_image(image_data, width, height)?;
+        Ok(reoriented(
+            image_data,
+            width,
+            height,
+            transform::Orientation::Transverse,
+        ))
+    }
+
     #[wasm_bindgen]
     pub fn flip(
         &self,
@@ -347,7 +499,8 @@ impl ImageProcessor {
         width: u32,
         height: u32,
         horizontal: bool,
-    ) -> Result<Vec<u8>, JsValue> {
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
         let mut result = vec![0u8; image_data.len()];
 
         for y in 0..height {
@@ -365,14 +518,664 @@ impl ImageProcessor {
         Ok(result)
     }
 
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn warp_affine(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        matrix: &[f32],
+        out_width: u32,
+        out_height: u32,
+        interpolation: &str,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        self.check_output(out_width, out_height)?;
+        let matrix: [f32; 6] = matrix
+            .try_into()
+            .map_err(|_| ImageError::invalid("affine matrix must have 6 values"))?;
+        let interpolation = parse_interpolation(interpolation)?;
+
+        warp::warp_affine(
+            image_data,
+            width,
+            height,
+            matrix,
+            out_width,
+            out_height,
+            interpolation,
+        )
+        .ok_or_else(|| ImageError::invalid("affine matrix is not invertible"))
+    }
+
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn warp_perspective(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        src_quad: &[f32],
+        dst_quad: &[f32],
+        out_width: u32,
+        out_height: u32,
+        interpolation: &str,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        self.check_output(out_width, out_height)?;
+        let src_quad: [f32; 8] = src_quad
+            .try_into()
+            .map_err(|_| ImageError::invalid("source quad must have 8 values"))?;
+        let dst_quad: [f32; 8] = dst_quad
+            .try_into()
+            .map_err(|_| ImageError::invalid("destination quad must have 8 values"))?;
+        let interpolation = parse_interpolation(interpolation)?;
+
+        warp::warp_perspective(
+            image_data,
+            width,
+            height,
+            src_quad,
+            dst_quad,
+            out_width,
+            out_height,
+            interpolation,
+        )
+        .ok_or_else(|| ImageError::invalid("quads do not define a valid perspective transform"))
+    }
+
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn composite(
+        &self,
+        base_data: &[u8],
+        base_width: u32,
+        base_height: u32,
+        overlay_data: &[u8],
+        overlay_width: u32,
+        overlay_height: u32,
+        x: i32,
+        y: i32,
+        opacity: f32,
+        mode: &str,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(base_data, base_width, base_height)?;
+        self.check_image(overlay_data, overlay_width, overlay_height)?;
+        let mode = parse_blend_mode(mode)?;
+
+        Ok(blend::composite(
+            base_data,
+            base_width,
+            base_height,
+            overlay_data,
+            overlay_width,
+            overlay_height,
+            x,
+            y,
+            opacity,
+            mode,
+        ))
+    }
+
+    #[wasm_bindgen]
+    pub fn apply_mask(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        mask: &[u8],
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        validate_mask(mask, width, height)?;
+        Ok(mask::apply_mask(image_data, mask))
+    }
+
+    #[wasm_bindgen]
+    pub fn extract_alpha(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        Ok(mask::extract_alpha(image_data))
+    }
+
+    #[wasm_bindgen]
+    pub fn invert_mask(&self, mask: &[u8], width: u32, height: u32) -> Result<Vec<u8>, ImageError> {
+        self.check_mask(mask, width, height)?;
+        Ok(mask::invert(mask))
+    }
+
+    #[wasm_bindgen]
+    pub fn feather_mask(
+        &self,
+        mask: &[u8],
+        width: u32,
+        height: u32,
+        radius: f32,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_mask(mask, width, height)?;
+        // capped like effect sizes, the kernel grows with the radius
+        if !radius.is_finite() || !(0.0..=1000.0).contains(&radius) {
+            return Err(ImageError::invalid(
+                "feather radius must be between 0 and 1000",
+            ));
+        }
+        Ok(mask::feather(
+            mask,
+            width,
+            height,
+            mask::radius_to_sigma(radius),
+        ))
+    }
+
+    #[wasm_bindgen]
+    pub fn grow_mask(
+        &self,
+        mask: &[u8],
+        width: u32,
+        height: u32,
+        amount: i32,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_mask(mask, width, height)?;
+        Ok(mask::grow(mask, width, height, amount))
+    }
+
+    #[wasm_bindgen]
+    pub fn combine_masks(
+        &self,
+        a: &[u8],
+        b: &[u8],
+        width: u32,
+        height: u32,
+        mode: &str,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_mask(a, width, height)?;
+        validate_mask(b, width, height)?;
+        let mode = mask::CombineMode::from_string(mode)
+            .ok_or_else(|| ImageError::invalid(format!("unknown mask combine mode: {}", mode)))?;
+        Ok(mask::combine(a, b, mode))
+    }
+
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn magic_wand(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        x: u32,
+        y: u32,
+        tolerance: f32,
+        contiguous: bool,
+        color_space: &str,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        validate_rect(x, y, 1, 1, width, height)?;
+        let space = parse_color_space(color_space)?;
+
+        Ok(selection::magic_wand(
+            image_data,
+            width,
+            height,
+            x,
+            y,
+            tolerance.clamp(0.0, 1.0),
+            contiguous,
+            space,
+        ))
+    }
+
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn flood_fill(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        x: u32,
+        y: u32,
+        color: &[u8],
+        tolerance: f32,
+        contiguous: bool,
+        color_space: &str,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        validate_rect(x, y, 1, 1, width, height)?;
+        let color = parse_color(color)?;
+        let space = parse_color_space(color_space)?;
+
+        Ok(selection::flood_fill(
+            image_data,
+            width,
+            height,
+            x,
+            y,
+            color,
+            tolerance.clamp(0.0, 1.0),
+            contiguous,
+            space,
+        ))
+    }
+
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn chroma_key(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        key_color: &[u8],
+        tolerance: f32,
+        softness: f32,
+        spill: f32,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        let key = parse_color(key_color)?;
+
+        Ok(chroma_key::chroma_key(
+            image_data,
+            key,
+            tolerance.clamp(0.0, 1.0),
+            softness.clamp(0.0, 1.0),
+            spill.clamp(0.0, 1.0),
+        ))
+    }
+
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn grab_cut(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        x: u32,
+        y: u32,
+        rect_width: u32,
+        rect_height: u32,
+        foreground_mask: Option<Vec<u8>>,
+        background_mask: Option<Vec<u8>>,
+        iterations: u32,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        validate_rect(x, y, rect_width, rect_height, width, height)?;
+        for mask in [&foreground_mask, &background_mask].into_iter().flatten() {
+            validate_mask(mask, width, height)?;
+        }
+
+        grabcut::grab_cut(
+            image_data,
+            width,
+            height,
+            (x, y, rect_width, rect_height),
+            foreground_mask.as_deref(),
+            background_mask.as_deref(),
+            iterations.clamp(1, 10),
+        )
+        .ok_or_else(|| {
+            ImageError::invalid("segmentation needs both foreground and background pixels")
+        })
+    }
+
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn refine_matte(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        mask: &[u8],
+        band_radius: u32,
+        decontaminate: bool,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        validate_mask(mask, width, height)?;
+
+        Ok(matting::refine(
+            image_data,
+            width,
+            height,
+            mask,
+            band_radius.clamp(1, 64),
+            decontaminate,
+        ))
+    }
+
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn drop_shadow(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        offset_x: i32,
+        offset_y: i32,
+        blur: f32,
+        spread: f32,
+        color: &[u8],
+        opacity: f32,
+    ) -> Result<EffectResult, ImageError> {
+        self.check_image(image_data, width, height)?;
+        let blur = effect_size(blur, "blur")?;
+        let spread = effect_size(spread, "spread")?;
+        let color = parse_color(color)?;
+        let reach = (blur + spread).ceil() as u32;
+        self.check_output(
+            width
+                .saturating_add(reach.saturating_mul(2))
+                .saturating_add(offset_x.unsigned_abs()),
+            height
+                .saturating_add(reach.saturating_mul(2))
+                .saturating_add(offset_y.unsigned_abs()),
+        )?;
+
+        Ok(effects::drop_shadow(
+            image_data,
+            width,
+            height,
+            offset_x,
+            offset_y,
+            blur,
+            spread,
+            color,
+            opacity.clamp(0.0, 1.0),
+        )
+        .into())
+    }
+
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn outer_glow(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        radius: f32,
+        spread: f32,
+        color: &[u8],
+        opacity: f32,
+    ) -> Result<EffectResult, ImageError> {
+        self.check_image(image_data, width, height)?;
+        let radius = effect_size(radius, "radius")?;
+        let spread = effect_size(spread, "spread")?;
+        let color = parse_color(color)?;
+        let reach = (radius + spread).ceil() as u32;
+        self.check_output(
+            width.saturating_add(reach.saturating_mul(2)),
+            height.saturating_add(reach.saturating_mul(2)),
+        )?;
+
+        Ok(effects::outer_glow(
+            image_data,
+            width,
+            height,
+            radius,
+            spread,
+            color,
+            opacity.clamp(0.0, 1.0),
+        )
+        .into())
+    }
+
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn inner_glow(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        radius: f32,
+        color: &[u8],
+        opacity: f32,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        let radius = effect_size(radius, "radius")?;
+        let color = parse_color(color)?;
+
+        Ok(effects::inner_glow(
+            image_data,
+            width,
+            height,
+            radius,
+            color,
+            opacity.clamp(0.0, 1.0),
+        ))
+    }
+
+    #[wasm_bindgen]
+    pub fn stroke(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        stroke_width: f32,
+        color: &[u8],
+        position: &str,
+    ) -> Result<EffectResult, ImageError> {
+        self.check_image(image_data, width, height)?;
+        let stroke_width = effect_size(stroke_width, "stroke width")?;
+        let color = parse_color(color)?;
+        let position = effects::StrokePosition::from_string(position)
+            .ok_or_else(|| ImageError::invalid(format!("unknown stroke position: {}", position)))?;
+        let margin = stroke_width.ceil() as u32 + 1;
+        self.check_output(
+            width.saturating_add(margin.saturating_mul(2)),
+            height.saturating_add(margin.saturating_mul(2)),
+        )?;
+
+        Ok(effects::stroke(image_data, width, height, stroke_width, color, position).into())
+    }
+
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn inpaint(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        mask: &[u8],
+        method: &str,
+        seed: u32,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        validate_mask(mask, width, height)?;
+        let method = inpaint::InpaintMethod::from_string(method)
+            .ok_or_else(|| ImageError::invalid(format!("unknown inpaint method: {}", method)))?;
+
+        inpaint::inpaint(image_data, width, height, mask, method, seed)
+            .ok_or_else(|| ImageError::invalid("mask covers the whole image"))
+    }
+
+    #[wasm_bindgen]
+    pub fn extract_palette(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        count: u32,
+        method: &str,
+        seed: u32,
+    ) -> Result<PaletteData, ImageError> {
+        self.check_image(image_data, width, height)?;
+        if !(1..=256).contains(&count) {
+            return Err(ImageError::invalid(
+                "palette size must be between 1 and 256",
+            ));
+        }
+        let method = palette::PaletteMethod::from_string(method)
+            .ok_or_else(|| ImageError::invalid(format!("unknown palette method: {}", method)))?;
+
+        let swatches = palette::extract(image_data, count as usize, method, seed);
+        Ok(PaletteData {
+            colors: swatches.iter().flat_map(|s| s.color).collect(),
+            shares: swatches.iter().map(|s| s.share).collect(),
+        })
+    }
+
+    // reduces the image to `colors` colours, or to `palette` (flat rgb) when given;
+    // transparent pixels share one extra index after the colours
+    #[wasm_bindgen]
+    pub fn quantize(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        colors: u32,
+        palette: Option<Vec<u8>>,
+        dither: &str,
+    ) -> Result<QuantizeResult, ImageError> {
+        self.check_image(image_data, width, height)?;
+        let dither = quantize::Dither::from_string(dither)
+            .ok_or_else(|| ImageError::invalid(format!("unknown dither: {}", dither)))?;
+        let has_transparent = image_data
+            .chunks_exact(4)
+            .any(|p| p[3] < palette::MIN_ALPHA);
+        let limit = if has_transparent { 255 } else { 256 };
+
+        let colors: Vec<[u8; 3]> = match palette {
+            Some(bytes) => {
+                if bytes.is_empty() || bytes.len() % 3 != 0 {
+                    return Err(ImageError::invalid(
+                        "palette must be a non-empty list of rgb triples",
+                    ));
+                }
+                if bytes.len() / 3 > limit {
+                    return Err(ImageError::invalid(format!(
+                        "palette can hold at most {} colours for this image",
+                        limit
+                    )));
+                }
+                bytes.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect()
+            }
+            None => {
+                if colors < 1 || colors as usize > limit {
+                    return Err(ImageError::invalid(format!(
+                        "colour count must be between 1 and {}",
+                        limit
+                    )));
+                }
+                palette::extract(
+                    image_data,
+                    colors as usize,
+                    palette::PaletteMethod::MedianCut,
+                    0,
+                )
+                .into_iter()
+                .map(|s| s.color)
+                .collect()
+            }
+        };
+
+        let indexed = quantize::quantize(image_data, width, height, &colors, dither);
+        Ok(QuantizeResult {
+            data: indexed.to_rgba(),
+            palette: indexed.palette.concat(),
+            transparent_index: indexed.transparent.map(|i| i as u32),
+            indices: indexed.indices,
+        })
+    }
+
+    // bokeh blur whose radius follows the blur mask, 255 blurring by the full
+    // radius; without a mask the whole image is blurred. for portrait-mode
+    // blur pass the inverted subject mask
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn lens_blur(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        blur_mask: Option<Vec<u8>>,
+        radius: f32,
+        aperture: &str,
+        highlight_boost: f32,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        if let Some(mask) = &blur_mask {
+            validate_mask(mask, width, height)?;
+        }
+        let aperture = parse_aperture(aperture)?;
+        check_lens(radius, highlight_boost)?;
+
+        Ok(lens_blur::lens_blur(
+            image_data,
+            width,
+            height,
+            blur_mask.as_deref(),
+            radius,
+            aperture,
+            highlight_boost,
+        ))
+    }
+
+    // lens blur outside a "linear" or "radial" focus band centred at a share
+    // of the width and height; `focus` and `falloff` size the sharp area and
+    // its fade
+    #[wasm_bindgen]
+    #[allow(clippy::too_many_arguments)]
+    pub fn tilt_shift(
+        &self,
+        image_data: &[u8],
+        width: u32,
+        height: u32,
+        shape: &str,
+        center_x: f32,
+        center_y: f32,
+        angle: f32,
+        focus: f32,
+        falloff: f32,
+        radius: f32,
+        aperture: &str,
+        highlight_boost: f32,
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
+        let shape = lens_blur::FocusShape::from_string(shape)
+            .ok_or_else(|| ImageError::invalid(format!("unknown focus shape: {}", shape)))?;
+        let aperture = parse_aperture(aperture)?;
+        check_lens(radius, highlight_boost)?;
+        let shares = [center_x, center_y, focus, falloff];
+        if shares
+            .iter()
+            .any(|v| !v.is_finite() || !(0.0..=1.0).contains(v))
+        {
+            return Err(ImageError::invalid(
+                "center, focus and falloff must be between 0 and 1",
+            ));
+        }
+        if !angle.is_finite() {
+            return Err(ImageError::invalid("angle must be finite"));
+        }
+
+        let band = lens_blur::FocusBand {
+            shape,
+            center: [center_x, center_y],
+            angle,
+            focus,
+            falloff,
+        };
+        let mask = lens_blur::focus_band_mask(width, height, &band);
+        Ok(lens_blur::lens_blur(
+            image_data,
+            width,
+            height,
+            Some(&mask),
+            radius,
+            aperture,
+            highlight_boost,
+        ))
+    }
+
     #[wasm_bindgen]
     pub fn adjust_hue(
         &self,
         image_data: &[u8],
-        _width: u32,
-        _height: u32,
+        width: u32,
+        height: u32,
         hue_shift: f32,
-    ) -> Result<Vec<u8>, JsValue> {
+    ) -> Result<Vec<u8>, ImageError> {
+        self.check_image(image_data, width, height)?;
         let mut result = image_data.to_vec();
 
         for i in (0..result.len()).step_by(4) {
@@ -395,56 +1198,222 @@ impl ImageProcessor {
             result[i + 2] = (nb * 255.0).round() as u8;
         }
 
-        Ok(result)
+        Ok(result)
+    }
+
+    #[wasm_bindgen]
+    pub fn get_histogram(&self, image_data: &[u8]) -> Result<HistogramData, ImageError> {
+        validate_len(image_data.len(), (image_data.len() as u64 / 4) * 4)?;
+        if image_data.len() as u64 / 4 > self.max_pixels {
+            return Err(ImageError::TooLarge {
+                pixels: image_data.len() as u64 / 4,
+                max_pixels: self.max_pixels,
+            });
+        }
+        let mut red = vec![0u32; 256];
+        let mut green = vec![0u32; 256];
+        let mut blue = vec![0u32; 256];
+        let mut luminance = vec![0u32; 256];
+
+        for i in (0..image_data.len()).step_by(4) {
+            let r = image_data[i] as usize;
+            let g = image_data[i + 1] as usize;
+            let b = image_data[i + 2] as usize;
+
+            red[r] += 1;
+            green[g] += 1;
+            blue[b] += 1;
+
+            let lum = (0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32).round() as usize;
+            luminance[lum.min(255)] += 1;
+        }
+
+        Ok(HistogramData {
+            red,
+            green,
+            blue,
+            luminance,
+        })
+    }
+}
+
+impl ImageProcessor {
+    fn check_image(&self, image_data: &[u8], width: u32, height: u32) -> Result<(), ImageError> {
+        validate_image(image_data, width, height, self.max_pixels)
+    }
+
+    fn check_output(&self, width: u32, height: u32) -> Result<(), ImageError> {
+        validate_dimensions(width, height, self.max_pixels)
+    }
+
+    fn check_mask(&self, mask: &[u8], width: u32, height: u32) -> Result<(), ImageError> {
+        validate_dimensions(width, height, self.max_pixels)?;
+        validate_mask(mask, width, height)
+    }
+}
+
+impl Default for ImageProcessor {
+    fn default() -> Self {
+        Self::new().unwrap()
+    }
+}
+
+#[wasm_bindgen]
+pub struct RotateResult {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+#[wasm_bindgen]
+impl RotateResult {
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+#[wasm_bindgen]
+pub struct ImageResult {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+}
+
+#[wasm_bindgen]
+impl ImageResult {
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+#[wasm_bindgen]
+pub struct TrimResult {
+    data: Vec<u8>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+#[wasm_bindgen]
+impl TrimResult {
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn x(&self) -> u32 {
+        self.x
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn y(&self) -> u32 {
+        self.y
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+// x and y locate the original image on the grown canvas
+#[wasm_bindgen]
+pub struct EffectResult {
+    data: Vec<u8>,
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+}
+
+#[wasm_bindgen]
+impl EffectResult {
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
     }
 
-    #[wasm_bindgen]
-    pub fn get_histogram(&self, image_data: &[u8]) -> Result<HistogramData, JsValue> {
-        let mut red = vec![0u32; 256];
-        let mut green = vec![0u32; 256];
-        let mut blue = vec![0u32; 256];
-        let mut luminance = vec![0u32; 256];
-
-        for i in (0..image_data.len()).step_by(4) {
-            let r = image_data[i] as usize;
-            let g = image_data[i + 1] as usize;
-            let b = image_data[i + 2] as usize;
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> u32 {
+        self.width
+    }
 
-            red[r] += 1;
-            green[g] += 1;
-            blue[b] += 1;
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> u32 {
+        self.height
+    }
 
-            let lum = (0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32).round() as usize;
-            luminance[lum.min(255)] += 1;
-        }
+    #[wasm_bindgen(getter)]
+    pub fn x(&self) -> u32 {
+        self.x
+    }
 
-        Ok(HistogramData {
-            red,
-            green,
-            blue,
-            luminance,
-        })
+    #[wasm_bindgen(getter)]
+    pub fn y(&self) -> u32 {
+        self.y
     }
 }
 
-impl Default for ImageProcessor {
-    fn default() -> Self {
-        Self::new().unwrap()
+impl From<effects::Expanded> for EffectResult {
+    fn from(e: effects::Expanded) -> Self {
+        EffectResult {
+            data: e.data,
+            width: e.width,
+            height: e.height,
+            x: e.x,
+            y: e.y,
+        }
     }
 }
 
 #[wasm_bindgen]
-pub struct RotateResult {
-    data: Vec<u8>,
+pub struct CropSuggestion {
+    x: u32,
+    y: u32,
     width: u32,
     height: u32,
+    score: f32,
 }
 
 #[wasm_bindgen]
-impl RotateResult {
+impl CropSuggestion {
     #[wasm_bindgen(getter)]
-    pub fn data(&self) -> Vec<u8> {
-        self.data.clone()
+    pub fn x(&self) -> u32 {
+        self.x
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn y(&self) -> u32 {
+        self.y
     }
 
     #[wasm_bindgen(getter)]
@@ -456,6 +1425,11 @@ impl RotateResult {
     pub fn height(&self) -> u32 {
         self.height
     }
+
+    #[wasm_bindgen(getter)]
+    pub fn score(&self) -> f32 {
+        self.score
+    }
 }
 
 #[wasm_bindgen]
@@ -489,8 +1463,495 @@ impl HistogramData {
     }
 }
 
+// colours are flat rgb triples, largest share first; transparent pixels are not counted
+#[wasm_bindgen]
+pub struct PaletteData {
+    colors: Vec<u8>,
+    shares: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl PaletteData {
+    #[wasm_bindgen(getter)]
+    pub fn colors(&self) -> Vec<u8> {
+        self.colors.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn shares(&self) -> Vec<f32> {
+        self.shares.clone()
+    }
+}
+
+// indices point into the flat rgb palette; the rendered rgba is included for previews
+#[wasm_bindgen]
+pub struct QuantizeResult {
+    data: Vec<u8>,
+    indices: Vec<u8>,
+    palette: Vec<u8>,
+    transparent_index: Option<u32>,
+}
+
+#[wasm_bindgen]
+impl QuantizeResult {
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn indices(&self) -> Vec<u8> {
+        self.indices.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn palette(&self) -> Vec<u8> {
+        self.palette.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn transparent_index(&self) -> Option<u32> {
+        self.transparent_index
+    }
+}
+
+fn reoriented(
+    image_data: &[u8],
+    width: u32,
+    height: u32,
+    orientation: transform::Orientation,
+) -> RotateResult {
+    let (data, width, height) = transform::reorient(image_data, width, height, orientation);
+    RotateResult {
+        data,
+        width,
+        height,
+    }
+}
+
+// accepts rgb or rgba bytes, rgb is treated as opaque
+fn parse_color(bytes: &[u8]) -> Result<[u8; 4], ImageError> {
+    match *bytes {
+        [r, g, b] => Ok([r, g, b, 255]),
+        [r, g, b, a] => Ok([r, g, b, a]),
+        _ => Err(ImageError::invalid("colour must have 3 or 4 components")),
+    }
+}
+
+// a missing colour fills with transparent pixels
+fn parse_fill(name: &str, color: Option<Vec<u8>>) -> Result<layout::Fill, ImageError> {
+    let color = match color {
+        Some(c) => parse_color(&c)?,
+        None => [0, 0, 0, 0],
+    };
+    layout::Fill::from_string(name, color)
+        .ok_or_else(|| ImageError::invalid(format!("unknown fill: {}", name)))
+}
+
+fn parse_blend_mode(name: &str) -> Result<blend::BlendMode, ImageError> {
+    blend::BlendMode::from_string(name)
+        .ok_or_else(|| ImageError::invalid(format!("unknown blend mode: {}", name)))
+}
+
+// checked settings of the filter being applied, parsed once from its params;
+// filters that only take an intensity carry none
+#[derive(Clone, Debug, PartialEq)]
+enum FilterSettings {
+    None,
+    GradientMap(Vec<gradient_map::GradientStop>),
+    Halftone(halftone::Halftone),
+    OilPaint(painterly::OilPaint),
+    Watercolor(painterly::Watercolor),
+    PencilSketch(sketch::Sketch),
+    Cartoon(sketch::Cartoon),
+    PixelSort(glitch::PixelSort),
+    RgbSplit(glitch::RgbSplit),
+    BlockDisplace(glitch::Displace),
+    ScanlineJitter(glitch::Displace),
+    Crt(retro::Crt),
+    Vhs(retro::Vhs),
+    Scanlines(retro::Scanlines),
+}
+
+impl FilterSettings {
+    // what a filter runs with when it is given no params
+    fn defaults(filter: FilterType) -> FilterSettings {
+        match filter {
+            FilterType::GradientMap => FilterSettings::GradientMap(Vec::new()),
+            FilterType::Halftone => FilterSettings::Halftone(Default::default()),
+            FilterType::OilPaint => FilterSettings::OilPaint(Default::default()),
+            FilterType::Watercolor => FilterSettings::Watercolor(Default::default()),
+            FilterType::PencilSketch => FilterSettings::PencilSketch(Default::default()),
+            FilterType::Cartoon => FilterSettings::Cartoon(Default::default()),
+            FilterType::PixelSort => FilterSettings::PixelSort(Default::default()),
+            FilterType::RgbSplit => FilterSettings::RgbSplit(Default::default()),
+            FilterType::BlockDisplace => FilterSettings::BlockDisplace(Default::default()),
+            FilterType::ScanlineJitter => {
+                FilterSettings::ScanlineJitter(glitch::Displace::jitter())
+            }
+            FilterType::Crt => FilterSettings::Crt(Default::default()),
+            FilterType::Vhs => FilterSettings::Vhs(Default::default()),
+            FilterType::Scanlines => FilterSettings::Scanlines(Default::default()),
+            _ => FilterSettings::None,
+        }
+    }
+
+    fn apply(&self, data: &[u8], width: u32, height: u32, intensity: f32) -> Vec<u8> {
+        match self {
+            FilterSettings::None => data.to_vec(),
+            FilterSettings::GradientMap(stops) => gradient_map::apply(data, stops, intensity),
+            FilterSettings::Halftone(s) => halftone::halftone(data, width, height, s, intensity),
+            FilterSettings::OilPaint(s) => painterly::oil_paint(data, width, height, s, intensity),
+            FilterSettings::Watercolor(s) => {
+                painterly::watercolor(data, width, height, s, intensity)
+            }
+            FilterSettings::PencilSketch(s) => {
+                sketch::pencil_sketch(data, width, height, s, intensity)
+            }
+            FilterSettings::Cartoon(s) => sketch::cartoon(data, width, height, s, intensity),
+            FilterSettings::PixelSort(s) => glitch::pixel_sort(data, width, height, s, intensity),
+            FilterSettings::RgbSplit(s) => glitch::rgb_split(data, width, height, s, intensity),
+            FilterSettings::BlockDisplace(s) => {
+                glitch::block_displace(data, width, height, s, intensity)
+            }
+            FilterSettings::ScanlineJitter(s) => {
+                glitch::scanline_jitter(data, width, height, s, intensity)
+            }
+            FilterSettings::Crt(s) => retro::crt(data, width, height, s, intensity),
+            FilterSettings::Vhs(s) => retro::vhs(data, width, height, s, intensity),
+            FilterSettings::Scanlines(s) => retro::scanlines(data, width, s, intensity),
+        }
+    }
+}
+
+// a missing params object leaves the filter on its defaults; only the keys of
+// the filter being applied are accepted
+fn parse_filter_params(filter: FilterType, value: JsValue) -> Result<FilterSettings, ImageError> {
+    if value.is_undefined() || value.is_null() {
+        return Ok(FilterSettings::defaults(filter));
+    }
+    Ok(match filter {
+        FilterType::GradientMap => FilterSettings::GradientMap(gradient_stops(&from_js(value)?)?),
+        FilterType::Halftone => FilterSettings::Halftone(halftone_settings(&from_js(value)?)?),
+        FilterType::OilPaint => FilterSettings::OilPaint(oil_paint_settings(&from_js(value)?)?),
+        FilterType::Watercolor => {
+            FilterSettings::Watercolor(watercolor_settings(&from_js(value)?)?)
+        }
+        FilterType::PencilSketch => {
+            FilterSettings::PencilSketch(sketch_settings(&from_js(value)?)?)
+        }
+        FilterType::Cartoon => FilterSettings::Cartoon(cartoon_settings(&from_js(value)?)?),
+        FilterType::PixelSort => FilterSettings::PixelSort(pixel_sort_settings(&from_js(value)?)?),
+        FilterType::RgbSplit => FilterSettings::RgbSplit(rgb_split_settings(&from_js(value)?)?),
+        FilterType::BlockDisplace => FilterSettings::BlockDisplace(displace_settings(
+            &from_js(value)?,
+            glitch::Displace::default(),
+        )?),
+        FilterType::ScanlineJitter => FilterSettings::ScanlineJitter(displace_settings(
+            &from_js(value)?,
+            glitch::Displace::jitter(),
+        )?),
+        FilterType::Crt => FilterSettings::Crt(crt_settings(&from_js(value)?)?),
+        FilterType::Vhs => FilterSettings::Vhs(vhs_settings(&from_js(value)?)?),
+        FilterType::Scanlines => FilterSettings::Scanlines(scanline_settings(&from_js(value)?)?),
+        _ => FilterSettings::None,
+    })
+}
+
+fn from_js<T: serde::de::DeserializeOwned>(value: JsValue) -> Result<T, ImageError> {
+    serde_wasm_bindgen::from_value(value)
+        .map_err(|e| ImageError::invalid(format!("invalid filter params: {}", e)))
+}
+
+// a missing value takes the default, a given one must lie in the range
+fn param_in_range(
+    value: Option<f32>,
+    default: f32,
+    min: f32,
+    max: f32,
+    name: &str,
+) -> Result<f32, ImageError> {
+    let value = value.unwrap_or(default);
+    if !value.is_finite() || value < min || value > max {
+        return Err(ImageError::invalid(format!(
+            "{} must be between {} and {}",
+            name, min, max
+        )));
+    }
+    Ok(value)
+}
+
+fn oil_paint_settings(params: &OilPaintParams) -> Result<painterly::OilPaint, ImageError> {
+    let defaults = painterly::OilPaint::default();
+    Ok(painterly::OilPaint {
+        radius: param_in_range(params.radius, defaults.radius, 1.0, 16.0, "radius")?,
+        sharpness: param_in_range(params.sharpness, defaults.sharpness, 1.0, 16.0, "sharpness")?,
+    })
+}
+
+fn watercolor_settings(params: &WatercolorParams) -> Result<painterly::Watercolor, ImageError> {
+    let defaults = painterly::Watercolor::default();
+    Ok(painterly::Watercolor {
+        radius: param_in_range(params.radius, defaults.radius, 1.0, 16.0, "radius")?,
+        edge_darkening: param_in_range(
+            params.edge_darkening,
+            defaults.edge_darkening,
+            0.0,
+            1.0,
+            "edge darkening",
+        )?,
+        granulation: param_in_range(
+            params.granulation,
+            defaults.granulation,
+            0.0,
+            1.0,
+            "granulation",
+        )?,
+        bleed: param_in_range(params.bleed, defaults.bleed, 0.0, 1.0, "bleed")?,
+        seed: params.seed.unwrap_or(defaults.seed),
+    })
+}
+
+fn sketch_settings(params: &SketchParams) -> Result<sketch::Sketch, ImageError> {
+    let defaults = sketch::Sketch::default();
+    Ok(sketch::Sketch {
+        radius: param_in_range(params.radius, defaults.radius, 1.0, 96.0, "radius")?,
+        hatching: param_in_range(params.hatching, defaults.hatching, 0.0, 1.0, "hatching")?,
+        seed: params.seed.unwrap_or(defaults.seed),
+    })
+}
+
+fn cartoon_settings(params: &CartoonParams) -> Result<sketch::Cartoon, ImageError> {
+    let defaults = sketch::Cartoon::default();
+    let levels = params.levels.unwrap_or(defaults.levels);
+    if !(2..=32).contains(&levels) {
+        return Err(ImageError::invalid("levels must be between 2 and 32"));
+    }
+    Ok(sketch::Cartoon {
+        levels,
+        edge_thickness: param_in_range(
+            params.edge_thickness,
+            defaults.edge_thickness,
+            0.0,
+            16.0,
+            "edge thickness",
+        )?,
+    })
+}
+
+fn pixel_sort_settings(params: &PixelSortParams) -> Result<glitch::PixelSort, ImageError> {
+    let defaults = glitch::PixelSort::default();
+    let key = match params.sort_by.as_deref() {
+        Some(name) => glitch::SortKey::from_string(name)
+            .ok_or_else(|| ImageError::invalid(format!("unknown sort key: {}", name)))?,
+        None => defaults.key,
+    };
+    let [lo, hi] = params.threshold.unwrap_or(defaults.threshold);
+    if !(0.0..=1.0).contains(&lo) || !(0.0..=1.0).contains(&hi) || lo > hi {
+        return Err(ImageError::invalid(
+            "threshold must be a low and a high value between 0 and 1",
+        ));
+    }
+    Ok(glitch::PixelSort {
+        key,
+        threshold: [lo, hi],
+        angle: angle_param(params.angle, defaults.angle)?,
+        reverse: params.reverse,
+    })
+}
+
+fn rgb_split_settings(params: &RgbSplitParams) -> Result<glitch::RgbSplit, ImageError> {
+    let defaults = glitch::RgbSplit::default();
+    Ok(glitch::RgbSplit {
+        distance: param_in_range(params.distance, defaults.distance, 0.0, 256.0, "distance")?,
+        angle: angle_param(params.angle, defaults.angle)?,
+    })
+}
+
+// block displacement and scanline jitter differ only in the size of what moves
+fn displace_settings(
+    params: &DisplaceParams,
+    defaults: glitch::Displace,
+) -> Result<glitch::Displace, ImageError> {
+    Ok(glitch::Displace {
+        distance: param_in_range(params.distance, defaults.distance, 0.0, 1024.0, "distance")?,
+        block_size: param_in_range(
+            params.block_size,
+            defaults.block_size,
+            1.0,
+            512.0,
+            "block size",
+        )?,
+        density: param_in_range(params.density, defaults.density, 0.0, 1.0, "density")?,
+        seed: params.seed.unwrap_or(defaults.seed),
+    })
+}
+
+fn crt_settings(params: &CrtParams) -> Result<retro::Crt, ImageError> {
+    let defaults = retro::Crt::default();
+    let phosphor = match params.phosphor.as_deref() {
+        Some(name) => retro::Phosphor::from_string(name)
+            .ok_or_else(|| ImageError::invalid(format!("unknown phosphor mask: {}", name)))?,
+        None => defaults.phosphor,
+    };
+    Ok(retro::Crt {
+        curvature: param_in_range(params.curvature, defaults.curvature, 0.0, 1.0, "curvature")?,
+        phosphor,
+        mask_strength: param_in_range(
+            params.mask_strength,
+            defaults.mask_strength,
+            0.0,
+            1.0,
+            "mask strength",
+        )?,
+        scanlines: param_in_range(params.scanlines, defaults.scanlines, 0.0, 1.0, "scanlines")?,
+        bloom: param_in_range(params.bloom, defaults.bloom, 0.0, 1.0, "bloom")?,
+        spacing: param_in_range(params.spacing, defaults.spacing, 2.0, 64.0, "spacing")?,
+    })
+}
+
+fn vhs_settings(params: &VhsParams) -> Result<retro::Vhs, ImageError> {
+    let defaults = retro::Vhs::default();
+    Ok(retro::Vhs {
+        chroma_bleed: param_in_range(
+            params.chroma_bleed,
+            defaults.chroma_bleed,
+            0.0,
+            64.0,
+            "chroma bleed",
+        )?,
+        tracking: param_in_range(params.tracking, defaults.tracking, 0.0, 1.0, "tracking")?,
+        color_shift: param_in_range(
+            params.color_shift,
+            defaults.color_shift,
+            -64.0,
+            64.0,
+            "color shift",
+        )?,
+        wobble: param_in_range(params.wobble, defaults.wobble, 0.0, 64.0, "wobble")?,
+        seed: params.seed.unwrap_or(defaults.seed),
+    })
+}
+
+fn scanline_settings(params: &ScanlineParams) -> Result<retro::Scanlines, ImageError> {
+    let defaults = retro::Scanlines::default();
+    Ok(retro::Scanlines {
+        spacing: param_in_range(params.spacing, defaults.spacing, 2.0, 64.0, "spacing")?,
+        thickness: param_in_range(params.thickness, defaults.thickness, 0.0, 1.0, "thickness")?,
+    })
+}
+
+fn angle_param(angle: Option<f32>, default: f32) -> Result<f32, ImageError> {
+    let angle = angle.unwrap_or(default);
+    if !angle.is_finite() {
+        return Err(ImageError::invalid("angle must be finite"));
+    }
+    Ok(angle)
+}
+
+// the gradient from explicit stops or a preset, at most one of them
+fn gradient_stops(
+    params: &GradientMapParams,
+) -> Result<Vec<gradient_map::GradientStop>, ImageError> {
+    let gradients = !params.stops.is_empty() as u8
+        + params.duotone.is_some() as u8
+        + params.tritone.is_some() as u8;
+    if gradients > 1 {
+        return Err(ImageError::invalid(
+            "give only one of stops, duotone or tritone",
+        ));
+    }
+    if params.stops.iter().any(|s| !s.position.is_finite()) {
+        return Err(ImageError::invalid(
+            "gradient stop positions must be finite",
+        ));
+    }
+    Ok(match (params.duotone, params.tritone) {
+        (Some([shadow, highlight]), _) => gradient_map::duotone(shadow, highlight),
+        (None, Some([shadow, midtone, highlight])) => {
+            gradient_map::tritone(shadow, midtone, highlight)
+        }
+        _ => params.stops.clone(),
+    })
+}
+
+fn halftone_settings(params: &HalftoneParams) -> Result<halftone::Halftone, ImageError> {
+    let defaults = halftone::Halftone::default();
+    let pattern = match params.pattern.as_deref() {
+        Some(name) => halftone::Pattern::from_string(name)
+            .ok_or_else(|| ImageError::invalid(format!("unknown halftone pattern: {}", name)))?,
+        None => defaults.pattern,
+    };
+    let mask = match params.output.as_deref() {
+        None | Some("rgba") => false,
+        Some("mask") => true,
+        Some(other) => return Err(ImageError::invalid(format!("unknown output: {}", other))),
+    };
+    // the mask is a single ink's coverage, there is no one plane to give for cmyk
+    if mask && params.cmyk {
+        return Err(ImageError::invalid(
+            "a mask output needs a single ink, turn cmyk off",
+        ));
+    }
+    let cell_size = params.cell_size.unwrap_or(defaults.cell_size);
+    if !cell_size.is_finite() || !(2.0..=256.0).contains(&cell_size) {
+        return Err(ImageError::invalid("cell size must be between 2 and 256"));
+    }
+    let angle = params.angle.unwrap_or(defaults.angle);
+    let ink_angles = params.ink_angles.unwrap_or(halftone::CMYK_ANGLES);
+    if !angle.is_finite() || ink_angles.iter().any(|a| !a.is_finite()) {
+        return Err(ImageError::invalid("screen angles must be finite"));
+    }
+
+    Ok(halftone::Halftone {
+        pattern,
+        cell_size,
+        angle,
+        cmyk: params.cmyk.then_some(ink_angles),
+        mask,
+    })
+}
+
+fn parse_color_space(name: &str) -> Result<selection::ColorSpace, ImageError> {
+    selection::ColorSpace::from_string(name)
+        .ok_or_else(|| ImageError::invalid(format!("unknown colour space: {}", name)))
+}
+
+// pixel sizes for effects, capped so a typo cannot allocate a huge canvas
+fn effect_size(value: f32, name: &str) -> Result<f32, ImageError> {
+    if !value.is_finite() || value < 0.0 {
+        return Err(ImageError::invalid(format!(
+            "{} must be a non-negative number",
+            name
+        )));
+    }
+    Ok(value.min(1000.0))
+}
+
+fn parse_aperture(name: &str) -> Result<lens_blur::Aperture, ImageError> {
+    lens_blur::Aperture::from_string(name)
+        .ok_or_else(|| ImageError::invalid(format!("unknown aperture: {}", name)))
+}
+
+fn check_lens(radius: f32, highlight_boost: f32) -> Result<(), ImageError> {
+    if !radius.is_finite() || !(0.0..=100.0).contains(&radius) {
+        return Err(ImageError::invalid("blur radius must be between 0 and 100"));
+    }
+    if !highlight_boost.is_finite() || !(0.0..=1.0).contains(&highlight_boost) {
+        return Err(ImageError::invalid(
+            "highlight boost must be between 0 and 1",
+        ));
+    }
+    Ok(())
+}
+
+fn parse_interpolation(name: &str) -> Result<warp::Interpolation, ImageError> {
+    warp::Interpolation::from_string(name)
+        .ok_or_else(|| ImageError::invalid(format!("unknown interpolation: {}", name)))
+}
+
 fn clamp(value: f32) -> f32 {
-    value.max(0.0).min(255.0)
+    value.clamp(0.0, 255.0)
 }
 
 fn lerp(a: f32, b: f32, t: f32) -> f32 {
@@ -569,6 +2030,270 @@ fn hsl_to_rgb(h: f32, s: f32, l: f32) -> (f32, f32, f32) {
     )
 }
 
+fn filter_image(
+    image_data: &[u8],
+    width: u32,
+    height: u32,
+    filter: FilterType,
+    intensity: f32,
+) -> Vec<u8> {
+    filter_image_with_params(
+        image_data,
+        width,
+        height,
+        filter,
+        intensity,
+        &FilterSettings::defaults(filter),
+    )
+}
+
+fn filter_image_with_params(
+    image_data: &[u8],
+    width: u32,
+    height: u32,
+    filter: FilterType,
+    intensity: f32,
+    settings: &FilterSettings,
+) -> Vec<u8> {
+    let mut result = image_data.to_vec();
+    let len = result.len();
+
+    match filter {
+        FilterType::Grayscale => {
+            for i in (0..len).step_by(4) {
+                let r = result[i] as f32;
+                let g = result[i + 1] as f32;
+                let b = result[i + 2] as f32;
+                let gray = r * 0.2126 + g * 0.7152 + b * 0.0722;
+                result[i] = lerp(r, gray, intensity) as u8;
+                result[i + 1] = lerp(g, gray, intensity) as u8;
+                result[i + 2] = lerp(b, gray, intensity) as u8;
+            }
+        }
+        FilterType::Sepia => {
+            for i in (0..len).step_by(4) {
+                let r = result[i] as f32;
+                let g = result[i + 1] as f32;
+                let b = result[i + 2] as f32;
+                let sepia_r = r * 0.393 + g * 0.769 + b * 0.189;
+                let sepia_g = r * 0.349 + g * 0.686 + b * 0.168;
+                let sepia_b = r * 0.272 + g * 0.534 + b * 0.131;
+                result[i] = clamp(lerp(r, sepia_r, intensity)) as u8;
+                result[i + 1] = clamp(lerp(g, sepia_g, intensity)) as u8;
+                result[i + 2] = clamp(lerp(b, sepia_b, intensity)) as u8;
+            }
+        }
+        FilterType::Invert => {
+            for i in (0..len).step_by(4) {
+                result[i] = lerp(result[i] as f32, 255.0 - result[i] as f32, intensity) as u8;
+                result[i + 1] = lerp(
+                    result[i + 1] as f32,
+                    255.0 - result[i + 1] as f32,
+                    intensity,
+                ) as u8;
+                result[i + 2] = lerp(
+                    result[i + 2] as f32,
+                    255.0 - result[i + 2] as f32,
+                    intensity,
+                ) as u8;
+            }
+        }
+        FilterType::Brightness => {
+            let adj = (intensity - 0.5) * 2.0 * 255.0;
+            for i in (0..len).step_by(4) {
+                result[i] = clamp(result[i] as f32 + adj) as u8;
+                result[i + 1] = clamp(result[i + 1] as f32 + adj) as u8;
+                result[i + 2] = clamp(result[i + 2] as f32 + adj) as u8;
+            }
+        }
+        FilterType::Contrast => {
+            let factor = intensity * 2.0;
+            for i in (0..len).step_by(4) {
+                result[i] = clamp((result[i] as f32 - 128.0) * factor + 128.0) as u8;
+                result[i + 1] = clamp((result[i + 1] as f32 - 128.0) * factor + 128.0) as u8;
+                result[i + 2] = clamp((result[i + 2] as f32 - 128.0) * factor + 128.0) as u8;
+            }
+        }
+        FilterType::Saturation => {
+            let sat = intensity * 2.0;
+            for i in (0..len).step_by(4) {
+                let r = result[i] as f32;
+                let g = result[i + 1] as f32;
+                let b = result[i + 2] as f32;
+                let gray = r * 0.2126 + g * 0.7152 + b * 0.0722;
+                result[i] = clamp(gray + (r - gray) * sat) as u8;
+                result[i + 1] = clamp(gray + (g - gray) * sat) as u8;
+                result[i + 2] = clamp(gray + (b - gray) * sat) as u8;
+            }
+        }
+        FilterType::Blur => {
+            let radius = (intensity * 10.0) as i32 + 1;
+            result = box_blur(&result, width, height, radius);
+        }
+        FilterType::Sharpen => {
+            result = sharpen_image(&result, width, height, intensity);
+        }
+        FilterType::Vignette => {
+            let cx = width as f32 / 2.0;
+            let cy = height as f32 / 2.0;
+            let max_dist = (cx * cx + cy * cy).sqrt();
+            for y in 0..height {
+                for x in 0..width {
+                    let dx = x as f32 - cx;
+                    let dy = y as f32 - cy;
+                    let dist = (dx * dx + dy * dy).sqrt() / max_dist;
+                    let vignette = 1.0 - (dist * intensity * 1.5).min(1.0);
+                    let idx = ((y * width + x) * 4) as usize;
+                    result[idx] = (result[idx] as f32 * vignette) as u8;
+                    result[idx + 1] = (result[idx + 1] as f32 * vignette) as u8;
+                    result[idx + 2] = (result[idx + 2] as f32 * vignette) as u8;
+                }
+            }
+        }
+        FilterType::Vintage => {
+            for i in (0..len).step_by(4) {
+                let r = result[i] as f32;
+                let g = result[i + 1] as f32;
+                let b = result[i + 2] as f32;
+                let sepia_r = r * 0.393 + g * 0.769 + b * 0.189;
+                let sepia_g = r * 0.349 + g * 0.686 + b * 0.168;
+                let sepia_b = r * 0.272 + g * 0.534 + b * 0.131;
+                let contrast_r = (sepia_r - 128.0) * 0.9 + 128.0;
+                let contrast_g = (sepia_g - 128.0) * 0.9 + 128.0;
+                let contrast_b = (sepia_b - 128.0) * 0.9 + 128.0;
+                result[i] = clamp(lerp(r, contrast_r, intensity)) as u8;
+                result[i + 1] = clamp(lerp(g, contrast_g, intensity)) as u8;
+                result[i + 2] = clamp(lerp(b, contrast_b, intensity)) as u8;
+            }
+        }
+        FilterType::Warm => {
+            for i in (0..len).step_by(4) {
+                result[i] = clamp(result[i] as f32 + 25.0 * intensity) as u8;
+                result[i + 2] = clamp(result[i + 2] as f32 - 25.0 * intensity) as u8;
+            }
+        }
+        FilterType::Cool => {
+            for i in (0..len).step_by(4) {
+                result[i] = clamp(result[i] as f32 - 25.0 * intensity) as u8;
+                result[i + 2] = clamp(result[i + 2] as f32 + 25.0 * intensity) as u8;
+            }
+        }
+        FilterType::Posterize => {
+            let levels = (intensity * 10.0 + 2.0).max(2.0);
+            let step = 255.0 / (levels - 1.0);
+            for i in (0..len).step_by(4) {
+                result[i] = ((result[i] as f32 / step).round() * step) as u8;
+                result[i + 1] = ((result[i + 1] as f32 / step).round() * step) as u8;
+                result[i + 2] = ((result[i + 2] as f32 / step).round() * step) as u8;
+            }
+        }
+        FilterType::Emboss => {
+            result = emboss_image(&result, width, height, intensity);
+        }
+        FilterType::EdgeDetect => {
+            result = edge_detect_image(&result, width, height, intensity);
+        }
+        FilterType::Noise => {
+            let noise_intensity = intensity * 50.0;
+            for i in (0..len).step_by(4) {
+                let noise = (pseudo_random(i as u32) - 0.5) * noise_intensity;
+                result[i] = clamp(result[i] as f32 + noise) as u8;
+                result[i + 1] = clamp(result[i + 1] as f32 + noise) as u8;
+                result[i + 2] = clamp(result[i + 2] as f32 + noise) as u8;
+            }
+        }
+        FilterType::Pixelate => {
+            let block_size = (intensity * 20.0) as u32 + 1;
+            result = pixelate_image(&result, width, height, block_size);
+        }
+        FilterType::ChromaticAberration => {
+            let offset = (intensity * 10.0) as i32;
+            result = chromatic_aberration(&result, width, height, offset);
+        }
+        FilterType::GradientMap
+        | FilterType::Halftone
+        | FilterType::OilPaint
+        | FilterType::Watercolor
+        | FilterType::PencilSketch
+        | FilterType::Cartoon
+        | FilterType::PixelSort
+        | FilterType::RgbSplit
+        | FilterType::BlockDisplace
+        | FilterType::ScanlineJitter
+        | FilterType::Crt
+        | FilterType::Vhs
+        | FilterType::Scanlines => {
+            result = settings.apply(&result, width, height, intensity);
+        }
+    }
+
+    result
+}
+
+fn crop_image(
+    image_data: &[u8],
+    width: u32,
+    x: u32,
+    y: u32,
+    crop_width: u32,
+    crop_height: u32,
+) -> Vec<u8> {
+    let mut result = Vec::with_capacity((crop_width * crop_height * 4) as usize);
+
+    for row in y..(y + crop_height) {
+        let start = ((row * width + x) * 4) as usize;
+        let end = start + (crop_width * 4) as usize;
+        result.extend_from_slice(&image_data[start..end]);
+    }
+
+    result
+}
+
+// bilinear resample
+fn resize_image(
+    image_data: &[u8],
+    src_width: u32,
+    src_height: u32,
+    dst_width: u32,
+    dst_height: u32,
+) -> Vec<u8> {
+    let mut result = vec![0u8; (dst_width * dst_height * 4) as usize];
+
+    let x_ratio = src_width as f32 / dst_width as f32;
+    let y_ratio = src_height as f32 / dst_height as f32;
+
+    for y in 0..dst_height {
+        for x in 0..dst_width {
+            let src_x = x as f32 * x_ratio;
+            let src_y = y as f32 * y_ratio;
+
+            let x0 = src_x.floor() as u32;
+            let y0 = src_y.floor() as u32;
+            let x1 = (x0 + 1).min(src_width - 1);
+            let y1 = (y0 + 1).min(src_height - 1);
+
+            let x_diff = src_x - x0 as f32;
+            let y_diff = src_y - y0 as f32;
+
+            for c in 0..4 {
+                let p00 = image_data[((y0 * src_width + x0) * 4 + c) as usize] as f32;
+                let p10 = image_data[((y0 * src_width + x1) * 4 + c) as usize] as f32;
+                let p01 = image_data[((y1 * src_width + x0) * 4 + c) as usize] as f32;
+                let p11 = image_data[((y1 * src_width + x1) * 4 + c) as usize] as f32;
+
+                let value = p00 * (1.0 - x_diff) * (1.0 - y_diff)
+                    + p10 * x_diff * (1.0 - y_diff)
+                    + p01 * (1.0 - x_diff) * y_diff
+                    + p11 * x_diff * y_diff;
+
+                result[((y * dst_width + x) * 4 + c) as usize] = value.round() as u8;
+            }
+        }
+    }
+
+    result
+}
+
 fn box_blur(data: &[u8], width: u32, height: u32, radius: i32) -> Vec<u8> {
     let mut result = data.to_vec();
 
@@ -654,27 +2379,12 @@ fn emboss_image(data: &[u8], width: u32, height: u32, intensity: f32) -> Vec<u8>
 
 fn edge_detect_image(data: &[u8], width: u32, height: u32, intensity: f32) -> Vec<u8> {
     let mut result = data.to_vec();
+    let edges = sobel_magnitude(data, width, height);
 
-    for y in 1..height - 1 {
-        for x in 1..width - 1 {
+    for y in 1..height.saturating_sub(1) {
+        for x in 1..width.saturating_sub(1) {
             let idx = ((y * width + x) * 4) as usize;
-
-            let get_lum = |ox: i32, oy: i32| -> f32 {
-                let nx = (x as i32 + ox) as u32;
-                let ny = (y as i32 + oy) as u32;
-                let i = ((ny * width + nx) * 4) as usize;
-                (data[i] as f32 + data[i + 1] as f32 + data[i + 2] as f32) / 3.0
-            };
-
-            let gx = -get_lum(-1, -1) - 2.0 * get_lum(-1, 0) - get_lum(-1, 1)
-                + get_lum(1, -1)
-                + 2.0 * get_lum(1, 0)
-                + get_lum(1, 1);
-            let gy = -get_lum(-1, -1) - 2.0 * get_lum(0, -1) - get_lum(1, -1)
-                + get_lum(-1, 1)
-                + 2.0 * get_lum(0, 1)
-                + get_lum(1, 1);
-            let edge = (gx * gx + gy * gy).sqrt();
+            let edge = edges[(y * width + x) as usize];
 
             for c in 0..3 {
                 let orig = data[idx + c] as f32;
@@ -686,6 +2396,46 @@ fn edge_detect_image(data: &[u8], width: u32, height: u32, intensity: f32) -> Ve
     result
 }
 
+// horizontal and vertical sobel derivatives of a plane, edges are clamped
+fn sobel_gradients(plane: &[f32], width: u32, height: u32) -> (Vec<f32>, Vec<f32>) {
+    let (w, h) = (width as i32, height as i32);
+    let at = |x: i32, y: i32| plane[(y.clamp(0, h - 1) * w + x.clamp(0, w - 1)) as usize];
+    let mut gx = Vec::with_capacity(plane.len());
+    let mut gy = Vec::with_capacity(plane.len());
+
+    for y in 0..h {
+        for x in 0..w {
+            gx.push(
+                at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1)
+                    - 2.0 * at(x - 1, y)
+                    - at(x - 1, y + 1),
+            );
+            gy.push(
+                at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1)
+                    - 2.0 * at(x, y - 1)
+                    - at(x + 1, y - 1),
+            );
+        }
+    }
+
+    (gx, gy)
+}
+
+// sobel gradient magnitude of the channel mean, edges are clamped
+fn sobel_magnitude(data: &[u8], width: u32, height: u32) -> Vec<f32> {
+    let mean: Vec<f32> = data
+        .chunks_exact(4)
+        .map(|p| (p[0] as f32 + p[1] as f32 + p[2] as f32) / 3.0)
+        .collect();
+    let (gx, gy) = sobel_gradients(&mean, width, height);
+    gx.iter()
+        .zip(&gy)
+        .map(|(x, y)| (x * x + y * y).sqrt())
+        .collect()
+}
+
 fn pixelate_image(data: &[u8], width: u32, height: u32, block_size: u32) -> Vec<u8> {
     let mut result = data.to_vec();
     let block_size = block_size.max(1);
@@ -754,3 +2504,58 @@ fn chromatic_aberration(data: &[u8], width: u32, height: u32, offset: i32) -> Ve
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_params_give_each_filter_its_defaults() {
+        let halftone = halftone_settings(&HalftoneParams::default()).unwrap();
+        let sort = pixel_sort_settings(&PixelSortParams::default()).unwrap();
+        assert_eq!(
+            FilterSettings::Halftone(halftone),
+            FilterSettings::defaults(FilterType::Halftone)
+        );
+        assert_eq!(
+            FilterSettings::PixelSort(sort),
+            FilterSettings::defaults(FilterType::PixelSort)
+        );
+        // an angle means a screen to halftone and a streak to pixel sort
+        assert_eq!((halftone.angle, sort.angle), (45.0, 0.0));
+
+        let blocks = displace_settings(&DisplaceParams::default(), glitch::Displace::default());
+        let rows = displace_settings(&DisplaceParams::default(), glitch::Displace::jitter());
+        assert!(blocks.unwrap().block_size > rows.unwrap().block_size);
+        assert_eq!(
+            FilterSettings::defaults(FilterType::Grayscale),
+            FilterSettings::None
+        );
+    }
+
+    #[test]
+    fn test_each_filter_checks_only_its_own_ranges() {
+        // a radius past what oil paint allows is a plausible pencil line
+        let radius = Some(40.0);
+        let oil = OilPaintParams {
+            radius,
+            ..Default::default()
+        };
+        let pencil = SketchParams {
+            radius,
+            ..Default::default()
+        };
+        assert!(oil_paint_settings(&oil).is_err());
+        assert_eq!(sketch_settings(&pencil).unwrap().radius, 40.0);
+
+        let mut halftone = HalftoneParams {
+            cmyk: true,
+            ..Default::default()
+        };
+        assert!(halftone_settings(&halftone).is_ok());
+        halftone.output = Some("mask".into());
+        assert!(halftone_settings(&halftone).is_err());
+        halftone.cmyk = false;
+        assert!(halftone_settings(&halftone).unwrap().mask);
+    }
+}
@@ -0,0 +1,284 @@
+use crate::resize_image;
+
+// single-channel 8-bit masks, 255 is fully selected
+
+const INF: f32 = 1.0e20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CombineMode {
+    Union,
+    Intersect,
+    Subtract,
+}
+
+impl CombineMode {
+    pub fn from_string(s: &str) -> Option<CombineMode> {
+        match s {
+            "union" => Some(CombineMode::Union),
+            "intersect" => Some(CombineMode::Intersect),
+            "subtract" => Some(CombineMode::Subtract),
+            _ => None,
+        }
+    }
+}
+
+fn mul_div_255(a: u8, b: u8) -> u8 {
+    ((a as u16 * b as u16 + 127) / 255) as u8
+}
+
+// scales the image alpha by the mask
+pub fn apply_mask(data: &[u8], mask: &[u8]) -> Vec<u8> {
+    let mut result = data.to_vec();
+    for (pixel, &m) in result.chunks_exact_mut(4).zip(mask) {
+        pixel[3] = mul_div_255(pixel[3], m);
+    }
+    result
+}
+
+pub fn extract_alpha(data: &[u8]) -> Vec<u8> {
+    data.chunks_exact(4).map(|p| p[3]).collect()
+}
+
+pub fn invert(mask: &[u8]) -> Vec<u8> {
+    mask.iter().map(|&m| 255 - m).collect()
+}
+
+pub fn combine(a: &[u8], b: &[u8], mode: CombineMode) -> Vec<u8> {
+    a.iter()
+        .zip(b)
+        .map(|(&a, &b)| match mode {
+            CombineMode::Union => a.max(b),
+            CombineMode::Intersect => a.min(b),
+            CombineMode::Subtract => mul_div_255(a, 255 - b),
+        })
+        .collect()
+}
+
+// bilinear resize of a mask, reusing the rgba resampler
+pub fn resize(mask: &[u8], width: u32, height: u32, new_width: u32, new_height: u32) -> Vec<u8> {
+    let rgba: Vec<u8> = mask.iter().flat_map(|&m| [m, m, m, 255]).collect();
+    resize_image(&rgba, width, height, new_width, new_height)
+        .chunks_exact(4)
+        .map(|p| p[0])
+        .collect()
+}
+
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let radius = (sigma * 3.0).ceil() as i32;
+    let mut kernel: Vec<f32> = (-radius..=radius)
+        .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = kernel.iter().sum();
+    for k in kernel.iter_mut() {
+        *k /= sum;
+    }
+    kernel
+}
+
+// gaussian sigma for a blur `radius` in pixels: the kernel reaches three
+// standard deviations out, so the radius is where the blur visibly ends
+pub fn radius_to_sigma(radius: f32) -> f32 {
+    radius / 3.0
+}
+
+// separable gaussian over a float plane, edges are clamped
+pub fn gaussian_blur_plane(values: &[f32], width: u32, height: u32, sigma: f32) -> Vec<f32> {
+    if sigma <= 0.0 {
+        return values.to_vec();
+    }
+    let kernel = gaussian_kernel(sigma);
+    let radius = (kernel.len() / 2) as i64;
+    let (w, h) = (width as i64, height as i64);
+
+    let mut horizontal = vec![0.0f32; values.len()];
+    for y in 0..h {
+        for x in 0..w {
+            let mut sum = 0.0;
+            for (k, weight) in kernel.iter().enumerate() {
+                let sx = (x + k as i64 - radius).clamp(0, w - 1);
+                sum += values[(y * w + sx) as usize] * weight;
+            }
+            horizontal[(y * w + x) as usize] = sum;
+        }
+    }
+
+    let mut result = vec![0.0f32; values.len()];
+    for y in 0..h {
+        for x in 0..w {
+            let mut sum = 0.0;
+            for (k, weight) in kernel.iter().enumerate() {
+                let sy = (y + k as i64 - radius).clamp(0, h - 1);
+                sum += horizontal[(sy * w + x) as usize] * weight;
+            }
+            result[(y * w + x) as usize] = sum;
+        }
+    }
+
+    result
+}
+
+pub fn feather(mask: &[u8], width: u32, height: u32, sigma: f32) -> Vec<u8> {
+    let values: Vec<f32> = mask.iter().map(|&m| m as f32).collect();
+    gaussian_blur_plane(&values, width, height, sigma)
+        .iter()
+        .map(|&v| v.round().clamp(0.0, 255.0) as u8)
+        .collect()
+}
+
+// 1d squared distance transform of a sampled function (felzenszwalb & huttenlocher)
+fn edt_1d(f: &[f32], out: &mut [f32], v: &mut [usize], z: &mut [f32]) {
+    let n = f.len();
+    let mut k = 0usize;
+    v[0] = 0;
+    z[0] = -INF;
+    z[1] = INF;
+
+    for q in 1..n {
+        loop {
+            let p = v[k];
+            let s =
+                ((f[q] + (q * q) as f32) - (f[p] + (p * p) as f32)) / (2.0 * (q as f32 - p as f32));
+            if s <= z[k] && k > 0 {
+                k -= 1;
+                continue;
+            }
+            if s <= z[k] {
+                // k == 0 and the new parabola dominates the first one
+                v[0] = q;
+                z[0] = -INF;
+                z[1] = INF;
+                break;
+            }
+            k += 1;
+            v[k] = q;
+            z[k] = s;
+            z[k + 1] = INF;
+            break;
+        }
+    }
+
+    k = 0;
+    for (q, o) in out.iter_mut().enumerate() {
+        while z[k + 1] < q as f32 {
+            k += 1;
+        }
+        let d = q as f32 - v[k] as f32;
+        *o = d * d + f[v[k]];
+    }
+}
+
+// euclidean distance from every pixel to the nearest pixel where `inside` is set
+pub fn distance_transform(inside: &[bool], width: u32, height: u32) -> Vec<f32> {
+    let (w, h) = (width as usize, height as usize);
+    let mut grid: Vec<f32> = inside.iter().map(|&b| if b { 0.0 } else { INF }).collect();
+
+    let n = w.max(h);
+    let mut f = vec![0.0f32; n];
+    let mut out = vec![0.0f32; n];
+    let mut v = vec![0usize; n];
+    let mut z = vec![0.0f32; n + 1];
+
+    for x in 0..w {
+        for y in 0..h {
+            f[y] = grid[y * w + x];
+        }
+        edt_1d(&f[..h], &mut out[..h], &mut v, &mut z);
+        for y in 0..h {
+            grid[y * w + x] = out[y];
+        }
+    }
+
+    for y in 0..h {
+        let row = &mut grid[y * w..(y + 1) * w];
+        f[..w].copy_from_slice(row);
+        edt_1d(&f[..w], &mut out[..w], &mut v, &mut z);
+        row.copy_from_slice(&out[..w]);
+    }
+
+    grid.iter().map(|&d| d.sqrt()).collect()
+}
+
+// grows the mask by `amount` pixels, or shrinks it when negative
+pub fn grow(mask: &[u8], width: u32, height: u32, amount: i32) -> Vec<u8> {
+    // no distance inside the image exceeds width + height, so anything past it
+    // changes nothing and i32::MIN can still be negated
+    let limit = (width + height).min(i32::MAX as u32) as i32;
+    let amount = amount.clamp(-limit, limit);
+    let inside: Vec<bool> = mask.iter().map(|&m| m >= 128).collect();
+    if amount == 0 {
+        return inside.iter().map(|&b| if b { 255 } else { 0 }).collect();
+    }
+
+    if amount > 0 {
+        let dist = distance_transform(&inside, width, height);
+        dist.iter()
+            .map(|&d| if d <= amount as f32 { 255 } else { 0 })
+            .collect()
+    } else {
+        let outside: Vec<bool> = inside.iter().map(|&b| !b).collect();
+        let dist = distance_transform(&outside, width, height);
+        dist.iter()
+            .map(|&d| if d > (-amount) as f32 { 255 } else { 0 })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance_transform() {
+        let mut inside = vec![false; 5 * 5];
+        inside[2 * 5 + 2] = true;
+        let dist = distance_transform(&inside, 5, 5);
+        assert_eq!(dist[2 * 5 + 2], 0.0);
+        assert_eq!(dist[2 * 5 + 4], 2.0);
+        assert!((dist[0] - 8f32.sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_grow_and_shrink() {
+        let mut mask = vec![0u8; 7 * 7];
+        for y in 2..5 {
+            for x in 2..5 {
+                mask[y * 7 + x] = 255;
+            }
+        }
+        let count = |m: &[u8]| m.iter().filter(|&&v| v == 255).count();
+
+        assert_eq!(count(&grow(&mask, 7, 7, 1)), 21);
+        assert_eq!(count(&grow(&mask, 7, 7, -1)), 1);
+        assert_eq!(count(&grow(&mask, 7, 7, 0)), 9);
+    }
+
+    #[test]
+    fn test_combine_and_invert() {
+        let a = [0, 255, 255, 128];
+        let b = [255, 255, 0, 255];
+        assert_eq!(
+            combine(&a, &b, CombineMode::Union),
+            vec![255, 255, 255, 255]
+        );
+        assert_eq!(
+            combine(&a, &b, CombineMode::Intersect),
+            vec![0, 255, 0, 128]
+        );
+        assert_eq!(combine(&a, &b, CombineMode::Subtract), vec![0, 0, 255, 0]);
+        assert_eq!(invert(&a), vec![255, 0, 0, 127]);
+    }
+
+    #[test]
+    fn test_feather_preserves_flat_regions() {
+        let mask = vec![255u8; 16];
+        assert_eq!(feather(&mask, 4, 4, 2.0), mask);
+    }
+
+    #[test]
+    fn test_grow_extremes() {
+        let mut mask = vec![0u8; 5 * 5];
+        mask[2 * 5 + 2] = 255;
+        assert!(grow(&mask, 5, 5, i32::MAX).iter().all(|&m| m == 255));
+        assert!(grow(&mask, 5, 5, i32::MIN).iter().all(|&m| m == 0));
+    }
+}
@@ -0,0 +1,254 @@
+use crate::mask::{gaussian_blur_plane, grow};
+use crate::utils::{integral, window_sum};
+
+// regularisation of the guided filter, in squared 0..1 luma
+const GUIDE_EPSILON: f64 = 1e-3;
+// below this colour separation the local models cannot tell fore from back
+const MIN_SEPARATION: f32 = 0.02;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Region {
+    Background,
+    Unknown,
+    Foreground,
+}
+
+// splits the rough mask into sure background, sure foreground and an
+// uncertain band `radius` pixels either side of its edge
+fn trimap(mask: &[u8], width: u32, height: u32, radius: u32) -> Vec<Region> {
+    let outer = grow(mask, width, height, radius as i32);
+    let inner = grow(mask, width, height, -(radius as i32));
+    outer
+        .iter()
+        .zip(&inner)
+        .map(|(&o, &i)| {
+            if i == 255 {
+                Region::Foreground
+            } else if o == 0 {
+                Region::Background
+            } else {
+                Region::Unknown
+            }
+        })
+        .collect()
+}
+
+// spreads the colours of the known pixels into their surroundings with a
+// normalised gaussian, so every band pixel gets a nearby colour estimate
+fn spread_colors(
+    data: &[u8],
+    known: &[bool],
+    width: u32,
+    height: u32,
+    sigma: f32,
+) -> Vec<[f32; 3]> {
+    let weights: Vec<f32> = known.iter().map(|&k| k as u8 as f32).collect();
+    let weight_sum = gaussian_blur_plane(&weights, width, height, sigma);
+    let channels: Vec<Vec<f32>> = (0..3)
+        .map(|c| {
+            let plane: Vec<f32> = data
+                .chunks_exact(4)
+                .zip(&weights)
+                .map(|(p, &wt)| p[c] as f32 / 255.0 * wt)
+                .collect();
+            gaussian_blur_plane(&plane, width, height, sigma)
+        })
+        .collect();
+
+    (0..weight_sum.len())
+        .map(|i| {
+            let ws = weight_sum[i].max(1e-6);
+            [
+                channels[0][i] / ws,
+                channels[1][i] / ws,
+                channels[2][i] / ws,
+            ]
+        })
+        .collect()
+}
+
+// mean of a plane over a (2r+1)^2 window clipped to the image
+fn box_mean(values: &[f64], width: u32, height: u32, radius: u32) -> Vec<f64> {
+    let table = integral(values, width, height);
+    let mut result = vec![0.0f64; values.len()];
+    for y in 0..height {
+        let y0 = y.saturating_sub(radius);
+        let y1 = (y + radius + 1).min(height);
+        for x in 0..width {
+            let x0 = x.saturating_sub(radius);
+            let x1 = (x + radius + 1).min(width);
+            let area = ((x1 - x0) * (y1 - y0)) as f64;
+            result[(y * width + x) as usize] =
+                window_sum(&table, width, x0, y0, x1 - x0, y1 - y0) / area;
+        }
+    }
+    result
+}
+
+// edge-preserving smoothing of `p` guided by the luma plane (he et al.)
+fn guided_filter(guide: &[f64], p: &[f64], width: u32, height: u32, radius: u32) -> Vec<f64> {
+    let mean_i = box_mean(guide, width, height, radius);
+    let mean_p = box_mean(p, width, height, radius);
+    let ii: Vec<f64> = guide.iter().map(|&g| g * g).collect();
+    let ip: Vec<f64> = guide.iter().zip(p).map(|(&g, &v)| g * v).collect();
+    let corr_ii = box_mean(&ii, width, height, radius);
+    let corr_ip = box_mean(&ip, width, height, radius);
+
+    let mut a = vec![0.0f64; p.len()];
+    let mut b = vec![0.0f64; p.len()];
+    for i in 0..p.len() {
+        let var = corr_ii[i] - mean_i[i] * mean_i[i];
+        let cov = corr_ip[i] - mean_i[i] * mean_p[i];
+        a[i] = cov / (var + GUIDE_EPSILON);
+        b[i] = mean_p[i] - a[i] * mean_i[i];
+    }
+    let mean_a = box_mean(&a, width, height, radius);
+    let mean_b = box_mean(&b, width, height, radius);
+    (0..p.len())
+        .map(|i| mean_a[i] * guide[i] + mean_b[i])
+        .collect()
+}
+
+// cut-out with a soft alpha along the mask edge, and optionally the
+// background colour removed from the semi-transparent pixels
+pub fn refine(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    mask: &[u8],
+    radius: u32,
+    decontaminate: bool,
+) -> Vec<u8> {
+    let regions = trimap(mask, width, height, radius.max(1));
+    let sigma = radius.max(2) as f32;
+    let is_fg: Vec<bool> = regions.iter().map(|&r| r == Region::Foreground).collect();
+    let is_bg: Vec<bool> = regions.iter().map(|&r| r == Region::Background).collect();
+    let fg = spread_colors(data, &is_fg, width, height, sigma);
+    let bg = spread_colors(data, &is_bg, width, height, sigma);
+    let has_fg = gaussian_blur_plane(
+        &is_fg.iter().map(|&k| k as u8 as f32).collect::<Vec<_>>(),
+        width,
+        height,
+        sigma,
+    );
+    let has_bg = gaussian_blur_plane(
+        &is_bg.iter().map(|&k| k as u8 as f32).collect::<Vec<_>>(),
+        width,
+        height,
+        sigma,
+    );
+
+    // per-pixel alpha from projecting the colour onto the local fg-bg line,
+    // falling back to the rough mask where the two colours are too close
+    let pixel = |i: usize| -> [f32; 3] {
+        let p = &data[i * 4..i * 4 + 3];
+        [
+            p[0] as f32 / 255.0,
+            p[1] as f32 / 255.0,
+            p[2] as f32 / 255.0,
+        ]
+    };
+    let raw: Vec<f64> = (0..regions.len())
+        .map(|i| match regions[i] {
+            Region::Foreground => 1.0,
+            Region::Background => 0.0,
+            Region::Unknown => {
+                let rough = mask[i] as f64 / 255.0;
+                if has_fg[i] < 1e-3 || has_bg[i] < 1e-3 {
+                    return rough;
+                }
+                let c = pixel(i);
+                let d = [
+                    fg[i][0] - bg[i][0],
+                    fg[i][1] - bg[i][1],
+                    fg[i][2] - bg[i][2],
+                ];
+                let len2 = d[0] * d[0] + d[1] * d[1] + d[2] * d[2];
+                if len2 < MIN_SEPARATION * MIN_SEPARATION {
+                    return rough;
+                }
+                let along =
+                    (c[0] - bg[i][0]) * d[0] + (c[1] - bg[i][1]) * d[1] + (c[2] - bg[i][2]) * d[2];
+                (along / len2).clamp(0.0, 1.0) as f64
+            }
+        })
+        .collect();
+
+    let guide: Vec<f64> = data
+        .chunks_exact(4)
+        .map(|p| (0.299 * p[0] as f64 + 0.587 * p[1] as f64 + 0.114 * p[2] as f64) / 255.0)
+        .collect();
+    let smoothed = guided_filter(&guide, &raw, width, height, (radius / 4).max(1));
+
+    let mut result = data.to_vec();
+    for (i, out) in result.chunks_exact_mut(4).enumerate() {
+        let alpha = match regions[i] {
+            Region::Foreground => 1.0,
+            Region::Background => 0.0,
+            Region::Unknown => smoothed[i].clamp(0.0, 1.0) as f32,
+        };
+
+        if decontaminate && regions[i] == Region::Unknown && alpha > 0.0 {
+            // solve I = aF + (1 - a)B for F, trusting the spread estimate more as alpha drops
+            let c = pixel(i);
+            for ch in 0..3 {
+                let solved = ((c[ch] - (1.0 - alpha) * bg[i][ch]) / alpha).clamp(0.0, 1.0);
+                let value = fg[i][ch] + (solved - fg[i][ch]) * alpha;
+                out[ch] = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+            }
+        }
+        out[3] = (out[3] as f32 * alpha).round() as u8;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trimap_band() {
+        let mut mask = vec![0u8; 11];
+        mask[..6].fill(255);
+        let regions = trimap(&mask, 11, 1, 2);
+        assert_eq!(regions[0], Region::Foreground);
+        assert_eq!(regions[3], Region::Foreground);
+        assert_eq!(regions[4], Region::Unknown);
+        assert_eq!(regions[7], Region::Unknown);
+        assert_eq!(regions[8], Region::Background);
+    }
+
+    #[test]
+    fn test_soft_edge_and_decontamination() {
+        // white subject fading into a green backdrop, the rough mask cuts hard at x = 8
+        let width = 16u32;
+        let mut data = Vec::new();
+        for _ in 0..4 {
+            for x in 0..width {
+                let a = (1.0 - (x as f32 - 6.0) / 4.0).clamp(0.0, 1.0);
+                let mix = |f: f32, b: f32| (f * a + b * (1.0 - a)).round() as u8;
+                data.extend_from_slice(&[mix(255.0, 0.0), 255, mix(255.0, 0.0), 255]);
+            }
+        }
+        let mask: Vec<u8> = (0..width * 4)
+            .map(|i| if i % width < 8 { 255 } else { 0 })
+            .collect();
+
+        let out = refine(&data, width, 4, &mask, 4, true);
+        let row: Vec<u8> = out[..(width * 4) as usize]
+            .chunks_exact(4)
+            .map(|p| p[3])
+            .collect();
+        assert_eq!(row[0], 255);
+        assert_eq!(row[15], 0);
+        // alpha follows the real ramp instead of stepping at the mask edge
+        for (x, &a) in row.iter().enumerate().take(12).skip(4) {
+            let truth = ((1.0 - (x as f32 - 6.0) / 4.0).clamp(0.0, 1.0) * 255.0).round() as u8;
+            assert!(a.abs_diff(truth) <= 8, "x = {}: {}", x, a);
+        }
+
+        // the partly covered pixels are pulled back towards white
+        let p = &out[8 * 4..8 * 4 + 3];
+        assert!(p[0] > data[8 * 4] && p[0] > 240);
+    }
+}
@@ -0,0 +1,411 @@
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+
+use crate::mask::gaussian_blur_plane;
+use crate::sobel_gradients;
+use crate::utils::{hash_noise, smoothstep, value_noise};
+
+// the kuwahara disc is split into this many overlapping sectors
+const SECTORS: usize = 8;
+// smoothing of the structure tensor, in pixels
+const TENSOR_SIGMA: f32 = 2.0;
+// resolution of the precomputed sector weights across the disc
+const WEIGHT_CELLS: usize = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OilPaint {
+    // stroke size in pixels
+    pub radius: f32,
+    // 1..16, how strongly the calmest direction wins
+    pub sharpness: f32,
+}
+
+impl Default for OilPaint {
+    fn default() -> OilPaint {
+        OilPaint {
+            radius: 5.0,
+            sharpness: 8.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Watercolor {
+    // size of the flattened colour regions
+    pub radius: f32,
+    // 0..1 strengths of the three pigment effects
+    pub edge_darkening: f32,
+    pub granulation: f32,
+    pub bleed: f32,
+    pub seed: u32,
+}
+
+impl Default for Watercolor {
+    fn default() -> Watercolor {
+        Watercolor {
+            radius: 4.0,
+            edge_darkening: 0.5,
+            granulation: 0.4,
+            bleed: 0.4,
+            seed: 0,
+        }
+    }
+}
+
+fn to_rgb(data: &[u8]) -> Vec<[f32; 3]> {
+    data.chunks_exact(4)
+        .map(|p| {
+            [
+                p[0] as f32 / 255.0,
+                p[1] as f32 / 255.0,
+                p[2] as f32 / 255.0,
+            ]
+        })
+        .collect()
+}
+
+// mixes the painted colours into the image by `amount`, alpha is kept
+fn finish(data: &[u8], painted: &[[f32; 3]], amount: f32) -> Vec<u8> {
+    let amount = amount.clamp(0.0, 1.0);
+    let mut result = data.to_vec();
+    for (p, c) in result.chunks_exact_mut(4).zip(painted) {
+        for ch in 0..3 {
+            let v = p[ch] as f32 + (c[ch] * 255.0 - p[ch] as f32) * amount;
+            p[ch] = v.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    result
+}
+
+// per-pixel structure tensor (e, f, g) = sums over channels of (gx², gx·gy, gy²)
+// from sobel derivatives, scaled to unit weight
+fn structure_tensor(rgb: &[[f32; 3]], width: u32, height: u32) -> [Vec<f32>; 3] {
+    let mut e = vec![0.0f32; rgb.len()];
+    let mut f = vec![0.0f32; rgb.len()];
+    let mut g = vec![0.0f32; rgb.len()];
+    for c in 0..3 {
+        let plane: Vec<f32> = rgb.iter().map(|p| p[c]).collect();
+        let (gx, gy) = sobel_gradients(&plane, width, height);
+        for i in 0..rgb.len() {
+            let (dx, dy) = (gx[i] / 4.0, gy[i] / 4.0);
+            e[i] += dx * dx;
+            f[i] += dx * dy;
+            g[i] += dy * dy;
+        }
+    }
+    [e, f, g]
+}
+
+// local orientation from the smoothed structure tensor: the angle of the
+// edge tangent and an anisotropy in 0..1 (0 where there is no clear direction)
+fn orientation(rgb: &[[f32; 3]], width: u32, height: u32) -> Vec<(f32, f32)> {
+    let [e, f, g] = structure_tensor(rgb, width, height);
+    let e = gaussian_blur_plane(&e, width, height, TENSOR_SIGMA);
+    let f = gaussian_blur_plane(&f, width, height, TENSOR_SIGMA);
+    let g = gaussian_blur_plane(&g, width, height, TENSOR_SIGMA);
+
+    (0..rgb.len())
+        .map(|i| {
+            let root = ((e[i] - g[i]).powi(2) + 4.0 * f[i] * f[i]).sqrt();
+            let major = (e[i] + g[i] + root) / 2.0;
+            let minor = (e[i] + g[i] - root) / 2.0;
+            let (tx, ty) = (major - e[i], -f[i]);
+            let angle = if tx * tx + ty * ty > 0.0 {
+                ty.atan2(tx)
+            } else {
+                PI / 2.0
+            };
+            let anisotropy = if major + minor > 0.0 {
+                (major - minor) / (major + minor)
+            } else {
+                0.0
+            };
+            (angle, anisotropy)
+        })
+        .collect()
+}
+
+// polynomial sector weights (kyprianidis et al.) with a gaussian falloff, over
+// a grid across the disc of radius 0.5 that every ellipse is mapped onto
+fn sector_weights(radius: f32) -> Vec<[f32; SECTORS]> {
+    let zeta = 1.0 / radius;
+    let half_sector = PI / SECTORS as f32;
+    let eta = (zeta + half_sector.cos()) / half_sector.sin().powi(2);
+    let mut table = vec![[0.0f32; SECTORS]; WEIGHT_CELLS * WEIGHT_CELLS];
+    for (i, weights) in table.iter_mut().enumerate() {
+        let vx = ((i % WEIGHT_CELLS) as f32 + 0.5) / WEIGHT_CELLS as f32 - 0.5;
+        let vy = ((i / WEIGHT_CELLS) as f32 + 0.5) / WEIGHT_CELLS as f32 - 0.5;
+        let r2 = vx * vx + vy * vy;
+        if r2 > 0.25 {
+            continue;
+        }
+        let mut sum = 0.0;
+        for (half, (ux, uy)) in [
+            (0, (vx, vy)),
+            (1, ((vx - vy) * FRAC_1_SQRT_2, (vx + vy) * FRAC_1_SQRT_2)),
+        ] {
+            let xx = zeta - eta * ux * ux;
+            let yy = zeta - eta * uy * uy;
+            for (k, z) in [uy + xx, -ux + yy, -uy + xx, ux + yy]
+                .into_iter()
+                .enumerate()
+            {
+                let z = z.max(0.0);
+                weights[2 * k + half] = z * z;
+                sum += z * z;
+            }
+        }
+        if sum > 0.0 {
+            let falloff = (-3.125 * r2).exp() / sum;
+            for w in weights.iter_mut() {
+                *w *= falloff;
+            }
+        }
+    }
+    table
+}
+
+// anisotropic kuwahara (kyprianidis et al.): each pixel takes the mean of the
+// calmest sectors of an ellipse stretched along the local edge direction,
+// with polynomial sector weights; `sharpness` favours the calmest sector
+fn kuwahara_rgb(
+    rgb: &[[f32; 3]],
+    width: u32,
+    height: u32,
+    radius: f32,
+    sharpness: f32,
+) -> Vec<[f32; 3]> {
+    let (w, h) = (width as i32, height as i32);
+    let structure = orientation(rgb, width, height);
+    let weights = sector_weights(radius);
+    // large windows are sampled on a sparser grid, a few dozen samples are plenty
+    let stride = (radius / 4.0).ceil().max(1.0) as i32;
+    let mut result = vec![[0.0f32; 3]; rgb.len()];
+    for y in 0..h {
+        for x in 0..w {
+            let i = (y * w + x) as usize;
+            let (angle, anisotropy) = structure[i];
+            let a = radius * (1.0 + anisotropy);
+            let b = radius / (1.0 + anisotropy);
+            let (sin, cos) = angle.sin_cos();
+            let reach_y = (a * a * sin * sin + b * b * cos * cos).sqrt().ceil() as i32;
+            // each row of the ellipse spans the roots of a quadratic in dx
+            let qa = cos * cos / (a * a) + sin * sin / (b * b);
+            let qb = 2.0 * cos * sin * (1.0 / (a * a) - 1.0 / (b * b));
+            let qc = sin * sin / (a * a) + cos * cos / (b * b);
+
+            // offsets map linearly onto the weight grid across the disc
+            let cells = WEIGHT_CELLS as f32;
+            let (ux, uy) = (0.5 * cells * cos / a, 0.5 * cells * sin / a);
+            let (vx, vy) = (-0.5 * cells * sin / b, 0.5 * cells * cos / b);
+            let cell = |v: f32| (v + cells / 2.0).clamp(0.0, cells - 1.0) as usize;
+
+            // per sector sums of w·c and w·c² for each channel, and of w
+            let mut mean = [[0.0f32; SECTORS]; 3];
+            let mut square = [[0.0f32; SECTORS]; 3];
+            let mut total = [0.0f32; SECTORS];
+            for dy in (-(reach_y / stride) * stride..=reach_y).step_by(stride as usize) {
+                let (b1, c1) = (qb * dy as f32, qc * (dy * dy) as f32 - 1.0);
+                let disc = b1 * b1 - 4.0 * qa * c1;
+                if disc < 0.0 {
+                    continue;
+                }
+                let from = ((-b1 - disc.sqrt()) / (2.0 * qa)).ceil() as i32;
+                let to = ((-b1 + disc.sqrt()) / (2.0 * qa)).floor() as i32;
+                let from = from + (-from).rem_euclid(stride);
+                let row = (y + dy).clamp(0, h - 1) * w;
+                for dx in (from..=to).step_by(stride as usize) {
+                    let (fx, fy) = (dx as f32, dy as f32);
+                    let sample =
+                        &weights[cell(vx * fx + vy * fy) * WEIGHT_CELLS + cell(ux * fx + uy * fy)];
+                    let c = rgb[(row + (x + dx).clamp(0, w - 1)) as usize];
+                    for ch in 0..3 {
+                        let sq = c[ch] * c[ch];
+                        for k in 0..SECTORS {
+                            mean[ch][k] += c[ch] * sample[k];
+                            square[ch][k] += sq * sample[k];
+                        }
+                    }
+                    for k in 0..SECTORS {
+                        total[k] += sample[k];
+                    }
+                }
+            }
+
+            let mut out = [0.0f32; 3];
+            let mut out_weight = 0.0f32;
+            for k in 0..SECTORS {
+                if total[k] <= 0.0 {
+                    continue;
+                }
+                let m = [0, 1, 2].map(|ch| mean[ch][k] / total[k]);
+                let variance: f32 = (0..3)
+                    .map(|ch| (square[ch][k] / total[k] - m[ch] * m[ch]).abs())
+                    .sum();
+                let weight = 1.0 / (1.0 + (255.0 * variance).powf(0.5 * sharpness));
+                for ch in 0..3 {
+                    out[ch] += m[ch] * weight;
+                }
+                out_weight += weight;
+            }
+            result[i] = if out_weight > 0.0 {
+                out.map(|v| v / out_weight)
+            } else {
+                rgb[i]
+            };
+        }
+    }
+    result
+}
+
+// oil paint look: flat strokes that follow the image structure
+pub fn oil_paint(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    settings: &OilPaint,
+    amount: f32,
+) -> Vec<u8> {
+    let painted = kuwahara_rgb(
+        &to_rgb(data),
+        width,
+        height,
+        settings.radius,
+        settings.sharpness,
+    );
+    finish(data, &painted, amount)
+}
+
+// darkens or lightens pigment by a density factor (bousseau et al.), 1 leaves it
+fn pigment(c: f32, density: f32) -> f32 {
+    (c - (c - c * c) * (density - 1.0)).clamp(0.0, 1.0)
+}
+
+// flattened colour regions that bleed into each other, with pigment pooling
+// at their edges and settling into the paper grain
+pub fn watercolor(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    settings: &Watercolor,
+    amount: f32,
+) -> Vec<u8> {
+    let mut painted = kuwahara_rgb(&to_rgb(data), width, height, settings.radius, 8.0);
+
+    // bleeding: wet patches of the paper let the colours run into a blur
+    if settings.bleed > 0.0 {
+        let sigma = 2.0 + 6.0 * settings.bleed;
+        let blurred: Vec<Vec<f32>> = (0..3)
+            .map(|ch| {
+                let plane: Vec<f32> = painted.iter().map(|c| c[ch]).collect();
+                gaussian_blur_plane(&plane, width, height, sigma)
+            })
+            .collect();
+        for (i, c) in painted.iter_mut().enumerate() {
+            let (x, y) = ((i as u32 % width) as f32, (i as u32 / width) as f32);
+            let wet = smoothstep(0.3, 0.7, value_noise(x, y, 24.0, settings.seed));
+            let mix = settings.bleed * wet;
+            for ch in 0..3 {
+                c[ch] += (blurred[ch][i] - c[ch]) * mix;
+            }
+        }
+    }
+
+    // edge darkening: pigment collects where the colour changes
+    let [e, _, g] = structure_tensor(&painted, width, height);
+    let edges: Vec<f32> = e
+        .iter()
+        .zip(&g)
+        .map(|(e, g)| (e + g).sqrt().min(1.0))
+        .collect();
+    let edges = gaussian_blur_plane(&edges, width, height, 1.0);
+
+    for (i, c) in painted.iter_mut().enumerate() {
+        let (x, y) = (i as u32 % width, i as u32 / width);
+        let grain = 0.7 * hash_noise(x as i32, y as i32, settings.seed)
+            + 0.3 * value_noise(x as f32, y as f32, 4.0, settings.seed.wrapping_add(1));
+        let density =
+            1.0 + settings.edge_darkening * edges[i] * 2.0 + settings.granulation * (grain - 0.5);
+        *c = c.map(|v| pigment(v, density));
+    }
+    finish(data, &painted, amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // noisy red left half, noisy blue right half
+    fn noisy_halves(size: u32) -> Vec<u8> {
+        (0..size * size)
+            .flat_map(|i| {
+                let n = (hash_noise(i as i32, 0, 9) * 60.0) as u8;
+                if i % size < size / 2 {
+                    [180 + n, 20 + n / 2, 20, 255]
+                } else {
+                    [20, 20 + n / 2, 180 + n, 128]
+                }
+            })
+            .collect()
+    }
+
+    fn spread(data: &[u8], size: u32, column: u32) -> u8 {
+        let reds: Vec<u8> = (2..size - 2)
+            .map(|y| data[((y * size + column) * 4) as usize])
+            .collect();
+        reds.iter().max().unwrap() - reds.iter().min().unwrap()
+    }
+
+    #[test]
+    fn test_kuwahara_smooths_but_keeps_the_edge() {
+        let size = 24;
+        let data = noisy_halves(size);
+        let settings = OilPaint {
+            radius: 4.0,
+            ..Default::default()
+        };
+        let out = oil_paint(&data, size, size, &settings, 1.0);
+        assert!(spread(&out, size, 5) < spread(&data, size, 5) / 2);
+        // the step between the halves stays sharp and alpha is untouched
+        let row = (12 * size * 4) as usize;
+        assert!(out[row + 10 * 4] > 150 && out[row + 13 * 4] < 60);
+        assert_eq!(out[row + 13 * 4 + 3], 128);
+    }
+
+    #[test]
+    fn test_watercolor_darkens_edges_and_is_seeded() {
+        let size = 32;
+        let data = noisy_halves(size);
+        let settings = Watercolor {
+            radius: 3.0,
+            edge_darkening: 1.0,
+            granulation: 0.0,
+            bleed: 0.0,
+            seed: 3,
+        };
+        let plain = Watercolor {
+            edge_darkening: 0.0,
+            ..settings
+        };
+        let luma = |out: &[u8], x: u32| {
+            let i = ((16 * size + x) * 4) as usize;
+            out[i] as u32 + out[i + 1] as u32 + out[i + 2] as u32
+        };
+        let darkened = watercolor(&data, size, size, &settings, 1.0);
+        let flat = watercolor(&data, size, size, &plain, 1.0);
+        // the pixels along the colour boundary lose light, flat areas barely change
+        assert!(luma(&darkened, 15) + 20 < luma(&flat, 15));
+        assert!(luma(&darkened, 4).abs_diff(luma(&flat, 4)) < 10);
+
+        let grainy = Watercolor {
+            granulation: 1.0,
+            bleed: 0.5,
+            ..settings
+        };
+        let a = watercolor(&data, size, size, &grainy, 1.0);
+        assert_eq!(a, watercolor(&data, size, size, &grainy, 1.0));
+        assert_ne!(
+            a,
+            watercolor(&data, size, size, &Watercolor { seed: 4, ..grainy }, 1.0)
+        );
+    }
+}
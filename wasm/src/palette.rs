@@ -0,0 +1,386 @@
+use crate::color::{oklab_to_rgb, rgb_to_oklab};
+use crate::utils::Rng;
+
+// pixels below this alpha do not count towards the palette
+pub const MIN_ALPHA: u8 = 128;
+// k-means works on a 5-bit-per-channel histogram instead of raw pixels
+const HISTOGRAM_BITS: u32 = 5;
+const KMEANS_ROUNDS: usize = 24;
+// octree leaves start at this depth, deeper adds nothing visible
+const OCTREE_DEPTH: u32 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteMethod {
+    MedianCut,
+    KMeans,
+    Octree,
+}
+
+impl PaletteMethod {
+    pub fn from_string(s: &str) -> Option<PaletteMethod> {
+        match s {
+            "median-cut" => Some(PaletteMethod::MedianCut),
+            "kmeans" => Some(PaletteMethod::KMeans),
+            "octree" => Some(PaletteMethod::Octree),
+            _ => None,
+        }
+    }
+}
+
+// a palette entry and the fraction of counted pixels it stands for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Swatch {
+    pub color: [u8; 3],
+    pub share: f32,
+}
+
+fn opaque_pixels(data: &[u8]) -> Vec<[u8; 3]> {
+    data.chunks_exact(4)
+        .filter(|p| p[3] >= MIN_ALPHA)
+        .map(|p| [p[0], p[1], p[2]])
+        .collect()
+}
+
+fn mean_color(pixels: &[[u8; 3]]) -> [u8; 3] {
+    let mut sum = [0u64; 3];
+    for p in pixels {
+        for c in 0..3 {
+            sum[c] += p[c] as u64;
+        }
+    }
+    let n = pixels.len().max(1) as u64;
+    sum.map(|s| ((s + n / 2) / n) as u8)
+}
+
+// heckbert's median cut: keep splitting the box with the widest channel
+// range at its median until there are k boxes
+fn median_cut(pixels: &mut [[u8; 3]], k: usize) -> Vec<(usize, usize)> {
+    let channel_range = |slice: &[[u8; 3]]| -> (usize, u8) {
+        (0..3)
+            .map(|c| {
+                let lo = slice.iter().map(|p| p[c]).min().unwrap_or(0);
+                let hi = slice.iter().map(|p| p[c]).max().unwrap_or(0);
+                (c, hi - lo)
+            })
+            .max_by_key(|&(c, r)| (r, std::cmp::Reverse(c)))
+            .unwrap()
+    };
+
+    // each box keeps its split channel and score so only new boxes are measured
+    let measure = |pixels: &[[u8; 3]], start: usize, end: usize| {
+        let (channel, range) = channel_range(&pixels[start..end]);
+        (start, end, channel, range as u64 * (end - start) as u64)
+    };
+
+    // widest box first, weighted by how many pixels it holds
+    let mut boxes = vec![measure(pixels, 0, pixels.len())];
+    while boxes.len() < k {
+        let candidate = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.3 > 0)
+            .max_by_key(|&(i, b)| (b.3, std::cmp::Reverse(i)));
+        let Some((i, &(start, end, channel, _))) = candidate else {
+            break;
+        };
+
+        pixels[start..end].sort_unstable_by_key(|p| p[channel]);
+        let mut mid = start + (end - start) / 2;
+        // never split a run of equal values across two boxes
+        let value = pixels[mid][channel];
+        while mid > start && pixels[mid - 1][channel] == value {
+            mid -= 1;
+        }
+        if mid == start {
+            mid = start + (end - start) / 2;
+            while mid < end && pixels[mid][channel] == value {
+                mid += 1;
+            }
+        }
+        boxes[i] = measure(pixels, start, mid);
+        boxes.push(measure(pixels, mid, end));
+    }
+    boxes
+        .into_iter()
+        .map(|(start, end, _, _)| (start, end))
+        .collect()
+}
+
+// k-means++ seeding and lloyd iterations in oklab over weighted colours
+fn kmeans(colors: &[[f32; 3]], weights: &[f32], k: usize, seed: u32) -> Vec<[f32; 3]> {
+    let dist2 = |a: [f32; 3], b: [f32; 3]| {
+        (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+    };
+    let mut rng = Rng::new(seed);
+
+    let pick = |rng: &mut Rng, scores: &[f32]| -> usize {
+        let sum: f32 = scores.iter().sum();
+        let mut target = rng.next_f32() * sum;
+        for (i, &s) in scores.iter().enumerate() {
+            target -= s;
+            if target <= 0.0 && s > 0.0 {
+                return i;
+            }
+        }
+        scores.iter().rposition(|&s| s > 0.0).unwrap_or(0)
+    };
+
+    let mut centres = vec![colors[pick(&mut rng, weights)]];
+    let mut nearest: Vec<f32> = colors.iter().map(|&c| dist2(c, centres[0])).collect();
+    while centres.len() < k {
+        let scores: Vec<f32> = nearest.iter().zip(weights).map(|(d, w)| d * w).collect();
+        if scores.iter().sum::<f32>() <= 0.0 {
+            break;
+        }
+        let next = colors[pick(&mut rng, &scores)];
+        for (d, &c) in nearest.iter_mut().zip(colors) {
+            *d = d.min(dist2(c, next));
+        }
+        centres.push(next);
+    }
+
+    let mut assignment = vec![0usize; colors.len()];
+    for _ in 0..KMEANS_ROUNDS {
+        let mut changed = false;
+        for (a, &c) in assignment.iter_mut().zip(colors) {
+            let best = (0..centres.len())
+                .min_by(|&i, &j| dist2(c, centres[i]).total_cmp(&dist2(c, centres[j])))
+                .unwrap();
+            changed |= *a != best;
+            *a = best;
+        }
+        let mut sums = vec![[0.0f32; 3]; centres.len()];
+        let mut mass = vec![0.0f32; centres.len()];
+        for ((&a, c), &w) in assignment.iter().zip(colors).zip(weights) {
+            for i in 0..3 {
+                sums[a][i] += c[i] * w;
+            }
+            mass[a] += w;
+        }
+        for (i, centre) in centres.iter_mut().enumerate() {
+            if mass[i] > 0.0 {
+                *centre = sums[i].map(|s| s / mass[i]);
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    // drop clusters nobody ended up in
+    let mut mass = vec![0.0f32; centres.len()];
+    for (&a, &w) in assignment.iter().zip(weights) {
+        mass[a] += w;
+    }
+    centres
+        .into_iter()
+        .zip(mass)
+        .filter(|&(_, m)| m > 0.0)
+        .map(|(c, _)| c)
+        .collect()
+}
+
+// octree quantisation: colours are counted in leaves at a fixed depth, then
+// the sparsest groups of siblings fold into their parent until k leaves remain
+fn octree(pixels: &[[u8; 3]], k: usize) -> Vec<([u64; 3], u64)> {
+    use std::collections::BTreeMap;
+
+    // key is the interleaved top bits of r, g and b at the node's depth
+    let key = |p: [u8; 3], depth: u32| -> u32 {
+        let mut key = 0u32;
+        for level in 0..depth {
+            let bit = 7 - level;
+            key = (key << 3)
+                | (((p[0] >> bit) & 1) as u32) << 2
+                | (((p[1] >> bit) & 1) as u32) << 1
+                | ((p[2] >> bit) & 1) as u32;
+        }
+        key
+    };
+
+    // (depth, key) -> (channel sums, pixel count)
+    let mut leaves: BTreeMap<(u32, u32), ([u64; 3], u64)> = BTreeMap::new();
+    for &p in pixels {
+        let entry = leaves
+            .entry((OCTREE_DEPTH, key(p, OCTREE_DEPTH)))
+            .or_default();
+        for (total, &v) in entry.0.iter_mut().zip(&p) {
+            *total += v as u64;
+        }
+        entry.1 += 1;
+    }
+
+    for depth in (1..=OCTREE_DEPTH).rev() {
+        if leaves.len() <= k {
+            break;
+        }
+        // sibling groups at this depth, fewest pixels first
+        let mut groups: BTreeMap<u32, (u64, Vec<u32>)> = BTreeMap::new();
+        for (&(d, key), &(_, count)) in &leaves {
+            if d == depth {
+                let group = groups.entry(key >> 3).or_default();
+                group.0 += count;
+                group.1.push(key);
+            }
+        }
+        let mut order: Vec<(u32, u64, Vec<u32>)> =
+            groups.into_iter().map(|(p, (c, ks))| (p, c, ks)).collect();
+        order.sort_by_key(|(parent, count, _)| (*count, *parent));
+
+        for (parent, _, children) in order {
+            if leaves.len() <= k {
+                break;
+            }
+            let mut merged = ([0u64; 3], 0u64);
+            for child in children {
+                let (sum, count) = leaves.remove(&(depth, child)).unwrap();
+                for (total, v) in merged.0.iter_mut().zip(sum) {
+                    *total += v;
+                }
+                merged.1 += count;
+            }
+            leaves.insert((depth - 1, parent), merged);
+        }
+    }
+    leaves.into_values().collect()
+}
+
+// up to k dominant colours, largest share first
+pub fn extract(data: &[u8], k: usize, method: PaletteMethod, seed: u32) -> Vec<Swatch> {
+    let mut pixels = opaque_pixels(data);
+    if pixels.is_empty() || k == 0 {
+        return Vec::new();
+    }
+    let total = pixels.len() as f32;
+
+    let mut swatches: Vec<Swatch> = match method {
+        PaletteMethod::MedianCut => median_cut(&mut pixels, k)
+            .into_iter()
+            .map(|(start, end)| Swatch {
+                color: mean_color(&pixels[start..end]),
+                share: (end - start) as f32 / total,
+            })
+            .collect(),
+        PaletteMethod::Octree => octree(&pixels, k)
+            .into_iter()
+            .map(|(sum, count)| Swatch {
+                color: sum.map(|s| ((s + count / 2) / count) as u8),
+                share: count as f32 / total,
+            })
+            .collect(),
+        PaletteMethod::KMeans => {
+            let shift = 8 - HISTOGRAM_BITS;
+            let bins = 1usize << (3 * HISTOGRAM_BITS);
+            let mut counts = vec![0u32; bins];
+            let mut sums = vec![[0u64; 3]; bins];
+            for p in &pixels {
+                let bin = ((p[0] >> shift) as usize) << (2 * HISTOGRAM_BITS)
+                    | ((p[1] >> shift) as usize) << HISTOGRAM_BITS
+                    | (p[2] >> shift) as usize;
+                counts[bin] += 1;
+                for c in 0..3 {
+                    sums[bin][c] += p[c] as u64;
+                }
+            }
+            let mut colors = Vec::new();
+            let mut weights = Vec::new();
+            for (count, sum) in counts.iter().zip(&sums) {
+                if *count > 0 {
+                    let n = *count as u64;
+                    let mean = sum.map(|s| (s / n) as u8);
+                    colors.push(rgb_to_oklab(mean[0], mean[1], mean[2]));
+                    weights.push(*count as f32);
+                }
+            }
+
+            let centres = kmeans(&colors, &weights, k, seed);
+            let mut share = vec![0.0f32; centres.len()];
+            for (c, w) in colors.iter().zip(&weights) {
+                let best = (0..centres.len())
+                    .min_by(|&i, &j| {
+                        let d = |x: [f32; 3]| {
+                            (x[0] - c[0]).powi(2) + (x[1] - c[1]).powi(2) + (x[2] - c[2]).powi(2)
+                        };
+                        d(centres[i]).total_cmp(&d(centres[j]))
+                    })
+                    .unwrap();
+                share[best] += w / total;
+            }
+            centres
+                .into_iter()
+                .zip(share)
+                .map(|(c, share)| Swatch {
+                    color: oklab_to_rgb(c),
+                    share,
+                })
+                .collect()
+        }
+    };
+
+    swatches.retain(|s| s.share > 0.0);
+    swatches.sort_by(|a, b| b.share.total_cmp(&a.share).then(a.color.cmp(&b.color)));
+    swatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 60% red, 30% blue, 10% white, plus transparent pixels that must not count
+    fn image() -> Vec<u8> {
+        let mut data = Vec::new();
+        for i in 0..100 {
+            let p = match i {
+                0..=59 => [230, 20, 20, 255],
+                60..=89 => [20, 40, 220, 255],
+                _ => [250, 250, 250, 255],
+            };
+            data.extend_from_slice(&p);
+        }
+        for _ in 0..50 {
+            data.extend_from_slice(&[0, 255, 0, 0]);
+        }
+        data
+    }
+
+    #[test]
+    fn test_every_method_finds_the_three_colours() {
+        for method in [
+            PaletteMethod::MedianCut,
+            PaletteMethod::KMeans,
+            PaletteMethod::Octree,
+        ] {
+            let palette = extract(&image(), 3, method, 1);
+            assert_eq!(palette.len(), 3, "{:?}", method);
+            let shares: Vec<f32> = palette.iter().map(|s| (s.share * 100.0).round()).collect();
+            assert_eq!(shares, vec![60.0, 30.0, 10.0], "{:?}", method);
+            assert!(palette[0].color[0].abs_diff(230) <= 4, "{:?}", method);
+            assert!(palette[1].color[2].abs_diff(220) <= 4, "{:?}", method);
+        }
+    }
+
+    #[test]
+    fn test_fewer_colours_than_k() {
+        let data = [10, 20, 30, 255].repeat(16);
+        for method in [
+            PaletteMethod::MedianCut,
+            PaletteMethod::KMeans,
+            PaletteMethod::Octree,
+        ] {
+            let palette = extract(&data, 5, method, 3);
+            assert_eq!(palette.len(), 1, "{:?}", method);
+            assert_eq!(palette[0].share, 1.0);
+        }
+    }
+
+    #[test]
+    fn test_kmeans_is_seeded() {
+        let data: Vec<u8> = (0..4096u32)
+            .flat_map(|i| [(i * 7) as u8, (i * 13 / 5) as u8, (i / 16) as u8, 255])
+            .collect();
+        assert_eq!(
+            extract(&data, 6, PaletteMethod::KMeans, 9),
+            extract(&data, 6, PaletteMethod::KMeans, 9)
+        );
+    }
+}
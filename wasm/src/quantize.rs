@@ -0,0 +1,422 @@
+use std::sync::OnceLock;
+
+use crate::palette::MIN_ALPHA;
+use crate::utils::Rng;
+
+// side of the tiled blue-noise threshold map
+const NOISE_SIZE: usize = 64;
+// width of the energy filter used to build it (ulichney's void-and-cluster)
+const NOISE_SIGMA: f32 = 1.5;
+
+// (dx, dy, weight) taps and the divisor of an error-diffusion kernel
+type Diffusion = (&'static [(i32, i32, f32)], f32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dither {
+    None,
+    FloydSteinberg,
+    Atkinson,
+    Sierra,
+    Bayer,
+    BlueNoise,
+}
+
+impl Dither {
+    pub fn from_string(s: &str) -> Option<Dither> {
+        match s {
+            "none" => Some(Dither::None),
+            "floyd-steinberg" => Some(Dither::FloydSteinberg),
+            "atkinson" => Some(Dither::Atkinson),
+            "sierra" => Some(Dither::Sierra),
+            "bayer" => Some(Dither::Bayer),
+            "blue-noise" => Some(Dither::BlueNoise),
+            _ => None,
+        }
+    }
+
+    // atkinson only passes on 6/8 of the error
+    fn kernel(self) -> Option<Diffusion> {
+        match self {
+            Dither::FloydSteinberg => {
+                Some((&[(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)], 16.0))
+            }
+            Dither::Atkinson => Some((
+                &[
+                    (1, 0, 1.0),
+                    (2, 0, 1.0),
+                    (-1, 1, 1.0),
+                    (0, 1, 1.0),
+                    (1, 1, 1.0),
+                    (0, 2, 1.0),
+                ],
+                8.0,
+            )),
+            Dither::Sierra => Some((
+                &[
+                    (1, 0, 5.0),
+                    (2, 0, 3.0),
+                    (-2, 1, 2.0),
+                    (-1, 1, 4.0),
+                    (0, 1, 5.0),
+                    (1, 1, 4.0),
+                    (2, 1, 2.0),
+                    (-1, 2, 2.0),
+                    (0, 2, 3.0),
+                    (1, 2, 2.0),
+                ],
+                32.0,
+            )),
+            _ => None,
+        }
+    }
+}
+
+// an image as palette indices; pixels below MIN_ALPHA use `transparent`,
+// which is one past the last colour when there are any
+pub struct Indexed {
+    pub indices: Vec<u8>,
+    pub palette: Vec<[u8; 3]>,
+    pub transparent: Option<u8>,
+}
+
+impl Indexed {
+    pub fn to_rgba(&self) -> Vec<u8> {
+        self.indices
+            .iter()
+            .flat_map(|&i| match self.palette.get(i as usize) {
+                Some(&[r, g, b]) => [r, g, b, 255],
+                None => [0, 0, 0, 0],
+            })
+            .collect()
+    }
+}
+
+// 8x8 bayer threshold, built digit by digit from the 2x2 pattern
+fn bayer(x: usize, y: usize) -> f32 {
+    let mut value = 0;
+    for bit in 0..3 {
+        let xb = (x >> bit) & 1;
+        let yb = (y >> bit) & 1;
+        value = (value << 2) | ((xb ^ yb) << 1) | yb;
+    }
+    (value as f32 + 0.5) / 64.0
+}
+
+// toroidal gaussian energy of every cell from the set cells
+struct Energy {
+    kernel: Vec<f32>,
+    values: Vec<f32>,
+}
+
+impl Energy {
+    fn new() -> Energy {
+        let n = NOISE_SIZE;
+        let mut kernel = vec![0.0f32; n * n];
+        for dy in 0..n {
+            for dx in 0..n {
+                let wx = dx.min(n - dx) as f32;
+                let wy = dy.min(n - dy) as f32;
+                kernel[dy * n + dx] =
+                    (-(wx * wx + wy * wy) / (2.0 * NOISE_SIGMA * NOISE_SIGMA)).exp();
+            }
+        }
+        Energy {
+            kernel,
+            values: vec![0.0; n * n],
+        }
+    }
+
+    fn toggle(&mut self, cell: usize, sign: f32) {
+        let n = NOISE_SIZE;
+        let (cx, cy) = (cell % n, cell / n);
+        for y in 0..n {
+            let dy = (y + n - cy) % n;
+            for x in 0..n {
+                let dx = (x + n - cx) % n;
+                self.values[y * n + x] += sign * self.kernel[dy * n + dx];
+            }
+        }
+    }
+
+    // highest energy among set cells or lowest among empty ones
+    fn extreme(&self, pattern: &[bool], set: bool) -> usize {
+        let mut best = usize::MAX;
+        for (i, &on) in pattern.iter().enumerate() {
+            if on != set {
+                continue;
+            }
+            let better = best == usize::MAX
+                || if set {
+                    self.values[i] > self.values[best]
+                } else {
+                    self.values[i] < self.values[best]
+                };
+            if better {
+                best = i;
+            }
+        }
+        best
+    }
+}
+
+// ranks every cell of the tile so that each prefix is evenly spread
+fn void_and_cluster() -> Vec<f32> {
+    let cells = NOISE_SIZE * NOISE_SIZE;
+    let mut pattern = vec![false; cells];
+    let mut energy = Energy::new();
+    let mut rng = Rng::new(0);
+    let mut placed = 0;
+    while placed < cells / 10 {
+        let cell = rng.below(cells as u32) as usize;
+        if !pattern[cell] {
+            pattern[cell] = true;
+            energy.toggle(cell, 1.0);
+            placed += 1;
+        }
+    }
+
+    // move points from the tightest cluster to the largest void until stable
+    for _ in 0..cells {
+        let cluster = energy.extreme(&pattern, true);
+        pattern[cluster] = false;
+        energy.toggle(cluster, -1.0);
+        let void = energy.extreme(&pattern, false);
+        pattern[void] = true;
+        energy.toggle(void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0usize; cells];
+    let mut shrinking = pattern.clone();
+    let mut shrinking_energy = Energy {
+        kernel: energy.kernel.clone(),
+        values: energy.values.clone(),
+    };
+    for r in (0..placed).rev() {
+        let cluster = shrinking_energy.extreme(&shrinking, true);
+        shrinking[cluster] = false;
+        shrinking_energy.toggle(cluster, -1.0);
+        rank[cluster] = r;
+    }
+    for r in placed..cells {
+        let void = energy.extreme(&pattern, false);
+        pattern[void] = true;
+        energy.toggle(void, 1.0);
+        rank[void] = r;
+    }
+    rank.iter()
+        .map(|&r| (r as f32 + 0.5) / cells as f32)
+        .collect()
+}
+
+fn blue_noise() -> &'static [f32] {
+    static TILE: OnceLock<Vec<f32>> = OnceLock::new();
+    TILE.get_or_init(void_and_cluster)
+}
+
+// nearest palette colour by rgb distance; entries are kept sorted by green so the
+// search can walk outwards from the query and stop once green alone is too far
+struct Matcher {
+    sorted: Vec<([f32; 3], u8)>,
+}
+
+impl Matcher {
+    fn new(palette: &[[u8; 3]]) -> Matcher {
+        let mut sorted: Vec<([f32; 3], u8)> = palette
+            .iter()
+            .enumerate()
+            .map(|(i, p)| ([p[0] as f32, p[1] as f32, p[2] as f32], i as u8))
+            .collect();
+        sorted.sort_by(|a, b| a.0[1].total_cmp(&b.0[1]).then(a.1.cmp(&b.1)));
+        Matcher { sorted }
+    }
+
+    fn nearest(&self, color: [f32; 3]) -> u8 {
+        let start = self.sorted.partition_point(|e| e.0[1] < color[1]);
+        let mut best = (f32::MAX, u8::MAX);
+        let (mut up, mut down) = (start, start);
+        loop {
+            let reach = |e: &&([f32; 3], u8)| (e.0[1] - color[1]).powi(2) <= best.0;
+            let above = self.sorted.get(up).filter(reach);
+            let below = down.checked_sub(1).map(|i| &self.sorted[i]).filter(reach);
+            if above.is_none() && below.is_none() {
+                break;
+            }
+            for &&(p, index) in above.iter().chain(below.iter()) {
+                let d = (p[0] - color[0]).powi(2)
+                    + (p[1] - color[1]).powi(2)
+                    + (p[2] - color[2]).powi(2);
+                if d < best.0 || (d == best.0 && index < best.1) {
+                    best = (d, index);
+                }
+            }
+            up += above.is_some() as usize;
+            down -= below.is_some() as usize;
+        }
+        best.1
+    }
+}
+
+// maps every opaque pixel to the palette, which must hold 1..=256 colours
+// (255 if the image has transparent pixels)
+pub fn quantize(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    palette: &[[u8; 3]],
+    dither: Dither,
+) -> Indexed {
+    let (w, h) = (width as usize, height as usize);
+    let opaque: Vec<bool> = data.chunks_exact(4).map(|p| p[3] >= MIN_ALPHA).collect();
+    let transparent = opaque.iter().any(|&o| !o).then_some(palette.len() as u8);
+    let mut indices = vec![transparent.unwrap_or(0); w * h];
+
+    if !palette.is_empty() {
+        let matcher = Matcher::new(palette);
+        match dither.kernel() {
+            Some((taps, divisor)) => {
+                let mut work: Vec<[f32; 3]> = data
+                    .chunks_exact(4)
+                    .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32])
+                    .collect();
+                for y in 0..h {
+                    // serpentine scan keeps the error from drifting one way
+                    let reverse = y % 2 == 1;
+                    for step in 0..w {
+                        let x = if reverse { w - 1 - step } else { step };
+                        let i = y * w + x;
+                        if !opaque[i] {
+                            continue;
+                        }
+                        let value = work[i].map(|v| v.clamp(0.0, 255.0));
+                        let index = matcher.nearest(value);
+                        indices[i] = index;
+                        let chosen = palette[index as usize];
+                        let error = [
+                            value[0] - chosen[0] as f32,
+                            value[1] - chosen[1] as f32,
+                            value[2] - chosen[2] as f32,
+                        ];
+                        for &(dx, dy, weight) in taps {
+                            let tx = x as i32 + if reverse { -dx } else { dx };
+                            let ty = y as i32 + dy;
+                            if tx < 0 || tx >= w as i32 || ty >= h as i32 {
+                                continue;
+                            }
+                            let t = ty as usize * w + tx as usize;
+                            if opaque[t] {
+                                for c in 0..3 {
+                                    work[t][c] += error[c] * weight / divisor;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            None => {
+                // ordered dithering nudges by up to one palette step, estimated
+                // from how finely the colours would split an even rgb cube
+                let levels = (palette.len() as f32).cbrt();
+                let spread = 255.0 / (levels - 1.0).max(1.0);
+                let noise = (dither == Dither::BlueNoise).then(blue_noise);
+                for (i, p) in data.chunks_exact(4).enumerate() {
+                    if !opaque[i] {
+                        continue;
+                    }
+                    let (x, y) = (i % w, i / w);
+                    let offset = match (dither, noise) {
+                        (Dither::Bayer, _) => (bayer(x % 8, y % 8) - 0.5) * spread,
+                        (_, Some(tile)) => {
+                            (tile[(y % NOISE_SIZE) * NOISE_SIZE + x % NOISE_SIZE] - 0.5) * spread
+                        }
+                        _ => 0.0,
+                    };
+                    let value = [
+                        p[0] as f32 + offset,
+                        p[1] as f32 + offset,
+                        p[2] as f32 + offset,
+                    ];
+                    indices[i] = matcher.nearest(value);
+                }
+            }
+        }
+    }
+
+    Indexed {
+        indices,
+        palette: palette.to_vec(),
+        transparent,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLACK_WHITE: [[u8; 3]; 2] = [[0, 0, 0], [255, 255, 255]];
+
+    #[test]
+    fn test_threshold_maps_rank_every_cell() {
+        let mut bayer_ranks: Vec<f32> = (0..64).map(|i| bayer(i % 8, i / 8) * 64.0).collect();
+        bayer_ranks.sort_by(f32::total_cmp);
+        assert!(bayer_ranks
+            .iter()
+            .enumerate()
+            .all(|(i, &r)| r == i as f32 + 0.5));
+
+        let mut noise: Vec<f32> = blue_noise().to_vec();
+        noise.sort_by(f32::total_cmp);
+        let cells = (NOISE_SIZE * NOISE_SIZE) as f32;
+        assert!(noise
+            .iter()
+            .enumerate()
+            .all(|(i, &r)| r == (i as f32 + 0.5) / cells));
+    }
+
+    #[test]
+    fn test_dithered_grey_keeps_its_mean() {
+        let (w, h) = (32u32, 32u32);
+        let data = [100, 100, 100, 255].repeat((w * h) as usize);
+        for dither in [
+            Dither::FloydSteinberg,
+            Dither::Atkinson,
+            Dither::Sierra,
+            Dither::Bayer,
+            Dither::BlueNoise,
+        ] {
+            let out = quantize(&data, w, h, &BLACK_WHITE, dither);
+            let white = out.indices.iter().filter(|&&i| i == 1).count() as f32;
+            let mean = white / (w * h) as f32 * 255.0;
+            assert!((mean - 100.0).abs() < 12.0, "{:?}: {}", dither, mean);
+        }
+        let flat = quantize(&data, w, h, &BLACK_WHITE, Dither::None);
+        assert!(flat.indices.iter().all(|&i| i == 0));
+    }
+
+    #[test]
+    fn test_transparent_pixels_get_their_own_index() {
+        let data = [
+            [250, 10, 10, 255],
+            [0, 0, 0, 0],
+            [10, 10, 240, 255],
+            [5, 250, 5, 40],
+        ]
+        .concat();
+        let palette = [[255, 0, 0], [0, 0, 255]];
+        let out = quantize(&data, 2, 2, &palette, Dither::FloydSteinberg);
+        assert_eq!(out.transparent, Some(2));
+        assert_eq!(out.indices, vec![0, 2, 1, 2]);
+        assert_eq!(
+            out.to_rgba(),
+            [
+                [255, 0, 0, 255],
+                [0, 0, 0, 0],
+                [0, 0, 255, 255],
+                [0, 0, 0, 0]
+            ]
+            .concat()
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use crate::mask::gaussian_blur_plane;
+use crate::utils::{hash_noise, smoothstep, value_noise, Rng};
+use crate::warp::{self, Interpolation};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phosphor {
+    None,
+    // vertical red, green and blue stripes
+    Aperture,
+    // stripes broken into staggered slots
+    Slot,
+    // triads offset on every other row
+    Shadow,
+}
+
+impl Phosphor {
+    pub fn from_string(s: &str) -> Option<Phosphor> {
+        match s {
+            "none" => Some(Phosphor::None),
+            "aperture" => Some(Phosphor::Aperture),
+            "slot" => Some(Phosphor::Slot),
+            "shadow" => Some(Phosphor::Shadow),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Crt {
+    // 0..1 bulge of the glass, 0 is a flat screen
+    pub curvature: f32,
+    pub phosphor: Phosphor,
+    // 0..1 strengths of the phosphor mask, scanlines and glow
+    pub mask_strength: f32,
+    pub scanlines: f32,
+    pub bloom: f32,
+    // scanline period in pixels
+    pub spacing: f32,
+}
+
+impl Default for Crt {
+    fn default() -> Crt {
+        Crt {
+            curvature: 0.3,
+            phosphor: Phosphor::Aperture,
+            mask_strength: 0.3,
+            scanlines: 0.4,
+            bloom: 0.3,
+            spacing: 3.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Vhs {
+    // horizontal smear of the colour signal, in pixels
+    pub chroma_bleed: f32,
+    // 0..1 strength of the tracking band and tape noise
+    pub tracking: f32,
+    // sideways offset of colour against brightness, in pixels
+    pub color_shift: f32,
+    // largest sideways wander of the rows, in pixels
+    pub wobble: f32,
+    pub seed: u32,
+}
+
+impl Default for Vhs {
+    fn default() -> Vhs {
+        Vhs {
+            chroma_bleed: 4.0,
+            tracking: 0.3,
+            color_shift: 2.0,
+            wobble: 2.0,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scanlines {
+    // line period in pixels
+    pub spacing: f32,
+    // 0..1 share of the period each line covers
+    pub thickness: f32,
+}
+
+impl Default for Scanlines {
+    fn default() -> Scanlines {
+        Scanlines {
+            spacing: 3.0,
+            thickness: 0.5,
+        }
+    }
+}
+
+// mixes the processed colours into the image by `amount`; alpha is taken
+// from the processed pixels
+fn finish(data: &[u8], processed: &[[f32; 4]], amount: f32) -> Vec<u8> {
+    let amount = amount.clamp(0.0, 1.0);
+    let mut result = data.to_vec();
+    for (p, c) in result.chunks_exact_mut(4).zip(processed) {
+        for ch in 0..4 {
+            let v = p[ch] as f32 + (c[ch] * 255.0 - p[ch] as f32) * amount;
+            p[ch] = v.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    result
+}
+
+// share of the rows from y0 to y1 covered by dark lines taking the last
+// `thickness` of every `spacing` pixels
+fn line_coverage(y0: f32, y1: f32, spacing: f32, thickness: f32) -> f32 {
+    let dark = thickness.clamp(0.0, 1.0) * spacing;
+    let covered =
+        |y: f32| (y / spacing).floor() * dark + (y.rem_euclid(spacing) - (spacing - dark)).max(0.0);
+    (covered(y1) - covered(y0)) / (y1 - y0)
+}
+
+// darkens rows under evenly spaced lines, anti-aliased where a line edge
+// falls inside a row
+pub fn scanlines(data: &[u8], width: u32, settings: &Scanlines, amount: f32) -> Vec<u8> {
+    let Scanlines { spacing, thickness } = *settings;
+    let amount = amount.clamp(0.0, 1.0);
+    let mut result = data.to_vec();
+    for (y, row) in result.chunks_exact_mut((width * 4) as usize).enumerate() {
+        let shade = 1.0 - amount * line_coverage(y as f32, y as f32 + 1.0, spacing, thickness);
+        for p in row.chunks_exact_mut(4) {
+            for c in &mut p[..3] {
+                *c = (*c as f32 * shade).round() as u8;
+            }
+        }
+    }
+    result
+}
+
+// gain of each channel under the phosphor mask at a pixel
+fn phosphor_gain(kind: Phosphor, x: u32, y: u32, strength: f32) -> [f32; 3] {
+    let lit = match kind {
+        Phosphor::None => return [1.0; 3],
+        Phosphor::Aperture => x % 3,
+        Phosphor::Slot => {
+            // a dark gap closes each slot, staggered between neighbouring columns
+            if (y + (x / 3 % 2) * 2).is_multiple_of(4) {
+                return [1.0 - strength; 3];
+            }
+            x % 3
+        }
+        Phosphor::Shadow => (x + (y % 2) * 2) % 3,
+    };
+    // the unlit channels lose light, the lit one is driven a little harder
+    let mut gain = [1.0 - strength; 3];
+    gain[lit as usize] = 1.0 + strength * 0.6;
+    gain
+}
+
+// a curved tube: the picture bulges out and rounds off at the corners, is
+// drawn as scanlines through a phosphor mask, and its highlights glow
+pub fn crt(data: &[u8], width: u32, height: u32, settings: &Crt, amount: f32) -> Vec<u8> {
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let k = settings.curvature.clamp(0.0, 1.0) * 0.3;
+    let spacing = settings.spacing.max(2.0);
+
+    let len = (width * height) as usize;
+    let mut screen: Vec<[f32; 4]> = Vec::with_capacity(len);
+    let mut rows = Vec::with_capacity(len);
+    for y in 0..height {
+        for x in 0..width {
+            // the middle of each edge stays put, the corners fall off the glass
+            let u = (x as f32 + 0.5 - cx) / cx;
+            let v = (y as f32 + 0.5 - cy) / cy;
+            let f = (1.0 + k * (u * u + v * v)) / (1.0 + k);
+            let (su, sv) = (u * f, v * f);
+            let (sx, sy) = (cx + su * cx, cy + sv * cy);
+            let inside = ((1.0 - su.abs()) * cx + 0.5).clamp(0.0, 1.0)
+                * ((1.0 - sv.abs()) * cy + 0.5).clamp(0.0, 1.0);
+            let p = if inside > 0.0 {
+                warp::sample(data, width, height, sx, sy, Interpolation::Bilinear)
+            } else {
+                [0, 0, 0, 255]
+            };
+            let a = p[3] as f32 / 255.0;
+            screen.push([
+                p[0] as f32 / 255.0 * inside,
+                p[1] as f32 / 255.0 * inside,
+                p[2] as f32 / 255.0 * inside,
+                a * inside + (1.0 - inside),
+            ]);
+            rows.push(sy);
+        }
+    }
+
+    // highlights above mid grey spread into a soft glow
+    let bloom = settings.bloom.clamp(0.0, 1.0);
+    let glow: Option<[Vec<f32>; 3]> = (bloom > 0.0).then(|| {
+        let sigma = (width.max(height) as f32 / 80.0).max(2.0);
+        let bright: Vec<f32> = screen
+            .iter()
+            .map(|c| smoothstep(0.5, 1.0, 0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]))
+            .collect();
+        [0, 1, 2].map(|ch| {
+            let plane: Vec<f32> = screen.iter().zip(&bright).map(|(c, b)| c[ch] * b).collect();
+            gaussian_blur_plane(&plane, width, height, sigma)
+        })
+    });
+
+    let strength = settings.mask_strength.clamp(0.0, 1.0);
+    let lines = settings.scanlines.clamp(0.0, 1.0);
+    for (i, c) in screen.iter_mut().enumerate() {
+        let (x, y) = (i as u32 % width, i as u32 / width);
+        // scanlines follow the curved picture rather than the flat pixel rows
+        let sy = rows[i];
+        let shade = 1.0 - lines * line_coverage(sy - 0.5, sy + 0.5, spacing, 0.5);
+        let gain = phosphor_gain(settings.phosphor, x, y, strength);
+        for ch in 0..3 {
+            c[ch] *= shade * gain[ch];
+            if let Some(glow) = &glow {
+                c[ch] += glow[ch][i] * bloom;
+            }
+        }
+    }
+    finish(data, &screen, amount)
+}
+
+fn to_yiq(c: [f32; 3]) -> [f32; 3] {
+    [
+        0.299 * c[0] + 0.587 * c[1] + 0.114 * c[2],
+        0.596 * c[0] - 0.274 * c[1] - 0.322 * c[2],
+        0.211 * c[0] - 0.523 * c[1] + 0.312 * c[2],
+    ]
+}
+
+fn from_yiq(c: [f32; 3]) -> [f32; 3] {
+    [
+        c[0] + 0.956 * c[1] + 0.621 * c[2],
+        c[0] - 0.272 * c[1] - 0.647 * c[2],
+        c[0] - 1.106 * c[1] + 1.703 * c[2],
+    ]
+}
+
+// a worn tape: rows wander sideways, colour smears and lags behind the
+// brightness, and a seeded tracking band tears and flecks the picture
+pub fn vhs(data: &[u8], width: u32, height: u32, settings: &Vhs, amount: f32) -> Vec<u8> {
+    let w = width as usize;
+    let tracking = settings.tracking.clamp(0.0, 1.0);
+    let bleed = settings.chroma_bleed.max(0.0).round() as usize;
+    let shift = settings.color_shift.round() as i64;
+    let seed = settings.seed;
+
+    let mut rng = Rng::new(seed);
+    let band_center = rng.next_f32() * height as f32;
+    let band_half = height as f32 * 0.04 * tracking;
+
+    let mut processed = Vec::with_capacity(w * height as usize);
+    let mut yiq = vec![[0.0f32; 3]; w];
+    let mut alpha = vec![0.0f32; w];
+    let mut sums = vec![[0.0f32; 2]; w + 1];
+    for y in 0..height {
+        let fy = y as f32;
+        let band = if band_half > 0.0 {
+            (1.0 - (fy - band_center).abs() / band_half).max(0.0)
+        } else {
+            0.0
+        };
+        let offset = settings.wobble * (value_noise(0.0, fy, 40.0, seed) * 2.0 - 1.0)
+            + band * 24.0 * (hash_noise(0, y as i32, seed ^ 1) * 2.0 - 1.0);
+
+        // the row read back with its wander, edges clamped
+        let row = &data[y as usize * w * 4..(y as usize + 1) * w * 4];
+        for x in 0..w {
+            let sx = (x as f32 - offset).clamp(0.0, (w - 1) as f32);
+            let x0 = sx.floor() as usize;
+            let x1 = (x0 + 1).min(w - 1);
+            let f = sx - x0 as f32;
+            let at = |x: usize, c: usize| row[x * 4 + c] as f32 / 255.0;
+            let rgb = [0, 1, 2].map(|c| at(x0, c) + (at(x1, c) - at(x0, c)) * f);
+            yiq[x] = to_yiq(rgb);
+            alpha[x] = at(x0, 3) + (at(x1, 3) - at(x0, 3)) * f;
+        }
+
+        // colour is a low bandwidth signal: a box average along the row
+        for x in 0..w {
+            sums[x + 1] = [sums[x][0] + yiq[x][1], sums[x][1] + yiq[x][2]];
+        }
+        for x in 0..w {
+            let centre = (x as i64 - shift).clamp(0, w as i64 - 1) as usize;
+            let lo = centre.saturating_sub(bleed);
+            let hi = (centre + bleed + 1).min(w);
+            let n = (hi - lo) as f32;
+            let i = (sums[hi][0] - sums[lo][0]) / n;
+            let q = (sums[hi][1] - sums[lo][1]) / n;
+
+            let mut luma = yiq[x][0];
+            if tracking > 0.0 {
+                // fine tape grain everywhere, white streaks inside the band
+                luma += (hash_noise(x as i32, y as i32, seed ^ 2) - 0.5) * 0.08 * tracking;
+                let streak = hash_noise(x as i32 / 12, y as i32, seed ^ 3);
+                if streak > 1.0 - 0.3 * band {
+                    luma += 0.6 * band;
+                }
+            }
+            let [r, g, b] = from_yiq([luma, i, q]);
+            processed.push([r, g, b, alpha[x]]);
+        }
+    }
+    finish(data, &processed, amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                data.extend_from_slice(&[(x * 7 % 256) as u8, (y * 5 % 256) as u8, 120, 255]);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_scanlines_cover_their_share() {
+        let white = [255u8; 4].repeat(8 * 8);
+        let lines = |spacing: f32| Scanlines {
+            spacing,
+            thickness: 0.5,
+        };
+        let out = scanlines(&white, 8, &lines(4.0), 1.0);
+        let rows: Vec<u8> = out.chunks_exact(8 * 4).map(|r| r[0]).collect();
+        assert_eq!(rows, [255, 255, 0, 0, 255, 255, 0, 0]);
+        assert!(out.chunks_exact(4).all(|p| p[3] == 255));
+
+        // a line edge inside a row leaves it partly shaded
+        let out = scanlines(&white, 8, &lines(3.0), 1.0);
+        assert_eq!(out[(8 + 1) * 4], 128);
+        assert_eq!(scanlines(&white, 8, &lines(3.0), 0.0), white);
+    }
+
+    #[test]
+    fn test_crt_curves_masks_and_stays_flat_when_off() {
+        let data = gradient(24, 18);
+        let off = Crt {
+            curvature: 0.0,
+            phosphor: Phosphor::None,
+            mask_strength: 0.0,
+            scanlines: 0.0,
+            bloom: 0.0,
+            spacing: 3.0,
+        };
+        assert_eq!(crt(&data, 24, 18, &off, 1.0), data);
+
+        let curved = Crt {
+            curvature: 1.0,
+            ..off
+        };
+        let flat = [200, 100, 50, 255].repeat(24 * 18);
+        let out = crt(&flat, 24, 18, &curved, 1.0);
+        assert_eq!(&out[..4], &[0, 0, 0, 255]);
+        let centre = ((9 * 24 + 12) * 4) as usize;
+        assert_eq!(&out[centre..centre + 4], &[200, 100, 50, 255]);
+
+        let white = [255u8; 4].repeat(9 * 3);
+        let masked = Crt {
+            phosphor: Phosphor::Aperture,
+            mask_strength: 0.5,
+            ..off
+        };
+        let out = crt(&white, 9, 3, &masked, 1.0);
+        assert_eq!(&out[..3], &[255, 128, 128]);
+        assert_eq!(&out[4..7], &[128, 255, 128]);
+    }
+
+    #[test]
+    fn test_vhs_is_seeded_and_keeps_flat_colour() {
+        let flat = [180, 90, 40, 255].repeat(32 * 24);
+        let calm = Vhs {
+            chroma_bleed: 4.0,
+            tracking: 0.0,
+            color_shift: 2.0,
+            wobble: 0.0,
+            seed: 0,
+        };
+        let out = vhs(&flat, 32, 24, &calm, 1.0);
+        assert!(out.iter().zip(&flat).all(|(a, b)| a.abs_diff(*b) <= 1));
+
+        let data = gradient(32, 24);
+        let worn = Vhs {
+            tracking: 1.0,
+            wobble: 3.0,
+            seed: 5,
+            ..calm
+        };
+        let a = vhs(&data, 32, 24, &worn, 1.0);
+        assert_eq!(a, vhs(&data, 32, 24, &worn, 1.0));
+        assert_ne!(a, vhs(&data, 32, 24, &Vhs { seed: 6, ..worn }, 1.0));
+    }
+}
@@ -0,0 +1,274 @@
+use crate::sobel_magnitude;
+use crate::transform::{reorient, Orientation};
+
+// energy added or removed at full mask strength, dwarfs any sobel response
+const MASK_ENERGY: f32 = 1.0e6;
+
+struct Carver {
+    data: Vec<u8>,
+    bias: Vec<f32>,
+    width: u32,
+    height: u32,
+}
+
+fn transpose_plane<T: Copy + Default>(values: &[T], width: u32, height: u32) -> Vec<T> {
+    let mut result = vec![T::default(); values.len()];
+    for y in 0..height {
+        for x in 0..width {
+            result[(x * height + y) as usize] = values[(y * width + x) as usize];
+        }
+    }
+    result
+}
+
+// per-pixel energy offset from the optional protect and remove masks
+pub fn mask_bias(len: usize, protect: Option<&[u8]>, remove: Option<&[u8]>) -> Vec<f32> {
+    let mut bias = vec![0.0f32; len];
+    if let Some(mask) = protect {
+        for (b, &m) in bias.iter_mut().zip(mask) {
+            *b += m as f32 / 255.0 * MASK_ENERGY;
+        }
+    }
+    if let Some(mask) = remove {
+        for (b, &m) in bias.iter_mut().zip(mask) {
+            *b -= m as f32 / 255.0 * MASK_ENERGY;
+        }
+    }
+    bias
+}
+
+impl Carver {
+    fn transposed(self) -> Carver {
+        let (data, width, height) =
+            reorient(&self.data, self.width, self.height, Orientation::Transpose);
+        Carver {
+            data,
+            bias: transpose_plane(&self.bias, self.width, self.height),
+            width,
+            height,
+        }
+    }
+
+    // lowest-energy 8-connected vertical seam, one x per row
+    fn find_seam(&self) -> Vec<u32> {
+        let w = self.width as usize;
+        let h = self.height as usize;
+        let energy = sobel_magnitude(&self.data, self.width, self.height);
+
+        let mut cost: Vec<f32> = energy.iter().zip(&self.bias).map(|(e, b)| e + b).collect();
+        for y in 1..h {
+            for x in 0..w {
+                let row = (y - 1) * w;
+                let mut best = cost[row + x];
+                if x > 0 {
+                    best = best.min(cost[row + x - 1]);
+                }
+                if x + 1 < w {
+                    best = best.min(cost[row + x + 1]);
+                }
+                cost[y * w + x] += best;
+            }
+        }
+
+        let mut seam = vec![0u32; h];
+        let last = (h - 1) * w;
+        let mut x = (0..w)
+            .min_by(|&a, &b| cost[last + a].total_cmp(&cost[last + b]))
+            .unwrap_or(0);
+        seam[h - 1] = x as u32;
+
+        for y in (0..h - 1).rev() {
+            let row = y * w;
+            let lo = x.saturating_sub(1);
+            let hi = (x + 1).min(w - 1);
+            x = (lo..=hi)
+                .min_by(|&a, &b| cost[row + a].total_cmp(&cost[row + b]))
+                .unwrap_or(x);
+            seam[y] = x as u32;
+        }
+
+        seam
+    }
+
+    fn remove_seam(&mut self, seam: &[u32]) {
+        let w = self.width as usize;
+        let mut data = Vec::with_capacity(self.data.len() - self.height as usize * 4);
+        let mut bias = Vec::with_capacity(self.bias.len() - self.height as usize);
+
+        for (y, &sx) in seam.iter().enumerate() {
+            let sx = sx as usize;
+            let row = y * w;
+            data.extend_from_slice(&self.data[row * 4..(row + sx) * 4]);
+            data.extend_from_slice(&self.data[(row + sx + 1) * 4..(row + w) * 4]);
+            bias.extend_from_slice(&self.bias[row..row + sx]);
+            bias.extend_from_slice(&self.bias[row + sx + 1..row + w]);
+        }
+
+        self.data = data;
+        self.bias = bias;
+        self.width -= 1;
+    }
+
+    fn shrink(&mut self, count: u32) {
+        for _ in 0..count {
+            let seam = self.find_seam();
+            self.remove_seam(&seam);
+        }
+    }
+
+    // finds `count` seams on a scratch copy and duplicates them in place
+    fn grow_once(&mut self, count: u32) {
+        let w = self.width as usize;
+        let mut scratch = Carver {
+            data: self.data.clone(),
+            bias: self.bias.clone(),
+            width: self.width,
+            height: self.height,
+        };
+        let mut origin: Vec<u32> = (0..self.height).flat_map(|_| 0..self.width).collect();
+        let mut chosen: Vec<Vec<u32>> =
+            vec![Vec::with_capacity(count as usize); self.height as usize];
+
+        for _ in 0..count {
+            let seam = scratch.find_seam();
+            let sw = scratch.width as usize;
+            let mut next_origin = Vec::with_capacity(origin.len() - seam.len());
+            for (y, &sx) in seam.iter().enumerate() {
+                let row = y * sw;
+                chosen[y].push(origin[row + sx as usize]);
+                next_origin.extend_from_slice(&origin[row..row + sx as usize]);
+                next_origin.extend_from_slice(&origin[row + sx as usize + 1..row + sw]);
+            }
+            origin = next_origin;
+            scratch.remove_seam(&seam);
+        }
+
+        let new_w = w + count as usize;
+        let mut data = Vec::with_capacity(new_w * self.height as usize * 4);
+        let mut bias = Vec::with_capacity(new_w * self.height as usize);
+
+        for (y, seams) in chosen.iter_mut().enumerate() {
+            seams.sort_unstable();
+            let row = y * w;
+            let mut next = seams.iter().peekable();
+
+            for x in 0..w {
+                let idx = (row + x) * 4;
+                data.extend_from_slice(&self.data[idx..idx + 4]);
+                bias.push(self.bias[row + x]);
+
+                while next.peek().is_some_and(|&&sx| sx as usize == x) {
+                    next.next();
+                    let right = (row + (x + 1).min(w - 1)) * 4;
+                    for c in 0..4 {
+                        let avg =
+                            (self.data[idx + c] as u16 + self.data[right + c] as u16).div_ceil(2);
+                        data.push(avg as u8);
+                    }
+                    bias.push(self.bias[row + x]);
+                }
+            }
+        }
+
+        self.data = data;
+        self.bias = bias;
+        self.width = new_w as u32;
+    }
+
+    fn grow(&mut self, count: u32) {
+        // inserting more than half the width at once stretches a single region
+        let mut remaining = count;
+        while remaining > 0 {
+            let batch = remaining.min((self.width / 2).max(1));
+            self.grow_once(batch);
+            remaining -= batch;
+        }
+    }
+
+    fn resize_width(&mut self, target: u32) {
+        if target < self.width {
+            self.shrink(self.width - target);
+        } else if target > self.width {
+            // the remove mask only steers shrinking
+            for b in self.bias.iter_mut() {
+                *b = b.max(0.0);
+            }
+            self.grow(target - self.width);
+        }
+    }
+}
+
+// content-aware resize, width first and then height through a transpose
+pub fn seam_carve(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    target_width: u32,
+    target_height: u32,
+    bias: Vec<f32>,
+) -> Vec<u8> {
+    let mut carver = Carver {
+        data: data.to_vec(),
+        bias,
+        width,
+        height,
+    };
+
+    carver.resize_width(target_width);
+
+    if target_height != carver.height {
+        let mut turned = carver.transposed();
+        turned.resize_width(target_height);
+        carver = turned.transposed();
+    }
+
+    carver.data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // flat grey image with a sharp vertical stripe at column `stripe`
+    fn striped(width: u32, height: u32, stripe: u32) -> Vec<u8> {
+        let mut data = vec![120u8; (width * height * 4) as usize];
+        for y in 0..height {
+            let idx = ((y * width + stripe) * 4) as usize;
+            data[idx..idx + 3].copy_from_slice(&[255, 0, 0]);
+        }
+        data
+    }
+
+    fn red_columns(data: &[u8], width: u32) -> Vec<u32> {
+        (0..width)
+            .filter(|&x| data[(x * 4) as usize] == 255)
+            .collect()
+    }
+
+    #[test]
+    fn test_shrink_keeps_detail() {
+        let data = striped(12, 6, 8);
+        let out = seam_carve(&data, 12, 6, 8, 6, vec![0.0; 72]);
+        assert_eq!(out.len(), 8 * 6 * 4);
+        assert_eq!(red_columns(&out, 8).len(), 1);
+    }
+
+    #[test]
+    fn test_remove_mask_is_carved_first() {
+        let data = striped(10, 4, 3);
+        let mut remove = vec![0u8; 40];
+        for y in 0..4 {
+            remove[y * 10 + 3] = 255;
+        }
+        let bias = mask_bias(40, None, Some(&remove));
+        let out = seam_carve(&data, 10, 4, 9, 4, bias);
+        assert!(red_columns(&out, 9).is_empty());
+    }
+
+    #[test]
+    fn test_grow_both_directions() {
+        let data = striped(6, 5, 2);
+        let out = seam_carve(&data, 6, 5, 9, 7, vec![0.0; 30]);
+        assert_eq!(out.len(), 9 * 7 * 4);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::color::rgb_to_lab;
+
+// largest distance in each space, used to bring tolerances into 0..1
+const RGB_RANGE: f32 = 441.672_96;
+const LAB_RANGE: f32 = 100.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    Rgb,
+    Lab,
+}
+
+impl ColorSpace {
+    pub fn from_string(s: &str) -> Option<ColorSpace> {
+        match s {
+            "rgb" => Some(ColorSpace::Rgb),
+            "lab" => Some(ColorSpace::Lab),
+            _ => None,
+        }
+    }
+}
+
+// normalized distance of every pixel from the seed colour, alpha differences count too
+fn distances(data: &[u8], seed: usize, space: ColorSpace) -> Vec<f32> {
+    let to_space = |p: &[u8]| -> [f32; 3] {
+        match space {
+            ColorSpace::Rgb => [p[0] as f32, p[1] as f32, p[2] as f32],
+            ColorSpace::Lab => rgb_to_lab(p[0], p[1], p[2]),
+        }
+    };
+    let range = match space {
+        ColorSpace::Rgb => RGB_RANGE,
+        ColorSpace::Lab => LAB_RANGE,
+    };
+    let key = &data[seed * 4..seed * 4 + 4];
+    let key_color = to_space(key);
+
+    data.chunks_exact(4)
+        .map(|p| {
+            let alpha = p[3].abs_diff(key[3]) as f32 / 255.0;
+            if p[3] == 0 && key[3] == 0 {
+                return 0.0;
+            }
+            let c = to_space(p);
+            let d = ((c[0] - key_color[0]).powi(2)
+                + (c[1] - key_color[1]).powi(2)
+                + (c[2] - key_color[2]).powi(2))
+            .sqrt();
+            (d / range).min(1.0).max(alpha)
+        })
+        .collect()
+}
+
+// pixels within tolerance, flooded 4-connected from the seed when contiguous
+fn region(
+    dist: &[f32],
+    width: u32,
+    height: u32,
+    seed: usize,
+    tolerance: f32,
+    contiguous: bool,
+) -> Vec<bool> {
+    if !contiguous {
+        return dist.iter().map(|&d| d <= tolerance).collect();
+    }
+
+    let (w, h) = (width as usize, height as usize);
+    let mut selected = vec![false; dist.len()];
+    let mut stack = vec![seed];
+    selected[seed] = true;
+    while let Some(idx) = stack.pop() {
+        let (x, y) = (idx % w, idx / w);
+        let neighbours = [
+            (x > 0).then(|| idx - 1),
+            (x + 1 < w).then(|| idx + 1),
+            (y > 0).then(|| idx - w),
+            (y + 1 < h).then(|| idx + w),
+        ];
+        for n in neighbours.into_iter().flatten() {
+            if !selected[n] && dist[n] <= tolerance {
+                selected[n] = true;
+                stack.push(n);
+            }
+        }
+    }
+    selected
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn magic_wand(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+    tolerance: f32,
+    contiguous: bool,
+    space: ColorSpace,
+) -> Vec<u8> {
+    let seed = (y * width + x) as usize;
+    let dist = distances(data, seed, space);
+    region(&dist, width, height, seed, tolerance, contiguous)
+        .iter()
+        .map(|&s| if s { 255 } else { 0 })
+        .collect()
+}
+
+// selection with partial coverage on the pixels bordering it, so fills blend
+// into anti-aliased outlines instead of leaving a halo
+#[allow(clippy::too_many_arguments)]
+pub fn soft_selection(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+    tolerance: f32,
+    contiguous: bool,
+    space: ColorSpace,
+) -> Vec<f32> {
+    let seed = (y * width + x) as usize;
+    let dist = distances(data, seed, space);
+    let selected = region(&dist, width, height, seed, tolerance, contiguous);
+    let band = tolerance.max(0.05);
+
+    let (w, h) = (width as i64, height as i64);
+    let mut coverage: Vec<f32> = selected.iter().map(|&s| s as u8 as f32).collect();
+    for py in 0..h {
+        for px in 0..w {
+            let idx = (py * w + px) as usize;
+            if selected[idx] {
+                continue;
+            }
+            let borders = (-1..=1).any(|dy| {
+                (-1..=1).any(|dx| {
+                    let (nx, ny) = (px + dx, py + dy);
+                    nx >= 0 && ny >= 0 && nx < w && ny < h && selected[(ny * w + nx) as usize]
+                })
+            });
+            if borders {
+                coverage[idx] = (1.0 - (dist[idx] - tolerance) / band).clamp(0.0, 1.0);
+            }
+        }
+    }
+    coverage
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn flood_fill(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    x: u32,
+    y: u32,
+    color: [u8; 4],
+    tolerance: f32,
+    contiguous: bool,
+    space: ColorSpace,
+) -> Vec<u8> {
+    let coverage = soft_selection(data, width, height, x, y, tolerance, contiguous, space);
+    let mut result = data.to_vec();
+    for (pixel, &t) in result.chunks_exact_mut(4).zip(&coverage) {
+        if t <= 0.0 {
+            continue;
+        }
+        for c in 0..4 {
+            let value = pixel[c] as f32 + (color[c] as f32 - pixel[c] as f32) * t;
+            pixel[c] = value.round() as u8;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // two white squares split by a black column: W B W
+    fn split() -> Vec<u8> {
+        let mut data = Vec::new();
+        for _ in 0..3 {
+            data.extend_from_slice(&[255, 255, 255, 255, 0, 0, 0, 255, 250, 250, 250, 255]);
+        }
+        data
+    }
+
+    #[test]
+    fn test_contiguous_and_global() {
+        let data = split();
+        let contiguous = magic_wand(&data, 3, 3, 0, 0, 0.05, true, ColorSpace::Rgb);
+        assert_eq!(contiguous, vec![255, 0, 0, 255, 0, 0, 255, 0, 0]);
+
+        let global = magic_wand(&data, 3, 3, 0, 0, 0.05, false, ColorSpace::Lab);
+        assert_eq!(global, vec![255, 0, 255, 255, 0, 255, 255, 0, 255]);
+    }
+
+    #[test]
+    fn test_flood_fill_blends_the_border() {
+        // white, grey outline pixel, black
+        let data = vec![
+            255, 255, 255, 255, 255, 255, 255, 255, 220, 220, 220, 255, 0, 0, 0, 255,
+        ];
+        let out = flood_fill(
+            &data,
+            4,
+            1,
+            0,
+            0,
+            [255, 0, 0, 255],
+            0.1,
+            true,
+            ColorSpace::Rgb,
+        );
+        assert_eq!(&out[0..4], &[255, 0, 0, 255]);
+        assert_eq!(&out[4..8], &[255, 0, 0, 255]);
+        // the grey pixel is partly recoloured, the black one is untouched
+        assert!(out[9] < 220 && out[9] > 0);
+        assert_eq!(&out[12..16], &[0, 0, 0, 255]);
+    }
+}
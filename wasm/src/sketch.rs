@@ -0,0 +1,237 @@
+use std::f32::consts::FRAC_1_SQRT_2;
+
+use crate::color::{oklab_to_rgb, rgb_to_oklab};
+use crate::mask::{distance_transform, gaussian_blur_plane, radius_to_sigma};
+use crate::utils::{luminance, smoothstep, value_noise};
+use crate::{box_blur, sobel_magnitude};
+
+// distance between hatching strokes in pixels
+const HATCH_SPACING: f32 = 6.0;
+// sobel response, on the mean of rgb, that counts as an outline
+const EDGE_THRESHOLD: f32 = 96.0;
+// box blur radius that flattens texture before the cartoon bands are cut
+const CARTOON_SMOOTHING: i32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sketch {
+    // blur radius in pixels, wider gives softer and broader pencil lines
+    pub radius: f32,
+    // 0..1 strength of diagonal strokes shading the darker tones
+    pub hatching: f32,
+    pub seed: u32,
+}
+
+impl Default for Sketch {
+    fn default() -> Sketch {
+        Sketch {
+            radius: 12.0,
+            hatching: 0.0,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cartoon {
+    // lightness bands the colours are cut into
+    pub levels: u32,
+    // outline width in pixels, 0 leaves the outlines out
+    pub edge_thickness: f32,
+}
+
+impl Default for Cartoon {
+    fn default() -> Cartoon {
+        Cartoon {
+            levels: 5,
+            edge_thickness: 2.0,
+        }
+    }
+}
+
+fn luma(p: &[u8]) -> f32 {
+    luminance(p[0] as f32, p[1] as f32, p[2] as f32)
+}
+
+// mixes the drawn colours into the image by `amount`, alpha is kept
+fn finish(data: &[u8], drawn: &[[f32; 3]], amount: f32) -> Vec<u8> {
+    let amount = amount.clamp(0.0, 1.0);
+    let mut result = data.to_vec();
+    for (p, c) in result.chunks_exact_mut(4).zip(drawn) {
+        for ch in 0..3 {
+            let v = p[ch] as f32 + (c[ch] - p[ch] as f32) * amount;
+            p[ch] = v.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    result
+}
+
+// one family of parallel strokes at a coordinate across them; the strokes
+// wander a little so they look drawn rather than ruled
+fn strokes(across: f32, wander: f32) -> f32 {
+    let d = ((across + wander).rem_euclid(HATCH_SPACING) - HATCH_SPACING / 2.0).abs();
+    1.0 - smoothstep(0.4, 1.2, d)
+}
+
+// colour dodge of the grayscale by its own blurred negative: flat areas dodge
+// to white paper and only changes in tone leave graphite behind
+pub fn pencil_sketch(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    settings: &Sketch,
+    amount: f32,
+) -> Vec<u8> {
+    let gray: Vec<f32> = data.chunks_exact(4).map(luma).collect();
+    let negative: Vec<f32> = gray.iter().map(|&g| 255.0 - g).collect();
+    let blurred = gaussian_blur_plane(&negative, width, height, radius_to_sigma(settings.radius));
+    let hatching = settings.hatching.clamp(0.0, 1.0);
+
+    let mut drawn = Vec::with_capacity(gray.len());
+    for y in 0..height {
+        for x in 0..width {
+            let i = (y * width + x) as usize;
+            let base = gray[i];
+            let blend = blurred[i].round().min(255.0);
+            let mut v = if blend >= 255.0 {
+                255.0
+            } else {
+                (base * 255.0 / (255.0 - blend)).min(255.0)
+            };
+
+            // a first layer of strokes over the midtones, crossed in the shadows
+            if hatching > 0.0 {
+                let (fx, fy) = (x as f32, y as f32);
+                let tone = base / 255.0;
+                let wander = value_noise(fx, fy, 12.0, settings.seed) * HATCH_SPACING * 0.5;
+                let ink = strokes((fx + fy) * FRAC_1_SQRT_2, wander) * smoothstep(0.8, 0.4, tone)
+                    + strokes((fx - fy) * FRAC_1_SQRT_2, wander) * smoothstep(0.5, 0.15, tone);
+                v *= 1.0 - hatching * 0.6 * ink.min(1.0);
+            }
+            drawn.push([v; 3]);
+        }
+    }
+    finish(data, &drawn, amount)
+}
+
+// flat colour regions from banding a smoothed copy's oklab lightness, keeping
+// its hue, outlined in black wherever the smoothed image has a strong edge
+pub fn cartoon(data: &[u8], width: u32, height: u32, settings: &Cartoon, amount: f32) -> Vec<u8> {
+    let smooth = box_blur(data, width, height, CARTOON_SMOOTHING);
+    let steps = (settings.levels.max(2) - 1) as f32;
+
+    // outline coverage from the distance to the nearest edge pixel, with the
+    // last half pixel anti-aliased
+    let thickness = settings.edge_thickness.max(0.0);
+    let lines = (thickness > 0.0).then(|| {
+        let edges: Vec<bool> = sobel_magnitude(&smooth, width, height)
+            .iter()
+            .map(|&m| m > EDGE_THRESHOLD)
+            .collect();
+        distance_transform(&edges, width, height)
+            .iter()
+            .map(|&d| (thickness / 2.0 + 0.5 - d).clamp(0.0, 1.0))
+            .collect::<Vec<f32>>()
+    });
+
+    let drawn: Vec<[f32; 3]> = smooth
+        .chunks_exact(4)
+        .enumerate()
+        .map(|(i, p)| {
+            let [l, a, b] = rgb_to_oklab(p[0], p[1], p[2]);
+            let banded = (l.clamp(0.0, 1.0) * steps).round() / steps;
+            let ink = lines.as_ref().map_or(0.0, |lines| lines[i]);
+            oklab_to_rgb([banded, a, b]).map(|c| c as f32 * (1.0 - ink))
+        })
+        .collect();
+    finish(data, &drawn, amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn halves(size: u32, left: u8, right: u8) -> Vec<u8> {
+        let mut data = Vec::new();
+        for _ in 0..size {
+            for x in 0..size {
+                let v = if x < size / 2 { left } else { right };
+                data.extend_from_slice(&[v, v, v, 255]);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_sketch_leaves_paper_and_draws_edges() {
+        let settings = Sketch {
+            radius: 6.0,
+            hatching: 0.0,
+            seed: 0,
+        };
+        let flat = [90, 90, 90, 200].repeat(16 * 16);
+        let out = pencil_sketch(&flat, 16, 16, &settings, 1.0);
+        assert!(out.chunks_exact(4).all(|p| p == [255, 255, 255, 200]));
+
+        // the dark side of an edge picks up a line, far from it is paper again
+        let out = pencil_sketch(&halves(40, 0, 255), 40, 40, &settings, 1.0);
+        let at = |x: usize| out[(20 * 40 + x) * 4];
+        assert!(at(19) < 40, "{}", at(19));
+        assert_eq!(at(0), 255);
+        assert_eq!(at(39), 255);
+    }
+
+    #[test]
+    fn test_hatching_shades_dark_tones_only() {
+        let settings = Sketch {
+            radius: 6.0,
+            hatching: 1.0,
+            seed: 3,
+        };
+        let dark = pencil_sketch(&[40, 40, 40, 255].repeat(32 * 32), 32, 32, &settings, 1.0);
+        assert!(dark.chunks_exact(4).any(|p| p[0] < 160));
+        let light = pencil_sketch(
+            &[235, 235, 235, 255].repeat(32 * 32),
+            32,
+            32,
+            &settings,
+            1.0,
+        );
+        assert!(light.chunks_exact(4).all(|p| p[0] == 255));
+        let again = pencil_sketch(&[40, 40, 40, 255].repeat(32 * 32), 32, 32, &settings, 1.0);
+        assert_eq!(dark, again);
+    }
+
+    #[test]
+    fn test_cartoon_bands_and_outlines() {
+        let mut ramp = Vec::new();
+        for _ in 0..8 {
+            for x in 0..64u32 {
+                let v = (x * 4) as u8;
+                ramp.extend_from_slice(&[v, v, v, 255]);
+            }
+        }
+        let plain = Cartoon {
+            levels: 4,
+            edge_thickness: 0.0,
+        };
+        let out = cartoon(&ramp, 64, 8, &plain, 1.0);
+        let mut colors: Vec<&[u8]> = out.chunks_exact(4).collect();
+        colors.sort();
+        colors.dedup();
+        assert_eq!(colors.len(), 4, "{:?}", colors);
+
+        let inked = |thickness: f32| {
+            let settings = Cartoon {
+                levels: 4,
+                edge_thickness: thickness,
+            };
+            cartoon(&halves(32, 40, 220), 32, 32, &settings, 1.0)
+                .chunks_exact(4)
+                .filter(|p| p[0] == 0)
+                .count()
+        };
+        assert_eq!(inked(0.0), 0);
+        assert!(inked(4.0) > inked(1.0));
+        assert!(inked(1.0) > 0);
+    }
+}
@@ -0,0 +1,186 @@
+use crate::sobel_magnitude;
+use crate::utils::{integral, window_sum};
+
+const EDGE_WEIGHT: f64 = 1.0;
+const SATURATION_WEIGHT: f64 = 0.6;
+const SKIN_WEIGHT: f64 = 1.8;
+
+// reference skin chroma, normalised rgb
+const SKIN_COLOR: [f32; 3] = [0.78, 0.57, 0.44];
+const SKIN_THRESHOLD: f32 = 0.8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CropWindow {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub score: f32,
+}
+
+// 0..1 closeness of a pixel to typical skin tones
+fn skin_score(r: f32, g: f32, b: f32) -> f32 {
+    let mag = (r * r + g * g + b * b).sqrt();
+    if mag <= 0.0 {
+        return 0.0;
+    }
+    let dr = r / mag - SKIN_COLOR[0];
+    let dg = g / mag - SKIN_COLOR[1];
+    let db = b / mag - SKIN_COLOR[2];
+    let similarity = 1.0 - (dr * dr + dg * dg + db * db).sqrt();
+
+    // very dark and blown out pixels are rarely skin
+    let lightness = (r + g + b) / 3.0;
+    if similarity < SKIN_THRESHOLD || !(0.18..=0.95).contains(&lightness) {
+        return 0.0;
+    }
+    (similarity - SKIN_THRESHOLD) / (1.0 - SKIN_THRESHOLD)
+}
+
+// per-pixel interest combining edges, saturation and skin, weighted by alpha
+pub fn importance_map(data: &[u8], width: u32, height: u32) -> Vec<f64> {
+    let edges = sobel_magnitude(data, width, height);
+    let mut result = vec![0.0f64; (width * height) as usize];
+
+    for (i, value) in result.iter_mut().enumerate() {
+        let idx = i * 4;
+        let r = data[idx] as f32 / 255.0;
+        let g = data[idx + 1] as f32 / 255.0;
+        let b = data[idx + 2] as f32 / 255.0;
+        let a = data[idx + 3] as f32 / 255.0;
+
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let saturation = if max > 0.0 { (max - min) / max } else { 0.0 };
+        // a full-scale sobel response on the channel mean peaks near 1020
+        let edge = (edges[i] / 1020.0).min(1.0);
+
+        *value = (EDGE_WEIGHT * edge as f64
+            + SATURATION_WEIGHT * (saturation * max) as f64
+            + SKIN_WEIGHT * skin_score(r, g, b) as f64)
+            * a as f64;
+    }
+
+    result
+}
+
+// largest window of the target aspect ratio that fits inside the image
+pub fn window_size(width: u32, height: u32, target_width: u32, target_height: u32) -> (u32, u32) {
+    let aspect = target_width as f64 / target_height as f64;
+    if width as f64 / height as f64 > aspect {
+        let w = ((height as f64 * aspect).round() as u32).clamp(1, width);
+        (w, height)
+    } else {
+        let h = ((width as f64 / aspect).round() as u32).clamp(1, height);
+        (width, h)
+    }
+}
+
+// slides the largest target-aspect window over the image and keeps the one
+// holding the most interest, ties go to the window nearest the centre
+pub fn suggest_crop(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    target_width: u32,
+    target_height: u32,
+) -> CropWindow {
+    let (crop_w, crop_h) = window_size(width, height, target_width, target_height);
+    let importance = importance_map(data, width, height);
+    let total: f64 = importance.iter().sum();
+    let table = integral(&importance, width, height);
+
+    let center_x = (width - crop_w) as f64 / 2.0;
+    let center_y = (height - crop_h) as f64 / 2.0;
+
+    let mut best = CropWindow {
+        x: center_x.floor() as u32,
+        y: center_y.floor() as u32,
+        width: crop_w,
+        height: crop_h,
+        score: 0.0,
+    };
+    if total <= 0.0 {
+        return best;
+    }
+
+    let mut best_sum = f64::NEG_INFINITY;
+    let mut best_offset = f64::INFINITY;
+    for y in 0..=(height - crop_h) {
+        for x in 0..=(width - crop_w) {
+            let sum = window_sum(&table, width, x, y, crop_w, crop_h);
+            let offset = (x as f64 - center_x).abs() + (y as f64 - center_y).abs();
+            let tolerance = total * 1e-9;
+
+            if sum > best_sum + tolerance
+                || ((sum - best_sum).abs() <= tolerance && offset < best_offset)
+            {
+                best_sum = sum;
+                best_offset = offset;
+                best.x = x;
+                best.y = y;
+            }
+        }
+    }
+
+    best.score = (best_sum / total).clamp(0.0, 1.0) as f32;
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_size() {
+        assert_eq!(window_size(1920, 1080, 1, 1), (1080, 1080));
+        assert_eq!(window_size(1080, 1920, 16, 9), (1080, 608));
+        assert_eq!(window_size(100, 100, 4, 5), (80, 100));
+    }
+
+    #[test]
+    fn test_finds_detail_off_centre() {
+        let (width, height) = (40u32, 10u32);
+        let mut data = vec![128u8; (width * height * 4) as usize];
+        // checkerboard detail near the right edge
+        for y in 0..height {
+            for x in 30..38 {
+                let idx = ((y * width + x) * 4) as usize;
+                let v = if (x + y) % 2 == 0 { 0 } else { 255 };
+                data[idx..idx + 3].copy_from_slice(&[v, v, v]);
+            }
+        }
+
+        let crop = suggest_crop(&data, width, height, 1, 1);
+        assert_eq!((crop.width, crop.height), (10, 10));
+        assert!(crop.x >= 28 && crop.x <= 30, "x = {}", crop.x);
+        assert!(crop.score > 0.9);
+    }
+
+    #[test]
+    fn test_detail_on_the_border_counts() {
+        let (width, height) = (30u32, 10u32);
+        let mut data = vec![128u8; (width * height * 4) as usize];
+        for y in 0..height {
+            // vertical stripes along the left edge
+            for x in 0..3 {
+                let idx = ((y * width + x) * 4) as usize;
+                let v = if x % 2 == 0 { 0 } else { 255 };
+                data[idx..idx + 3].copy_from_slice(&[v, v, v]);
+            }
+        }
+
+        // the outermost column scores like the pixels next to it
+        let importance = importance_map(&data, width, height);
+        assert!((0..height).all(|y| importance[(y * width) as usize] > 0.0));
+        let crop = suggest_crop(&data, width, height, 1, 1);
+        assert_eq!((crop.x, crop.y), (0, 0));
+    }
+
+    #[test]
+    fn test_flat_image_centres() {
+        let data = vec![200u8; 20 * 10 * 4];
+        let crop = suggest_crop(&data, 20, 10, 1, 1);
+        assert_eq!((crop.x, crop.y), (5, 0));
+    }
+}
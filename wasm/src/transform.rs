@@ -0,0 +1,114 @@
+// tile edge used when walking the source, keeps both reads and writes cache local
+const BLOCK: u32 = 32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    Transpose,
+    Transverse,
+}
+
+// number of clockwise quarter turns when the angle is an exact multiple of 90
+pub fn quarter_turns(degrees: f32) -> Option<u32> {
+    let turns = degrees / 90.0;
+    if (turns - turns.round()).abs() > 1e-4 {
+        return None;
+    }
+    Some((turns.round() as i64).rem_euclid(4) as u32)
+}
+
+// remaps every pixel exactly, returns the data and the new width and height
+pub fn reorient(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    orientation: Orientation,
+) -> (Vec<u8>, u32, u32) {
+    if orientation == Orientation::Rotate180 {
+        let mut result = Vec::with_capacity(data.len());
+        for pixel in data.chunks_exact(4).rev() {
+            result.extend_from_slice(pixel);
+        }
+        return (result, width, height);
+    }
+
+    // every other orientation swaps the axes
+    let out_width = height;
+    let out_height = width;
+    let mut result = vec![0u8; data.len()];
+
+    for by in (0..height).step_by(BLOCK as usize) {
+        for bx in (0..width).step_by(BLOCK as usize) {
+            for y in by..(by + BLOCK).min(height) {
+                for x in bx..(bx + BLOCK).min(width) {
+                    let (dx, dy) = match orientation {
+                        Orientation::Rotate90 => (height - 1 - y, x),
+                        Orientation::Rotate270 => (y, width - 1 - x),
+                        Orientation::Transpose => (y, x),
+                        Orientation::Transverse => (height - 1 - y, width - 1 - x),
+                        Orientation::Rotate180 => unreachable!(),
+                    };
+
+                    let src_idx = ((y * width + x) * 4) as usize;
+                    let dst_idx = ((dy * out_width + dx) * 4) as usize;
+                    result[dst_idx..dst_idx + 4].copy_from_slice(&data[src_idx..src_idx + 4]);
+                }
+            }
+        }
+    }
+
+    (result, out_width, out_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 3x2 image whose red channel holds the pixel index
+    fn indexed() -> Vec<u8> {
+        (0..6u8).flat_map(|i| [i, 0, 0, 255]).collect()
+    }
+
+    fn reds(data: &[u8]) -> Vec<u8> {
+        data.chunks_exact(4).map(|p| p[0]).collect()
+    }
+
+    #[test]
+    fn test_rotations() {
+        let data = indexed();
+
+        let (out, w, h) = reorient(&data, 3, 2, Orientation::Rotate90);
+        assert_eq!((w, h), (2, 3));
+        assert_eq!(reds(&out), vec![3, 0, 4, 1, 5, 2]);
+
+        let (out, w, h) = reorient(&data, 3, 2, Orientation::Rotate180);
+        assert_eq!((w, h), (3, 2));
+        assert_eq!(reds(&out), vec![5, 4, 3, 2, 1, 0]);
+
+        let (out, w, h) = reorient(&data, 3, 2, Orientation::Rotate270);
+        assert_eq!((w, h), (2, 3));
+        assert_eq!(reds(&out), vec![2, 5, 1, 4, 0, 3]);
+    }
+
+    #[test]
+    fn test_transpose_and_transverse() {
+        let data = indexed();
+
+        let (out, _, _) = reorient(&data, 3, 2, Orientation::Transpose);
+        assert_eq!(reds(&out), vec![0, 3, 1, 4, 2, 5]);
+
+        let (out, _, _) = reorient(&data, 3, 2, Orientation::Transverse);
+        assert_eq!(reds(&out), vec![5, 2, 4, 1, 3, 0]);
+    }
+
+    #[test]
+    fn test_quarter_turns_from_degrees() {
+        assert_eq!(quarter_turns(0.0), Some(0));
+        assert_eq!(quarter_turns(90.0), Some(1));
+        assert_eq!(quarter_turns(-90.0), Some(3));
+        assert_eq!(quarter_turns(540.0), Some(2));
+        assert_eq!(quarter_turns(45.0), None);
+    }
+}
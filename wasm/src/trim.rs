@@ -0,0 +1,138 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrimMode {
+    // pixels whose alpha is at or below the tolerance
+    Transparent,
+    // pixels matching the most common corner colour
+    Corners,
+    // pixels matching an explicit rgba colour
+    Color([u8; 4]),
+}
+
+impl TrimMode {
+    pub fn from_string(s: &str, color: Option<[u8; 4]>) -> Option<TrimMode> {
+        match s {
+            "transparent" => Some(TrimMode::Transparent),
+            "corners" => Some(TrimMode::Corners),
+            "color" => color.map(TrimMode::Color),
+            _ => None,
+        }
+    }
+}
+
+fn pixel(data: &[u8], width: u32, x: u32, y: u32) -> [u8; 4] {
+    let idx = ((y * width + x) * 4) as usize;
+    [data[idx], data[idx + 1], data[idx + 2], data[idx + 3]]
+}
+
+// colour match within tolerance, any two near-transparent pixels match
+fn matches(p: [u8; 4], key: [u8; 4], tolerance: u8) -> bool {
+    if p[3] <= tolerance && key[3] <= tolerance {
+        return true;
+    }
+    p.iter()
+        .zip(key.iter())
+        .all(|(&a, &b)| a.abs_diff(b) <= tolerance)
+}
+
+// the corner colour shared by most corners, ties resolved clockwise from top-left
+fn corner_color(data: &[u8], width: u32, height: u32, tolerance: u8) -> [u8; 4] {
+    let corners = [
+        pixel(data, width, 0, 0),
+        pixel(data, width, width - 1, 0),
+        pixel(data, width, width - 1, height - 1),
+        pixel(data, width, 0, height - 1),
+    ];
+
+    let mut best = corners[0];
+    let mut best_count = 0;
+    for &candidate in &corners {
+        let count = corners
+            .iter()
+            .filter(|&&c| matches(c, candidate, tolerance))
+            .count();
+        if count > best_count {
+            best = candidate;
+            best_count = count;
+        }
+    }
+    best
+}
+
+// bounding box (x, y, width, height) of every pixel that is not background
+pub fn content_bounds(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    tolerance: u8,
+    mode: TrimMode,
+) -> Option<(u32, u32, u32, u32)> {
+    let key = match mode {
+        TrimMode::Transparent => None,
+        TrimMode::Corners => Some(corner_color(data, width, height, tolerance)),
+        TrimMode::Color(color) => Some(color),
+    };
+    let is_background = |x: u32, y: u32| -> bool {
+        let p = pixel(data, width, x, y);
+        match key {
+            None => p[3] <= tolerance,
+            Some(key) => matches(p, key, tolerance),
+        }
+    };
+    let row_empty = |y: u32| (0..width).all(|x| is_background(x, y));
+
+    let top = (0..height).find(|&y| !row_empty(y))?;
+    let bottom = (top..height).rev().find(|&y| !row_empty(y))?;
+    let col_empty = |x: u32| (top..=bottom).all(|y| is_background(x, y));
+    let left = (0..width).find(|&x| !col_empty(x))?;
+    let right = (left..width).rev().find(|&x| !col_empty(x))?;
+
+    Some((left, top, right - left + 1, bottom - top + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canvas(width: u32, height: u32, fill: [u8; 4]) -> Vec<u8> {
+        (0..width * height).flat_map(|_| fill).collect()
+    }
+
+    fn paint(data: &mut [u8], width: u32, x: u32, y: u32, color: [u8; 4]) {
+        let idx = ((y * width + x) * 4) as usize;
+        data[idx..idx + 4].copy_from_slice(&color);
+    }
+
+    #[test]
+    fn test_transparent_bounds() {
+        let mut data = canvas(8, 6, [0, 0, 0, 0]);
+        paint(&mut data, 8, 2, 1, [255, 0, 0, 255]);
+        paint(&mut data, 8, 5, 3, [255, 0, 0, 255]);
+        assert_eq!(
+            content_bounds(&data, 8, 6, 0, TrimMode::Transparent),
+            Some((2, 1, 4, 3))
+        );
+    }
+
+    #[test]
+    fn test_corner_and_explicit_color() {
+        let mut data = canvas(5, 5, [250, 250, 250, 255]);
+        paint(&mut data, 5, 0, 0, [0, 0, 0, 255]);
+        paint(&mut data, 5, 3, 2, [10, 20, 30, 255]);
+
+        // three corners agree on near-white, so the black corner counts as content
+        assert_eq!(
+            content_bounds(&data, 5, 5, 8, TrimMode::Corners),
+            Some((0, 0, 4, 3))
+        );
+        assert_eq!(
+            content_bounds(&data, 5, 5, 8, TrimMode::Color([0, 0, 0, 255])),
+            Some((0, 0, 5, 5))
+        );
+    }
+
+    #[test]
+    fn test_empty_image() {
+        let data = canvas(4, 4, [0, 0, 0, 0]);
+        assert_eq!(content_bounds(&data, 4, 4, 0, TrimMode::Transparent), None);
+    }
+}
@@ -14,17 +14,17 @@ extern "C" {
 
 #[macro_export]
 macro_rules! console_log {
-    ($($t:tt)*) => (crate::utils::log(&format_args!($($t)*).to_string()))
+    ($($t:tt)*) => ($crate::utils::log(&format_args!($($t)*).to_string()))
 }
 
 #[macro_export]
 macro_rules! console_warn {
-    ($($t:tt)*) => (crate::utils::warn(&format_args!($($t)*).to_string()))
+    ($($t:tt)*) => ($crate::utils::warn(&format_args!($($t)*).to_string()))
 }
 
 #[macro_export]
 macro_rules! console_error {
-    ($($t:tt)*) => (crate::utils::error(&format_args!($($t)*).to_string()))
+    ($($t:tt)*) => ($crate::utils::error(&format_args!($($t)*).to_string()))
 }
 
 pub fn set_panic_hook() {
@@ -93,6 +93,31 @@ pub fn luminance(r: f32, g: f32, b: f32) -> f32 {
     0.2126 * r + 0.7152 * g + 0.0722 * b
 }
 
+// summed-area table with a zero row and column in front, so any window of
+// `values` adds up in o(1) with window_sum
+pub fn integral(values: &[f64], width: u32, height: u32) -> Vec<f64> {
+    let stride = (width + 1) as usize;
+    let mut table = vec![0.0f64; stride * (height + 1) as usize];
+
+    for y in 0..height as usize {
+        let mut row_sum = 0.0;
+        for x in 0..width as usize {
+            row_sum += values[y * width as usize + x];
+            table[(y + 1) * stride + x + 1] = table[y * stride + x + 1] + row_sum;
+        }
+    }
+
+    table
+}
+
+pub fn window_sum(table: &[f64], width: u32, x: u32, y: u32, w: u32, h: u32) -> f64 {
+    let stride = (width + 1) as usize;
+    let (x0, y0) = (x as usize, y as usize);
+    let (x1, y1) = ((x + w) as usize, (y + h) as usize);
+    table[y1 * stride + x1] - table[y0 * stride + x1] - table[y1 * stride + x0]
+        + table[y0 * stride + x0]
+}
+
 // converts degrees to radians
 pub fn deg_to_rad(degrees: f32) -> f32 {
     degrees * std::f32::consts::PI / 180.0
@@ -103,6 +128,58 @@ pub fn rad_to_deg(radians: f32) -> f32 {
     radians * 180.0 / std::f32::consts::PI
 }
 
+// small seeded generator (splitmix64), the same seed always gives the same sequence
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u32) -> Rng {
+        Rng(seed as u64 ^ 0x9e37_79b9_7f4a_7c15)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        ((z ^ (z >> 31)) >> 32) as u32
+    }
+
+    // uniform in 0..1
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    // uniform in 0..n, n must not be zero
+    pub fn below(&mut self, n: u32) -> u32 {
+        ((self.next_u32() as u64 * n as u64) >> 32) as u32
+    }
+}
+
+// stateless noise in 0..1 for an integer lattice point, fixed for a given seed
+pub fn hash_noise(x: i32, y: i32, seed: u32) -> f32 {
+    let mut h = (x as u32 as u64) | ((y as u32 as u64) << 32);
+    h ^= (seed as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^= h >> 31;
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
+
+// smooth value noise in 0..1 with features about `scale` pixels across
+pub fn value_noise(x: f32, y: f32, scale: f32, seed: u32) -> f32 {
+    let (x, y) = (x / scale, y / scale);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (smoothstep(0.0, 1.0, x - x0), smoothstep(0.0, 1.0, y - y0));
+    let (ix, iy) = (x0 as i32, y0 as i32);
+    let top = lerp(hash_noise(ix, iy, seed), hash_noise(ix + 1, iy, seed), fx);
+    let bottom = lerp(
+        hash_noise(ix, iy + 1, seed),
+        hash_noise(ix + 1, iy + 1, seed),
+        fx,
+    );
+    lerp(top, bottom, fy)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -126,4 +203,33 @@ mod tests {
         assert!((luminance(1.0, 1.0, 1.0) - 1.0).abs() < 0.001);
         assert!((luminance(0.0, 0.0, 0.0) - 0.0).abs() < 0.001);
     }
+
+    #[test]
+    fn test_window_sum() {
+        let values: Vec<f64> = (1..=12).map(|v| v as f64).collect();
+        let table = integral(&values, 4, 3);
+        assert_eq!(window_sum(&table, 4, 0, 0, 4, 3), 78.0);
+        // 6 + 7 + 10 + 11
+        assert_eq!(window_sum(&table, 4, 1, 1, 2, 2), 34.0);
+        assert_eq!(window_sum(&table, 4, 3, 2, 0, 1), 0.0);
+    }
+
+    #[test]
+    fn test_rng_is_seeded() {
+        let mut a = Rng::new(7);
+        let mut b = Rng::new(7);
+        let first: Vec<u32> = (0..8).map(|_| a.next_u32()).collect();
+        assert_eq!(first, (0..8).map(|_| b.next_u32()).collect::<Vec<_>>());
+        assert_ne!(first[0], Rng::new(8).next_u32());
+        assert!((0..100).all(|_| a.below(10) < 10 && a.next_f32() < 1.0));
+    }
+
+    #[test]
+    fn test_noise_is_seeded_and_smooth() {
+        assert_eq!(hash_noise(3, -4, 1), hash_noise(3, -4, 1));
+        assert_ne!(hash_noise(3, -4, 1), hash_noise(3, -4, 2));
+        let a = value_noise(10.0, 10.0, 8.0, 5);
+        let b = value_noise(10.5, 10.0, 8.0, 5);
+        assert!((0.0..1.0).contains(&a) && (a - b).abs() < 0.2);
+    }
 }
@@ -0,0 +1,337 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Interpolation {
+    Nearest,
+    Bilinear,
+    Bicubic,
+}
+
+impl Interpolation {
+    pub fn from_string(s: &str) -> Option<Interpolation> {
+        match s {
+            "nearest" => Some(Interpolation::Nearest),
+            "bilinear" => Some(Interpolation::Bilinear),
+            "bicubic" => Some(Interpolation::Bicubic),
+            _ => None,
+        }
+    }
+}
+
+// reads a premultiplied pixel, transparent outside the image
+fn fetch(data: &[u8], width: u32, height: u32, x: i32, y: i32) -> [f32; 4] {
+    if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+        return [0.0; 4];
+    }
+    let idx = ((y as u32 * width + x as u32) * 4) as usize;
+    let a = data[idx + 3] as f32 / 255.0;
+    [
+        data[idx] as f32 * a,
+        data[idx + 1] as f32 * a,
+        data[idx + 2] as f32 * a,
+        data[idx + 3] as f32,
+    ]
+}
+
+// catmull-rom weights for the four taps around t
+fn cubic_weights(t: f32) -> [f32; 4] {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    [
+        -0.5 * t3 + t2 - 0.5 * t,
+        1.5 * t3 - 2.5 * t2 + 1.0,
+        -1.5 * t3 + 2.0 * t2 + 0.5 * t,
+        0.5 * t3 - 0.5 * t2,
+    ]
+}
+
+// samples the image at a continuous position where pixel centers sit on .5
+pub fn sample(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    x: f32,
+    y: f32,
+    interpolation: Interpolation,
+) -> [u8; 4] {
+    let px = x - 0.5;
+    let py = y - 0.5;
+
+    let premul = match interpolation {
+        Interpolation::Nearest => fetch(data, width, height, x.floor() as i32, y.floor() as i32),
+        Interpolation::Bilinear => {
+            let x0 = px.floor();
+            let y0 = py.floor();
+            let fx = px - x0;
+            let fy = py - y0;
+            let (x0, y0) = (x0 as i32, y0 as i32);
+
+            let p00 = fetch(data, width, height, x0, y0);
+            let p10 = fetch(data, width, height, x0 + 1, y0);
+            let p01 = fetch(data, width, height, x0, y0 + 1);
+            let p11 = fetch(data, width, height, x0 + 1, y0 + 1);
+
+            let mut out = [0.0; 4];
+            for c in 0..4 {
+                out[c] = p00[c] * (1.0 - fx) * (1.0 - fy)
+                    + p10[c] * fx * (1.0 - fy)
+                    + p01[c] * (1.0 - fx) * fy
+                    + p11[c] * fx * fy;
+            }
+            out
+        }
+        Interpolation::Bicubic => {
+            let x0 = px.floor();
+            let y0 = py.floor();
+            let wx = cubic_weights(px - x0);
+            let wy = cubic_weights(py - y0);
+            let (x0, y0) = (x0 as i32, y0 as i32);
+
+            let mut out = [0.0; 4];
+            for (j, wyj) in wy.iter().enumerate() {
+                for (i, wxi) in wx.iter().enumerate() {
+                    let p = fetch(data, width, height, x0 - 1 + i as i32, y0 - 1 + j as i32);
+                    let w = wxi * wyj;
+                    for c in 0..4 {
+                        out[c] += p[c] * w;
+                    }
+                }
+            }
+            out
+        }
+    };
+
+    let a = premul[3].clamp(0.0, 255.0);
+    if a <= 0.0 {
+        return [0, 0, 0, 0];
+    }
+    let scale = 255.0 / a;
+    [
+        (premul[0] * scale).clamp(0.0, 255.0).round() as u8,
+        (premul[1] * scale).clamp(0.0, 255.0).round() as u8,
+        (premul[2] * scale).clamp(0.0, 255.0).round() as u8,
+        a.round() as u8,
+    ]
+}
+
+// inverts a canvas-style [a, b, c, d, e, f] affine matrix
+pub fn invert_affine(m: [f32; 6]) -> Option<[f32; 6]> {
+    let det = m[0] * m[3] - m[1] * m[2];
+    if det.abs() < 1e-8 {
+        return None;
+    }
+    let inv_det = 1.0 / det;
+    let a = m[3] * inv_det;
+    let b = -m[1] * inv_det;
+    let c = -m[2] * inv_det;
+    let d = m[0] * inv_det;
+    let e = -(a * m[4] + c * m[5]);
+    let f = -(b * m[4] + d * m[5]);
+    Some([a, b, c, d, e, f])
+}
+
+// maps source pixels to x' = a*x + c*y + e, y' = b*x + d*y + f
+pub fn warp_affine(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    matrix: [f32; 6],
+    out_width: u32,
+    out_height: u32,
+    interpolation: Interpolation,
+) -> Option<Vec<u8>> {
+    let inv = invert_affine(matrix)?;
+    let mut result = vec![0u8; (out_width * out_height * 4) as usize];
+
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let dx = x as f32 + 0.5;
+            let dy = y as f32 + 0.5;
+            let sx = inv[0] * dx + inv[2] * dy + inv[4];
+            let sy = inv[1] * dx + inv[3] * dy + inv[5];
+
+            let idx = ((y * out_width + x) * 4) as usize;
+            result[idx..idx + 4].copy_from_slice(&sample(
+                data,
+                width,
+                height,
+                sx,
+                sy,
+                interpolation,
+            ));
+        }
+    }
+
+    Some(result)
+}
+
+// solves a dense linear system with partial pivoting
+fn solve_linear<const N: usize>(mut a: [[f64; N]; N], mut b: [f64; N]) -> Option<[f64; N]> {
+    for col in 0..N {
+        let pivot = (col..N).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let pivot_row = a[col];
+        for row in (col + 1)..N {
+            let factor = a[row][col] / pivot_row[col];
+            for (value, pivot) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *value -= factor * pivot;
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+
+    let mut x = [0.0; N];
+    for row in (0..N).rev() {
+        let mut sum = b[row];
+        for k in (row + 1)..N {
+            sum -= a[row][k] * x[k];
+        }
+        x[row] = sum / a[row][row];
+    }
+    Some(x)
+}
+
+// homography taking each `from` corner onto the matching `to` corner
+pub fn homography(from: [f32; 8], to: [f32; 8]) -> Option<[f64; 9]> {
+    let mut a = [[0.0f64; 8]; 8];
+    let mut b = [0.0f64; 8];
+
+    for i in 0..4 {
+        let (x, y) = (from[i * 2] as f64, from[i * 2 + 1] as f64);
+        let (u, v) = (to[i * 2] as f64, to[i * 2 + 1] as f64);
+        a[i * 2] = [x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y];
+        a[i * 2 + 1] = [0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y];
+        b[i * 2] = u;
+        b[i * 2 + 1] = v;
+    }
+
+    let h = solve_linear(a, b)?;
+    Some([h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7], 1.0])
+}
+
+// maps src_quad in the source onto dst_quad in an out_width x out_height canvas
+#[allow(clippy::too_many_arguments)]
+pub fn warp_perspective(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    src_quad: [f32; 8],
+    dst_quad: [f32; 8],
+    out_width: u32,
+    out_height: u32,
+    interpolation: Interpolation,
+) -> Option<Vec<u8>> {
+    // map destination pixels back into the source, scaled so the projective
+    // denominator is positive inside the destination quad
+    let mut h = homography(dst_quad, src_quad)?;
+    let cx = (dst_quad[0] + dst_quad[2] + dst_quad[4] + dst_quad[6]) as f64 / 4.0;
+    let cy = (dst_quad[1] + dst_quad[3] + dst_quad[5] + dst_quad[7]) as f64 / 4.0;
+    if h[6] * cx + h[7] * cy + h[8] < 0.0 {
+        h = h.map(|v| -v);
+    }
+    let mut result = vec![0u8; (out_width * out_height * 4) as usize];
+
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let dx = x as f64 + 0.5;
+            let dy = y as f64 + 0.5;
+            let w = h[6] * dx + h[7] * dy + h[8];
+            // at or past the horizon the source lies behind the viewer, leave
+            // those pixels transparent instead of sampling a mirrored image
+            if w <= 1e-12 {
+                continue;
+            }
+            let sx = (h[0] * dx + h[1] * dy + h[2]) / w;
+            let sy = (h[3] * dx + h[4] * dy + h[5]) / w;
+
+            let idx = ((y * out_width + x) * 4) as usize;
+            result[idx..idx + 4].copy_from_slice(&sample(
+                data,
+                width,
+                height,
+                sx as f32,
+                sy as f32,
+                interpolation,
+            ));
+        }
+    }
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                data.extend_from_slice(&[(x * 20) as u8, (y * 20) as u8, 100, 255]);
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_identity_affine() {
+        let data = gradient(6, 5);
+        for interp in [
+            Interpolation::Nearest,
+            Interpolation::Bilinear,
+            Interpolation::Bicubic,
+        ] {
+            let out = warp_affine(&data, 6, 5, [1.0, 0.0, 0.0, 1.0, 0.0, 0.0], 6, 5, interp);
+            assert_eq!(out.unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn test_singular_affine() {
+        let data = gradient(2, 2);
+        let out = warp_affine(
+            &data,
+            2,
+            2,
+            [1.0, 2.0, 2.0, 4.0, 0.0, 0.0],
+            2,
+            2,
+            Interpolation::Nearest,
+        );
+        assert!(out.is_none());
+    }
+
+    #[test]
+    fn test_perspective_translation() {
+        let data = gradient(4, 4);
+        let src = [0.0, 0.0, 4.0, 0.0, 4.0, 4.0, 0.0, 4.0];
+        let dst = [2.0, 1.0, 6.0, 1.0, 6.0, 5.0, 2.0, 5.0];
+        let out = warp_perspective(&data, 4, 4, src, dst, 6, 5, Interpolation::Nearest).unwrap();
+
+        let at = |x: u32, y: u32| &out[((y * 6 + x) * 4) as usize..((y * 6 + x) * 4 + 4) as usize];
+        assert_eq!(at(0, 0), &[0, 0, 0, 0]);
+        assert_eq!(at(2, 1), &data[0..4]);
+        assert_eq!(at(5, 4), &data[60..64]);
+    }
+
+    #[test]
+    fn test_perspective_past_horizon_is_transparent() {
+        // the quad sits in the middle of the source, so mirrored positions
+        // from beyond the horizon would still land on real pixels
+        let data = gradient(12, 12);
+        let src = [4.0, 4.0, 8.0, 4.0, 8.0, 8.0, 4.0, 8.0];
+        // the slanted sides meet at (5, 5), so y = 5 is the horizon
+        let dst = [4.0, 6.0, 6.0, 6.0, 10.0, 10.0, 0.0, 10.0];
+        let out =
+            warp_perspective(&data, 12, 12, src, dst, 10, 10, Interpolation::Nearest).unwrap();
+        for (i, p) in out.chunks_exact(4).enumerate() {
+            if i / 10 < 5 {
+                assert_eq!(p, [0, 0, 0, 0], "pixel {}", i);
+            }
+        }
+        assert_eq!(out[(8 * 10 + 5) * 4 + 3], 255);
+    }
+}